@@ -0,0 +1,28 @@
+use clap::Parser;
+use std::sync::OnceLock;
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Config {
+    /// Base URL of a running ucui-server
+    #[arg(short, long, value_name = "URL", default_value = "ws://127.0.0.1:8000")]
+    server: String,
+
+    /// Time in milliseconds given to each side when "go" carries no clock
+    #[arg(long, value_name = "TIME", default_value = "60000")]
+    default_time: i64,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::parse)
+}
+
+pub fn get_server() -> String {
+    config().server.trim_end_matches('/').to_string()
+}
+
+pub fn get_default_time() -> i64 {
+    config().default_time
+}
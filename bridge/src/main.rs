@@ -0,0 +1,224 @@
+//! A UCI engine on stdin/stdout backed by a running ucui-server, so that
+//! desktop GUIs can play against the engine configured on the server.
+//!
+//! A game is played in one session on the server's `/engine` endpoint,
+//! the GUI's moves sent as they come, so that the server sees the whole
+//! game. A position that does not follow the session, e.g. after a take
+//! back or a new game, starts a new session from it.
+//!
+//! The server's engine plays by the clock: `movetime` and `depth` are
+//! given to it as the clocks of `SearchLimit::clock`. It cannot be
+//! interrupted either, so `stop` answers with its move once it comes,
+//! and `go infinite` holds the move back until `stop`.
+
+use std::{
+    io,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, FromSetup, Move, Position};
+use shakmaty_uci::{UciMessage, UciMove, UciSearchControl, UciTimeControl};
+use ucui_engine::{Score, SearchLimit};
+
+use crate::{
+    config::{get_default_time, get_server},
+    server::Session,
+};
+
+mod config;
+mod server;
+
+fn set_position(
+    fen: Option<Fen>,
+    moves: Vec<UciMove>,
+    mode: CastlingMode,
+) -> Result<Chess, String> {
+    let mut game = match fen {
+        None => Chess::default(),
+        Some(fen) => Chess::from_setup(fen.into_setup(), mode).map_err(|e| e.to_string())?,
+    };
+    for m in moves {
+        let legal = m.to_move(&game).map_err(|e| format!("{m}: {e}"))?;
+        game.play_unchecked(&legal);
+    }
+    Ok(game)
+}
+
+fn clock(
+    time_control: Option<UciTimeControl>,
+    search_control: Option<UciSearchControl>,
+) -> (i64, i64) {
+    let ms = |d: Option<std::time::Duration>| {
+        d.map(|d| d.as_millis() as i64)
+            .unwrap_or(get_default_time())
+    };
+    let limit = |limit: SearchLimit| {
+        let ms = limit.clock().num_milliseconds();
+        (ms, ms)
+    };
+    match (
+        time_control,
+        search_control.and_then(|control| control.depth),
+    ) {
+        (
+            Some(UciTimeControl::TimeLeft {
+                white_time,
+                black_time,
+                ..
+            }),
+            _,
+        ) => (ms(white_time), ms(black_time)),
+        (Some(UciTimeControl::MoveTime(movetime)), _) => limit(SearchLimit::MoveTime {
+            millis: movetime.as_millis() as u64,
+        }),
+        (_, Some(depth)) => limit(SearchLimit::Depth {
+            depth: depth.into(),
+        }),
+        _ => (get_default_time(), get_default_time()),
+    }
+}
+
+fn score_info(score: &Score) -> Option<String> {
+    match score {
+        Score::CentiPawns { score } => Some(format!("info score cp {score}")),
+        Score::Mate { moves } => Some(format!("info score mate {moves}")),
+        Score::None => None,
+    }
+}
+
+type SearchResult = Result<(Move, Score), String>;
+
+/// A search on the server.
+struct Search {
+    game: Chess,
+    /// Set once `bestmove` is sent, or not wanted anymore.
+    answered: Arc<AtomicBool>,
+    /// The result of the search when the search does not send it,
+    /// under `go infinite` or once stopped.
+    result: Receiver<SearchResult>,
+    /// Gives the session back when the search is over.
+    handle: JoinHandle<Option<Session>>,
+}
+
+fn best_move(game: &Chess, result: SearchResult) {
+    match result {
+        Ok((m, score)) => {
+            if let Some(info) = score_info(&score) {
+                println!("{info}");
+            }
+            println!("bestmove {}", m.to_uci(game.castles().mode()));
+        }
+        Err(err) => {
+            println!("info string {err}");
+            println!("bestmove 0000");
+        }
+    }
+}
+
+fn go(
+    mut session: Option<Session>,
+    game: Chess,
+    white_time: i64,
+    black_time: i64,
+    infinite: bool,
+) -> Search {
+    let answered = Arc::new(AtomicBool::new(false));
+    let (sender, result) = channel();
+    Search {
+        game: game.clone(),
+        answered: answered.clone(),
+        result,
+        handle: thread::spawn(move || {
+            let result = server::search(&mut session, &game, white_time, black_time);
+            if infinite || answered.swap(true, Ordering::SeqCst) {
+                let _ = sender.send(result);
+            } else {
+                best_move(&game, result);
+            }
+            session
+        }),
+    }
+}
+
+/// Answers a stopped search with the server's move, once it comes.
+fn stop(search: &Search) {
+    if search.answered.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    match search.result.recv() {
+        Ok(result) => best_move(&search.game, result),
+        Err(_) => println!("bestmove 0000"),
+    }
+}
+
+/// The session of a finished search, to go on with.
+fn session(search: Option<Search>) -> Option<Session> {
+    // a search still running was stopped, its session is left behind
+    search
+        .filter(|search| search.handle.is_finished())
+        .and_then(|search| search.handle.join().ok().flatten())
+}
+
+fn main() {
+    let _ = crate::config::config();
+    let mut game = Chess::default();
+    let mut mode = CastlingMode::Standard;
+    let mut search: Option<Search> = None;
+
+    for line in io::stdin().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match UciMessage::from_str(line.trim()) {
+            Ok(UciMessage::Uci) => {
+                println!("id name ucui ({})", get_server());
+                println!("id author ucui");
+                println!("option name UCI_Chess960 type check default false");
+                println!("uciok");
+            }
+            Ok(UciMessage::IsReady) => println!("readyok"),
+            Ok(UciMessage::SetOption { name, value }) if name == "UCI_Chess960" => {
+                mode = CastlingMode::from_chess960(value.as_deref() == Some("true"));
+            }
+            Ok(UciMessage::UciNewGame) => {
+                game = Chess::default();
+                // a search still running is for the last game
+                if let Some(search) = search.take() {
+                    search.answered.store(true, Ordering::SeqCst);
+                }
+            }
+            Ok(UciMessage::Position { fen, moves, .. }) => match set_position(fen, moves, mode) {
+                Ok(position) => game = position,
+                Err(err) => println!("info string invalid position: {err}"),
+            },
+            Ok(UciMessage::Go {
+                time_control,
+                search_control,
+            }) => {
+                let infinite = matches!(time_control, Some(UciTimeControl::Infinite));
+                let (white_time, black_time) = clock(time_control, search_control);
+                search = Some(go(
+                    session(search.take()),
+                    game.clone(),
+                    white_time,
+                    black_time,
+                    infinite,
+                ));
+            }
+            Ok(UciMessage::Stop) => {
+                if let Some(search) = search.as_ref() {
+                    stop(search);
+                }
+            }
+            Ok(UciMessage::Quit) => break,
+            Ok(_) | Err(_) => {}
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::net::TcpStream;
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, Color, EnPassantMode, Move, Position};
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+use ucui_engine::Score;
+use ucui_protocol::{ClientMessage, ServerMessage, PROTOCOL_VERSION};
+use ucui_utils::ColorSerde;
+
+use crate::config::get_server;
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+fn color_param(color: Color) -> &'static str {
+    match ColorSerde::from(color) {
+        ColorSerde::White => "white",
+        ColorSerde::Black => "black",
+    }
+}
+
+fn fen(game: &Chess) -> String {
+    Fen::from_position(game.clone(), EnPassantMode::Legal).to_string()
+}
+
+fn game_url(game: &Chess, engine_color: Color, white_time: i64, black_time: i64) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .append_pair("engine_color", color_param(engine_color))
+        .append_pair("fen", &fen(game))
+        .append_pair("white_time", &white_time.to_string())
+        .append_pair("black_time", &black_time.to_string())
+        .append_pair(
+            "chess960",
+            &(game.castles().mode() == CastlingMode::Chess960).to_string(),
+        )
+        .append_pair("protocol", &PROTOCOL_VERSION.to_string())
+        .finish();
+    format!("{}/engine?{query}", get_server())
+}
+
+fn read_message(socket: &mut Socket) -> Result<ServerMessage, String> {
+    loop {
+        match socket.read().map_err(|e| e.to_string())? {
+            Message::Text(text) => {
+                return serde_json::from_str(text.as_str()).map_err(|e| e.to_string())
+            }
+            Message::Close(_) => return Err("server closed the connection".into()),
+            _ => {}
+        }
+    }
+}
+
+/// A game on the server, the server's engine playing one side.
+pub struct Session {
+    socket: Socket,
+    engine_color: Color,
+    /// The position on the server.
+    position: Chess,
+}
+
+impl Session {
+    /// Starts a game from `game`, the server's engine to move.
+    fn open(game: &Chess, white_time: i64, black_time: i64) -> Result<Self, String> {
+        let url = game_url(game, game.turn(), white_time, black_time);
+        let (socket, _) = tungstenite::connect(url).map_err(|e| e.to_string())?;
+        Ok(Session {
+            socket,
+            engine_color: game.turn(),
+            position: game.clone(),
+        })
+    }
+
+    /// The opponent's move from the position on the server to `game`,
+    /// when `game` goes on with this game.
+    fn move_to(&self, game: &Chess) -> Option<Move> {
+        if game.turn() != self.engine_color || self.position.turn() == self.engine_color {
+            return None;
+        }
+        let target = fen(game);
+        self.position.legal_moves().into_iter().find(|m| {
+            let mut next = self.position.clone();
+            next.play_unchecked(m);
+            fen(&next) == target
+        })
+    }
+
+    fn send_move(&mut self, m: &Move, white_time: i64, black_time: i64) -> Result<(), String> {
+        let message = ClientMessage::Move {
+            _move: m.clone().into(),
+            white_time,
+            black_time,
+        };
+        let text = serde_json::to_string(&message).map_err(|e| e.to_string())?;
+        self.socket
+            .send(Message::text(text))
+            .map_err(|e| e.to_string())?;
+        self.position.play_unchecked(m);
+        Ok(())
+    }
+
+    fn engine_move(&mut self) -> Result<(Move, Score), String> {
+        loop {
+            match read_message(&mut self.socket)? {
+                ServerMessage::EngineMove { _move, score, .. } => {
+                    let m: Move = _move.into();
+                    self.position.play_unchecked(&m);
+                    return Ok((m, score));
+                }
+                ServerMessage::Outcome { outcome, .. } => {
+                    return Err(format!("game over: {outcome}"))
+                }
+                ServerMessage::Error { message, .. } => {
+                    return Err(format!("server error: {message}"))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.socket.close(None);
+    }
+}
+
+/// Asks the server's engine to play the side to move in `game`, in the
+/// game of `session` when `game` goes on with it, else in a new one.
+/// The session is left empty when the game cannot go on.
+pub fn search(
+    session: &mut Option<Session>,
+    game: &Chess,
+    white_time: i64,
+    black_time: i64,
+) -> Result<(Move, Score), String> {
+    let followed = session.as_ref().and_then(|session| session.move_to(game));
+    let current = match (session.take(), followed) {
+        (Some(mut current), Some(m)) => {
+            current.send_move(&m, white_time, black_time)?;
+            current
+        }
+        _ => Session::open(game, white_time, black_time)?,
+    };
+    let current = session.insert(current);
+    let result = current.engine_move();
+    if result.is_err() {
+        *session = None;
+    }
+    result
+}
@@ -2,7 +2,9 @@ use std::sync::mpsc::{channel, Receiver};
 use std::{io, thread};
 
 use crate::clock::{Clock, ClockState, SharedClock};
-use crate::config::{get_engine_color, get_start_pos, get_time_black, get_time_white};
+use crate::config::{
+    get_engine_color, get_opening_moves, get_start_pos, get_time_black, get_time_white,
+};
 use crate::engine::{connect_engine, Engine, EngineState};
 use crate::logger::Logger;
 use crate::state::{self, State, StateValue};
@@ -58,7 +60,13 @@ impl App {
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
         self.logger.init(self.store.clone());
         event_loop(self.store.clone());
-        self.store.update_game(get_start_pos().unwrap_or_default());
+        let mut game = get_start_pos().unwrap_or_default();
+        let opening = get_opening_moves();
+        for m in opening.iter() {
+            game.play_unchecked(m);
+        }
+        self.store.update_hist(opening);
+        self.store.update_game(game);
         terminal.draw(|frame| self.draw(frame))?;
         let screen_key = String::from("Screen");
         loop {
@@ -1,8 +1,15 @@
-use std::{path::PathBuf, str::FromStr, sync::OnceLock};
+use std::{path::PathBuf, sync::OnceLock};
 
 use clap::{Parser, Subcommand, ValueEnum};
 use log::LevelFilter;
-use shakmaty::{fen::Fen, Chess, Color, FromSetup};
+use shakmaty::{CastlingMode, Chess, Color, Move, Position};
+use ucui_eco::lookup_opening;
+use ucui_engine::Bot;
+use ucui_utils::{
+    chess960::{self, parse_position},
+    handicap::Handicap,
+    MoveSerde,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -11,6 +18,13 @@ pub struct Config {
     #[arg(short, long, value_name = "ENGINE")]
     engine: Option<PathBuf>,
 
+    /// Built-in bot to play against when no engine is given
+    ///
+    /// From the weakest to the strongest: random, greedy, simple,
+    /// blunders-2, blunders-4, blunders-6, blunders.
+    #[arg(long, value_name = "BOT", default_value = "blunders")]
+    bot: Bot,
+
     /// White time in seconds
     #[arg(short, long, value_name = "TIME", default_value = "600")]
     white_time: i64,
@@ -24,9 +38,36 @@ pub struct Config {
     engine_color: EngineColor,
 
     /// Optional starting position in FEN format
+    ///
+    /// X-FEN and Shredder-FEN are accepted for Chess960 positions.
     #[arg(short, long, value_name = "FEN")]
     fen: Option<String>,
 
+    /// Play Chess960 from start position NUMBER (0 to 959), or a random one
+    ///
+    /// With --fen, the position is played with Chess960 castling rules.
+    #[arg(long, value_name = "NUMBER", value_parser = clap::value_parser!(u32).range(0..960))]
+    chess960: Option<Option<u32>>,
+
+    /// Start without some of the engine's material
+    ///
+    /// One of pawn-and-move, knight, rook, queen, or remove:SQUARES
+    /// to take away whatever stands on SQUARES, e.g. remove:a8,h8.
+    #[arg(long, value_name = "HANDICAP", conflicts_with_all = ["fen", "chess960"])]
+    handicap: Option<Handicap>,
+
+    /// Start after the moves of an opening
+    ///
+    /// OPENING is an ECO code, e.g. C50, or words of its name,
+    /// e.g. 'italian game'.
+    #[arg(
+        long,
+        value_name = "OPENING",
+        value_parser = parse_opening,
+        conflicts_with_all = ["fen", "chess960", "handicap"]
+    )]
+    opening: Option<Opening>,
+
     /// Optional arguments to pass to the engine (separated by ";")
     ///
     /// Example: --engine-args '--uci;--quiet'
@@ -49,6 +90,33 @@ pub struct Config {
     uci_option: Vec<String>,
 }
 
+/// An opening to start with, its moves played from the standard start
+/// position.
+#[derive(Clone)]
+struct Opening {
+    code: String,
+    name: String,
+    moves: Vec<Move>,
+}
+
+fn parse_opening(term: &str) -> Result<Opening, String> {
+    let eco = lookup_opening(term).ok_or_else(|| format!("unknown opening '{term}'"))?;
+    let mut game = Chess::default();
+    let mut moves = Vec::new();
+    for m in eco.moves.into_iter().map(<Move as From<MoveSerde>>::from) {
+        if !game.is_legal(&m) {
+            return Err(format!("illegal move {m} in opening {}", eco.code));
+        }
+        game.play_unchecked(&m);
+        moves.push(m);
+    }
+    Ok(Opening {
+        code: eco.code,
+        name: eco.name,
+        moves,
+    })
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     Play,
@@ -95,6 +163,10 @@ pub fn get_engine() -> Option<String> {
         .and_then(|path| path.as_os_str().to_str().map(String::from))
 }
 
+pub fn get_bot() -> Bot {
+    config().bot
+}
+
 pub fn get_engine_args() -> Option<Vec<String>> {
     config()
         .engine_args
@@ -117,14 +189,54 @@ pub fn get_time_black() -> i64 {
     std::cmp::max(0, config().black_time)
 }
 
+static START_POS: OnceLock<Option<(Chess, CastlingMode)>> = OnceLock::new();
+
+/// Computed once, a random Chess960 position has to stay the same.
+fn start() -> &'static Option<(Chess, CastlingMode)> {
+    START_POS.get_or_init(|| match (config().fen.as_ref(), config().chess960) {
+        (Some(fen), chess960) => parse_position(fen, chess960.is_some()).ok(),
+        (None, Some(number)) => {
+            let number = number.unwrap_or_else(chess960::random_number);
+            log::info!("Chess960 start position {number}");
+            chess960::start_position(number).map(|game| (game, CastlingMode::Chess960))
+        }
+        (None, None) => config().handicap.as_ref().and_then(|handicap| {
+            match handicap.start_position(get_engine_color()) {
+                Ok(game) => Some((game, CastlingMode::Standard)),
+                Err(err) => {
+                    log::error!("Invalid handicap '{handicap}': {err}");
+                    None
+                }
+            }
+        }),
+    })
+}
+
+pub fn get_handicap() -> Option<Handicap> {
+    config().handicap.clone()
+}
+
 pub fn get_start_pos() -> Option<Chess> {
-    config()
-        .fen
-        .clone()
-        .and_then(|fen| Fen::from_str(&fen).ok())
-        .and_then(|fen| {
-            Chess::from_setup(fen.as_setup().clone(), shakmaty::CastlingMode::Standard).ok()
-        })
+    start().as_ref().map(|(game, _)| game.clone())
+}
+
+/// The moves of the opening to start with, played from the standard
+/// start position.
+pub fn get_opening_moves() -> Vec<Move> {
+    match config().opening.as_ref() {
+        Some(opening) => {
+            log::info!("Opening {} {}", opening.code, opening.name);
+            opening.moves.clone()
+        }
+        None => Vec::new(),
+    }
+}
+
+pub fn get_castling_mode() -> CastlingMode {
+    start()
+        .as_ref()
+        .map(|(_, mode)| *mode)
+        .unwrap_or(CastlingMode::Standard)
 }
 
 pub fn get_log_level() -> LevelFilter {
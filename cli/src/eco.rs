@@ -1,8 +1,10 @@
-use shakmaty::Move;
+use shakmaty::{CastlingMode, Move};
 use std::cmp;
 use std::collections::HashMap;
 use std::sync::OnceLock;
 
+use crate::config::get_castling_mode;
+
 pub struct Eco<'a> {
     pub code: &'a str,
     pub name: &'a str,
@@ -24,12 +26,16 @@ fn init_table() -> HashMap<String, Eco<'static>> {
     result
 }
 
+/// Openings are only known for the standard start position.
 pub fn find_eco(mlist: &[Move]) -> Option<&Eco<'_>> {
+    if get_castling_mode() == CastlingMode::Chess960 {
+        return None;
+    }
     let slen = cmp::min(MAX_MOVES, mlist.len());
     let range = 0..=slen;
     let ucis: Vec<String> = mlist
         .iter()
-        .map(|m| format!("{}", m.to_uci(shakmaty::CastlingMode::Standard)))
+        .map(|m| format!("{}", m.to_uci(CastlingMode::Standard)))
         .collect();
 
     let table = ECO_TABLE.get_or_init(init_table);
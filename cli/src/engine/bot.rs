@@ -0,0 +1,96 @@
+use std::{
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use chrono::Duration;
+use shakmaty::{fen::Fen, CastlingMode};
+use ucui_engine::Bot;
+
+use crate::{config::get_castling_mode, state::Store};
+
+use super::{Engine, EngineMessage, EngineState};
+
+struct BotEngine {
+    rx: Receiver<EngineMessage>,
+    store: Store,
+    engine: Box<dyn ucui_engine::Engine + Send>,
+}
+
+impl BotEngine {
+    fn new(bot: Bot, rx: Receiver<EngineMessage>, store: Store) -> Self {
+        let engine = ucui_engine::connect_bot(bot);
+        engine.set_chess960(get_castling_mode() == CastlingMode::Chess960);
+        BotEngine { rx, store, engine }
+    }
+
+    fn start(&self) {
+        loop {
+            match self.rx.recv() {
+                Err(err) => {
+                    log::error!("Engine channel error: {}", err);
+                    break;
+                }
+                Ok(msg) => match msg {
+                    EngineMessage::NewGame => {
+                        self.engine.new_game();
+                        self.store.update_engine(EngineState::Idle);
+                    }
+                    EngineMessage::Go {
+                        fen,
+                        white_time,
+                        black_time,
+                    } => self.go(fen, white_time, black_time),
+                    EngineMessage::Stop => {
+                        self.engine.stop();
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    fn go(&self, fen: Fen, white_time: Duration, black_time: Duration) {
+        self.store.update_engine(EngineState::Computing);
+        self.engine.go(fen.to_string(), white_time, black_time);
+        match self.engine.recv() {
+            Ok(ucui_engine::EngineMessage::BestMove { move_, .. }) => {
+                self.store
+                    .update_engine(EngineState::PendingMove(move_.into()));
+            }
+            Ok(_) => {}
+            Err(err) => log::error!("<{}> {}", self.engine.name(), err),
+        }
+    }
+}
+
+pub struct BotConnection {
+    tx: Sender<EngineMessage>,
+}
+
+impl Engine for BotConnection {
+    fn new_game(&self) {
+        let _ = self.tx.send(EngineMessage::NewGame);
+    }
+
+    fn stop(&self) {
+        let _ = self.tx.send(EngineMessage::Stop);
+    }
+
+    fn go(&self, fen: Fen, white_time: Duration, black_time: Duration) {
+        let _ = self.tx.send(EngineMessage::Go {
+            fen,
+            white_time,
+            black_time,
+        });
+    }
+}
+
+pub fn connect_engine(bot: Bot, store: Store) -> BotConnection {
+    let (tx, rx) = channel::<EngineMessage>();
+    thread::spawn(move || {
+        let engine = BotEngine::new(bot, rx, store);
+        engine.start();
+    });
+    BotConnection { tx }
+}
@@ -1,10 +1,12 @@
 use chrono::Duration;
 use shakmaty::{fen::Fen, Move};
 
-use crate::{config::get_engine, state::Store};
+use crate::{
+    config::{get_bot, get_engine},
+    state::Store,
+};
 
-mod blunders;
-// mod simple;
+mod bot;
 mod uci;
 
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
@@ -38,6 +40,6 @@ pub fn connect_engine(store: Store) -> Box<dyn Engine> {
     if let Some(engine_path) = get_engine() {
         Box::new(uci::connect_engine(&engine_path, store))
     } else {
-        Box::new(blunders::connect_engine(store))
+        Box::new(bot::connect_engine(get_bot(), store))
     }
 }
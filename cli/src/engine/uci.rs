@@ -5,11 +5,11 @@ use std::{
 };
 
 use chrono::Duration;
-use shakmaty::{fen::Fen, Chess};
+use shakmaty::{fen::Fen, CastlingMode, Chess};
 use shakmaty_uci::{UciMessage, UciMove};
 
 use crate::{
-    config::{get_engine_args, get_engine_options},
+    config::{get_castling_mode, get_engine_args, get_engine_options},
     state::Store,
 };
 
@@ -31,6 +31,9 @@ impl UciEngine {
     }
 
     fn set_options(&self) {
+        if get_castling_mode() == CastlingMode::Chess960 {
+            let _ = self.engine.set_option("UCI_Chess960", "true");
+        }
         for (id, value) in get_engine_options() {
             let _ = self.engine.set_option(&id, &value);
         }
@@ -93,8 +96,8 @@ impl UciEngine {
         };
         let goc = shakmaty_uci::UciMessage::Go {
             time_control: Some(shakmaty_uci::UciTimeControl::TimeLeft {
-                white_time: Some(white_time.to_std().expect("positive duration")),
-                black_time: Some(black_time.to_std().expect("positive duration")),
+                white_time: Some(white_time.to_std().unwrap_or_default()),
+                black_time: Some(black_time.to_std().unwrap_or_default()),
                 white_increment: None,
                 black_increment: None,
                 moves_to_go: None,
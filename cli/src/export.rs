@@ -1,22 +1,26 @@
 // use std::{fs::File, io::Write};
 
-use shakmaty::{fen::Fen, Chess, Move, Position};
+use shakmaty::{fen::Fen, CastlingMode, Chess, Move, Position};
 
-use crate::{config::get_start_pos, turn::Turn};
+use crate::{
+    config::{get_castling_mode, get_handicap, get_start_pos},
+    turn::Turn,
+};
 
 pub fn export_pgn(game: &Chess, move_list: &Vec<Move>) -> String {
     let now = chrono::Utc::now();
     let date_format = now.format("%Y.%m.%d");
-    let mut turn = Turn::new(
-        get_start_pos().unwrap_or_default(),
-        move_list,
-    )
-    .seps(String::from(" "), String::from(" "))
-    .without_outcome();
+    let mut turn = Turn::new(get_start_pos().unwrap_or_default(), move_list)
+        .seps(String::from(" "), String::from(" "))
+        .without_outcome();
 
     let mut parts: Vec<String> = vec![];
 
-    let headers = format!("[Event \"Me vs Engine\"]\n[Date \"{date_format}\"]\n");
+    let event = match get_handicap() {
+        Some(handicap) => format!("Me vs Engine, {}", handicap.description()),
+        None => String::from("Me vs Engine"),
+    };
+    let headers = format!("[Event \"{event}\"]\n[Date \"{date_format}\"]\n");
     parts.push(headers);
     if let Some(outcome) = game.outcome() {
         let result = format!("[Result \"{outcome}\"]\n");
@@ -25,10 +29,14 @@ pub fn export_pgn(game: &Chess, move_list: &Vec<Move>) -> String {
         parts.push("[Result \"*\"]\n".to_string());
     }
 
+    if get_castling_mode() == CastlingMode::Chess960 {
+        parts.push("[Variant \"Chess960\"]\n".to_string());
+    }
+
     if let Some(start_pos) = get_start_pos() {
         let fen = export_fen(&start_pos);
         parts.push(format!("[FEN \"{fen}\"]\n"));
-        parts.push("[SetUp 1]\n".to_string());
+        parts.push("[SetUp \"1\"]\n".to_string());
     }
 
     let start = format!("\n{}", turn.format_move());
@@ -28,7 +28,7 @@ pub enum RoleDef {
 
 mod role_option {
     use super::RoleDef;
-    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use serde::{Serialize, Serializer};
     use shakmaty::Role;
 
     pub fn serialize<S>(value: &Option<Role>, serializer: S) -> Result<S::Ok, S::Error>
@@ -40,20 +40,9 @@ mod role_option {
 
         value.as_ref().map(Helper).serialize(serializer)
     }
-
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Role>, D::Error>
-    where
-        D: Deserializer<'de>,
-    {
-        #[derive(Deserialize)]
-        struct Helper(#[serde(with = "RoleDef")] Role);
-
-        let helper = Option::deserialize(deserializer)?;
-        Ok(helper.map(|Helper(external)| external))
-    }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize)]
 #[serde(remote = "Move")]
 #[serde(tag = "_tag")]
 pub enum MoveDef {
@@ -91,6 +80,3 @@ pub enum MoveDef {
 
 #[derive(Serialize)]
 pub struct MoveSer(#[serde(with = "MoveDef")] pub Move);
-
-// #[derive(Deserialize)]
-// pub struct MoveDe(#[serde(with = "MoveDef")] pub Move);
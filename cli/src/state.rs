@@ -12,6 +12,7 @@ use shakmaty::{fen::Fen, Chess, FromSetup, Move};
 
 use crate::{
     clock::ClockState,
+    config::get_castling_mode,
     engine::EngineState,
     ui::Screen,
     util::{alpha_to_i, MoveIndex},
@@ -60,11 +61,8 @@ pub enum StateValue {
 
 impl State {
     pub fn game(&self) -> Chess {
-        Chess::from_setup(
-            self.fen.as_setup().clone(),
-            shakmaty::CastlingMode::Standard,
-        )
-        .expect("State is not supposed to hold an invalid FEN position.")
+        Chess::from_setup(self.fen.as_setup().clone(), get_castling_mode())
+            .expect("State is not supposed to hold an invalid FEN position.")
     }
     #[allow(unused)]
     pub fn input_move(&self) -> Option<usize> {
@@ -117,56 +115,22 @@ impl State {
             ];
             for key in keys {
                 match key {
-                    "Screen" => {
-                        if self.screen != other.screen {
-                            diff.push("Screen".into());
-                        }
+                    "Screen" if self.screen != other.screen => diff.push("Screen".into()),
+                    "Fen" if self.fen != other.fen => diff.push("Fen".into()),
+                    "Hist" if self.hist != other.hist => diff.push("Hist".into()),
+                    "Clock" if self.clock != other.clock => diff.push("Clock".into()),
+                    "Engine" if self.engine != other.engine => diff.push("Engine".into()),
+                    "AvailInput" if self.avail_input != other.avail_input => {
+                        diff.push("AvailInput".into())
                     }
-                    "Fen" => {
-                        if self.fen != other.fen {
-                            diff.push("Fen".into());
-                        }
+                    "ValidateInput" if self.validate_input != other.validate_input => {
+                        diff.push("ValidateInput".into())
                     }
-                    "Hist" => {
-                        if self.hist != other.hist {
-                            diff.push("Hist".into());
-                        }
-                    }
-                    "Clock" => {
-                        if self.clock != other.clock {
-                            diff.push("Clock".into());
-                        }
-                    }
-                    "Engine" => {
-                        if self.engine != other.engine {
-                            diff.push("Engine".into());
-                        }
-                    }
-                    "AvailInput" => {
-                        if self.avail_input != other.avail_input {
-                            diff.push("AvailInput".into());
-                        }
-                    }
-                    "ValidateInput" => {
-                        if self.validate_input != other.validate_input {
-                            diff.push("ValidateInput".into());
-                        }
-                    }
-                    "Log" => {
-                        if self.log != other.log {
-                            diff.push("Log".into());
-                        }
-                    }
-                    "GameStarted" => {
-                        if self.game_started != other.game_started {
-                            diff.push("GameStarted".into());
-                        }
-                    }
-                    "Exit" => {
-                        if self.exit != other.exit {
-                            diff.push("Exit".into());
-                        }
+                    "Log" if self.log != other.log => diff.push("Log".into()),
+                    "GameStarted" if self.game_started != other.game_started => {
+                        diff.push("GameStarted".into())
                     }
+                    "Exit" if self.exit != other.exit => diff.push("Exit".into()),
                     _ => {}
                 };
             }
@@ -234,7 +198,9 @@ impl Store {
         V: Into<Vec<StateValue>>,
     {
         let _ = self.updater.send(values.into());
-        let _ = self.current_state().map(|state| self.signal.send(state));
+        if let Ok(state) = self.current_state() {
+            let _ = self.signal.send(state);
+        }
     }
 
     fn update(&self, value: StateValue) {
@@ -252,7 +218,6 @@ impl Store {
     pub fn update_game(&self, value: Chess) {
         self.update_fen(Fen::from_position(value, shakmaty::EnPassantMode::Always));
     }
-    #[allow(unused)]
     pub fn update_hist(&self, value: Vec<Move>) {
         self.update(StateValue::Hist(value));
     }
@@ -169,10 +169,8 @@ fn handle_key_event_on_play(store: &Store, state: &State, key_event: KeyEvent) {
                             store.update_input(MoveIndex::Full(r, next_index(len, i)));
                         }
                     }
-                    MoveIndex::Role(r) => {
-                        if !map.get_line(&r).is_empty() {
-                            store.update_input(MoveIndex::Full(r, 0));
-                        }
+                    MoveIndex::Role(r) if !map.get_line(&r).is_empty() => {
+                        store.update_input(MoveIndex::Full(r, 0));
                     }
                     _ => {}
                 }
@@ -1,12 +1,13 @@
 use crate::state::State;
 use crate::util::role::role_letter;
-use crate::util::{self, check_rect, px_width, shrink_rect, MoveIndex, MoveMap, ROLE_LIST};
+use crate::util::{self, check_rect, px_width, shrink_rect, MoveIndex, MoveMap};
 use ratatui::style::{Color as UiColor, Style, Stylize};
 use ratatui::widgets::Padding;
 use ratatui::{layout::Rect, widgets::Block, Frame};
 use shakmaty::san::San;
 use shakmaty::{Chess, Move, Position, Role};
 use tui_big_text::BigText;
+use ucui_utils::ROLE_LIST;
 
 // #[derive(Debug, Clone)]
 // struct PossibleMove {
@@ -168,7 +169,7 @@ fn render_input_border(
 
     let inner_area = border.inner(area);
     let row_count = inner_area.width / move_width;
-    let more = if len % row_count > 0 { 1 } else { 0 };
+    let more = if len.is_multiple_of(row_count) { 0 } else { 1 };
     let height = (((len / row_count) + more) * MOVE_HEIGHT) + 2 * pad;
     frame.render_widget(border, check_rect(area, Rect { height, ..area }));
 
@@ -1,10 +1,11 @@
 use ratatui::layout::Rect;
-use shakmaty::{Chess, Move, Position, Role, Square};
+use shakmaty::{san::San, Chess, Move, Position, Role, Square};
 use std::{
     cmp::Ordering,
     collections::{linked_list, LinkedList},
 };
 use tui_big_text::PixelSize;
+use ucui_utils::{notation, ROLE_LIST};
 
 #[allow(unused)]
 const ALPHA: [char; 26] = [
@@ -36,24 +37,13 @@ pub fn alpha_to_i(a: &str) -> Result<usize, &str> {
     Err("failed to parse alpha")
 }
 
+/// SAN with check and mate markers, those of `pos` itself when the
+/// move is already played.
 pub fn san_format_move(pos: &Chess, m: &Move, already_played: bool) -> String {
-    use shakmaty::san::San;
-    let san_string = San::from_move(pos, m).to_string();
-    let played = if already_played {
-        Ok(pos.clone())
+    if already_played {
+        format!("{}{}", San::from_move(pos, m), notation::suffix(pos))
     } else {
-        pos.clone().play(m)
-    };
-    match played {
-        Err(_) => san_string,
-        Ok(pos) => {
-            if pos.is_checkmate() {
-                return format!("{}#", san_string);
-            } else if pos.is_check() {
-                return format!("{}+", san_string);
-            }
-            san_string
-        }
+        notation::san(pos, m)
     }
 }
 
@@ -62,15 +52,6 @@ pub struct MoveMap {
     moves: Vec<(Role, usize)>,
 }
 
-pub const ROLE_LIST: [Role; 6] = [
-    Role::Pawn,
-    Role::Bishop,
-    Role::Knight,
-    Role::Rook,
-    Role::Queen,
-    Role::King,
-];
-
 fn next_role_raw(r: Role) -> Role {
     match r {
         Role::Pawn => Role::Bishop,
@@ -224,7 +205,7 @@ pub enum PaddingMod {
 }
 
 fn u16add(a: u16, b: u16) -> u16 {
-    a.checked_add(b).unwrap_or(u16::MAX)
+    a.saturating_add(b)
 }
 
 // fn u16min(a: u16, b: u16) -> u16 {
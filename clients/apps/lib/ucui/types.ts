@@ -303,14 +303,6 @@ export const gameConfig = (
   position = null as Nullable<string>
 ): GameConfig => ({ black, white, engineColor, fen: position });
 
-export type Eco = {
-  name: string;
-  code: string;
-  fen: string;
-  moves: Move[];
-  pgn: string;
-};
-
 export type SavedGame = {
   hist: MoveHist[];
   config: GameConfig;
@@ -1,6 +1,8 @@
 import { events, removeElement } from "../lib/dom";
 import { AcNode, addClass, DIV, replaceNodeContent } from "../lib/html";
 import { fromNullable, map } from "../lib/option";
+import type { GameSummary } from "../lib/ucui/protocol/GameSummary";
+import type { MonitorMessage } from "../lib/ucui/protocol/MonitorMessage";
 import { Color, Nullable, Role } from "../lib/ucui/types";
 import { fenToRanks, OccupProc } from "./fen";
 import "./style.css";
@@ -8,9 +10,8 @@ import "./style.css";
 let socket: Nullable<WebSocket> = null;
 const CONNECT_TIMEOUT = 4000;
 
-type MessageUpdate = { readonly _tag: "Update"; games: [string, string][] };
-type MessageInit = { readonly _tag: "Init"; games: [string, string][] };
-type Message = MessageInit | MessageUpdate;
+type MessageInit = Extract<MonitorMessage, { _tag: "Init" }>;
+type MessageDiff = Extract<MonitorMessage, { _tag: "Diff" }>;
 
 const socketURL = () => {
   const host = document.location.hostname;
@@ -77,13 +78,13 @@ const connect = (setStatus: (...values: AcNode[]) => void) =>
   });
 
 const handleIcoming = (event: MessageEvent) => {
-  const message = JSON.parse(event.data) as Message;
+  const message = JSON.parse(event.data) as MonitorMessage;
 
   switch (message._tag) {
     case "Init":
       return handleInit(message);
-    case "Update":
-      return handleUpdate(message);
+    case "Diff":
+      return handleDiff(message);
   }
 };
 
@@ -95,10 +96,10 @@ type Game = {
   status: Status;
 };
 
-const recToGame = (g: [string, string], status: Status): Game => ({
+const recToGame = (g: GameSummary, status: Status): Game => ({
   status,
-  key: mkId(g[0]),
-  fen: g[1],
+  key: mkId(g.id),
+  fen: g.fen,
 });
 
 let state: Game[] = [];
@@ -112,25 +113,30 @@ const mkId = (s: string) => "id-" + s;
 
 const handleInit = (message: MessageInit) => {
   console.log("Init", message.games);
-  state = message.games.map((g) => recToGame(g, "new"));
-  updateView();
-};
-
-const handleUpdate = (message: MessageUpdate) => {
-  const incomingKeys = message.games.map(([key, _]) => mkId(key));
-
-  const games = message.games.map(([key, fen]) => {
-    const game = findGame(mkId(key));
-    if (game) {
-      return recToGame([key, fen], "ongoing");
-    }
-    return recToGame([key, fen], "new");
-  });
-
+  const incomingKeys = message.games.map(({ id }) => mkId(id));
+  const games = message.games.map((summary) =>
+    recToGame(summary, findGame(mkId(summary.id)) ? "ongoing" : "new")
+  );
   const oldGames = state
     .filter((g) => !incomingKeys.includes(g.key))
     .map<Game>((g) => ({ ...g, status: "end" }));
   state = games.concat(oldGames);
+  updateView();
+};
+
+const handleDiff = (message: MessageDiff) => {
+  const removed = message.removed.map(mkId);
+  const changed = message.changed.map((summary) =>
+    recToGame(summary, "ongoing")
+  );
+  state = state
+    .map<Game>((g) =>
+      removed.includes(g.key)
+        ? { ...g, status: "end" }
+        : changed.find((c) => c.key === g.key) ??
+          ({ ...g, status: g.status === "new" ? "ongoing" : g.status } as Game)
+    )
+    .concat(message.added.map((summary) => recToGame(summary, "new")));
 
   updateView();
 };
@@ -1,10 +1,11 @@
 import { events, emptyElement } from "../lib/dom";
 import { DIV, replaceNodeContent, INPUT, AcNode } from "../lib/html";
 import { makeMoveOnFen } from "../lib/ucui/board";
-import { Eco, FEN_INITIAL_POSITION, moveHist } from "../lib/ucui/types";
+import type { Eco } from "../lib/ucui/protocol/Eco";
+import { FEN_INITIAL_POSITION, moveHist } from "../lib/ucui/types";
 import { iife } from "../lib/util";
 import { startGameWithMoves } from "./game";
-import { connect } from "./play";
+import { connect, isBoardMove } from "./play";
 import { assign, dispatch, get, subscribe } from "./store";
 import { UrlQuery, withQueryString } from "./util";
 
@@ -53,12 +54,16 @@ const startGameFromEco = (eco: Eco) => {
   dispatch("gameConfig", (state) => ({ ...state, fen: eco.fen }));
   connect()
     .then(() => {
-      const firstMove = eco.moves[0];
-      const moveList = eco.moves.slice(1).reduce(
+      const moves = eco.moves.filter(isBoardMove);
+      if (moves.length < eco.moves.length) {
+        throw new Error(`Opening ${eco.code} drops pieces`);
+      }
+      const firstMove = moves[0];
+      const moveList = moves.slice(1).reduce(
         (acc, move, index) => {
           const { resultingFen: fen } = acc[acc.length - 1];
           const newFen =
-            index === eco.moves.length - 2 ? eco.fen : makeMoveOnFen(fen, move);
+            index === moves.length - 2 ? eco.fen : makeMoveOnFen(fen, move);
           return acc.concat(moveHist(move, [], newFen));
         },
         [
@@ -1,6 +1,7 @@
 import { attrs, events } from "../lib/dom";
 import { replaceNodeContent, SPAN, DIV } from "../lib/html";
 import { EngineScore } from "../lib/ucui/types";
+import { MessageHint, requestHint } from "./play";
 import { formatMove } from "./san";
 import { assign, get, getPlayerColor, getTurn, subscribe } from "./store";
 
@@ -38,6 +39,17 @@ const renderScore = (score: EngineScore) => {
   }
 };
 
+const renderHint = (hint: MessageHint) => {
+  switch (hint.level) {
+    case "Piece":
+      return DIV("hint", `Hint: ${hint.role} on ${hint.from ?? "hand"}`);
+    case "Destination":
+      return DIV("hint", `Hint: to ${hint.to}`);
+    case "Move":
+      return DIV("hint", `Hint: ${hint.san}`);
+  }
+};
+
 const render = (
   engineInfo: HTMLElement,
   engineScore: HTMLElement,
@@ -53,7 +65,11 @@ const render = (
     case "idle": {
       const turn = getTurn();
       if (turn == getPlayerColor()) {
-        return setEngine(DIV("idle", `Your turn to play ${turn}`));
+        const hint = get("hint");
+        return setEngine(
+          DIV("idle", `Your turn to play ${turn}`),
+          ...(hint === null ? [] : [renderHint(hint)])
+        );
       }
       return setEngine(DIV("idle", `Engine to play ${turn}`));
     }
@@ -76,12 +92,18 @@ export const mountEngine = (root: HTMLElement) => {
     add("click", () => assign("screen", "movelist"))
   );
 
+  // the piece first, then the whole move
+  const hintButton = events(DIV("to-hint to-button", "?"), (add) =>
+    add("click", () => requestHint(get("hint") === null ? "Piece" : "Move"))
+  );
+
   render(engineInfo, engineScore, engineState);
   const engine = DIV(
     "engine",
     engineInfo,
     engineScore,
     engineState,
+    hintButton,
     toListButton
   );
 
@@ -89,6 +111,7 @@ export const mountEngine = (root: HTMLElement) => {
 
   subscribe(
     "engine",
-    "engineName"
+    "engineName",
+    "hint"
   )(() => render(engineInfo, engineScore, engineState));
 };
@@ -4,38 +4,40 @@ import {
   Nullable,
   position,
   engineCompute,
+  engineIdle,
   engineMove,
   moveHist,
   inputNone,
-  FEN_INITIAL_POSITION,
-  EngineScore,
 } from "../lib/ucui/types";
+import type { ClientMessage } from "../lib/ucui/protocol/ClientMessage";
+import type { HintLevel } from "../lib/ucui/protocol/HintLevel";
+import type { Move as ProtocolMove } from "../lib/ucui/protocol/Move";
+import type { ServerMessage } from "../lib/ucui/protocol/ServerMessage";
+import {
+  MIN_PROTOCOL_VERSION,
+  PROTOCOL_VERSION,
+} from "../lib/ucui/protocol/version";
 import { isPrivateIP } from "../lib/util";
 import { playSound } from "./sound";
 import { assign, dispatch, get } from "./store";
 import { withQueryString } from "./util";
 
-type Outcome = "½-½" | "1-0" | "0-1";
-type MessageReady = {
-  readonly _tag: "Ready";
-  name: string;
-  turn: Color;
-  legalMoves: Move[];
-};
-type MessagePosition = {
-  readonly _tag: "Position";
-  legalMoves: Move[];
-  fen: string;
-};
-type MessageEngineMove = {
-  readonly _tag: "EngineMove";
-  move: Move;
-  from: Move[];
-  check: string;
-  fen: string;
-  score: EngineScore;
-};
-type MessageOutcome = { readonly _tag: "Outcome"; outcome: Outcome };
+type Message<Tag extends ServerMessage["_tag"]> = Extract<
+  ServerMessage,
+  { _tag: Tag }
+>;
+type MessageReady = Message<"Ready">;
+type MessagePosition = Message<"Position">;
+type MessageEngineMove = Message<"EngineMove">;
+export type MessageHint = Message<"Hint">;
+type MessageOutcome = Message<"Outcome">;
+type MessageError = Message<"Error">;
+export type { HintLevel };
+
+// the server speaks of drops as well, which the board does not play
+export const isBoardMove = (move: ProtocolMove): move is Move =>
+  move._tag !== "Put";
+const moves = (list: ProtocolMove[]) => list.filter(isBoardMove);
 
 let socket: Nullable<WebSocket> = null;
 
@@ -54,35 +56,51 @@ const socketURL = () => {
     fen,
     white_time: white,
     black_time: black,
+    protocol: PROTOCOL_VERSION,
   });
 };
 
 const handleReady = (message: MessageReady) => {
+  if (
+    message.protocol < MIN_PROTOCOL_VERSION ||
+    message.protocol > PROTOCOL_VERSION
+  ) {
+    console.warn(
+      `Server speaks protocol version ${message.protocol}, we speak ${PROTOCOL_VERSION}`
+    );
+  }
   const config = get("gameConfig");
   assign("started", true);
   assign("engineName", message.name);
-  assign(
-    "position",
-    position(message.legalMoves, config.fen ?? FEN_INITIAL_POSITION)
-  );
+  assign("hint", null);
+  // the position after the moves given at connection, if any
+  assign("position", position(moves(message.legalMoves), message.fen));
   if (message.turn === config.engineColor) {
     assign("engine", engineCompute());
   }
 };
 const handlePosition = (message: MessagePosition) => {
   console.debug("handlePosition", message);
-  assign("position", position(message.legalMoves, message.fen));
+  assign("position", position(moves(message.legalMoves), message.fen));
 };
 const handleEngineMove = (message: MessageEngineMove) => {
   console.debug("handleEngineMove", message);
+  const move = message.move;
+  if (!isBoardMove(move)) {
+    console.error("The board cannot play the engine's drop", move);
+    return;
+  }
   playSound();
-  assign(
-    "engine",
-    engineMove(message.move, message.from, message.score, message.check)
-  );
+  const from = moves(message.from);
+  assign("engine", engineMove(move, from, message.score, message.check));
   dispatch("moveList", (list) =>
-    list.concat(moveHist(message.move, message.from, message.fen))
+    list.concat(moveHist(move, from, message.fen))
   );
+  assign("hint", null);
+};
+const handleHint = (message: MessageHint) => {
+  console.debug("handleHint", message);
+  assign("hint", message);
 };
 const handleOutcome = (message: MessageOutcome) => {
   console.debug("handleOutcome", message);
@@ -92,21 +110,38 @@ const handleOutcome = (message: MessageOutcome) => {
   assign("screen", "movelist");
 };
 
+const handleError = (message: MessageError) => {
+  console.error(
+    `Server error ${message.code}: ${message.message}`,
+    message.context
+  );
+  // a refused move leaves the turn to the player, the position follows
+  if (message.code === "IllegalMove" || message.code === "InvalidTime") {
+    assign("engine", engineIdle());
+  }
+};
+
 const handleIcoming = (event: MessageEvent) => {
-  const message = JSON.parse(event.data);
+  const message = JSON.parse(event.data) as ServerMessage;
 
   switch (message._tag) {
     case "Ready":
-      return handleReady(message as MessageReady);
+      return handleReady(message);
     case "Position":
-      return handlePosition(message as MessagePosition);
+      return handlePosition(message);
     case "EngineMove":
-      return handleEngineMove(message as MessageEngineMove);
+      return handleEngineMove(message);
+    case "Hint":
+      return handleHint(message);
     case "Outcome":
-      return handleOutcome(message as MessageOutcome);
+      return handleOutcome(message);
+    case "Error":
+      return handleError(message);
   }
 };
 
+const send = (message: ClientMessage) => socket?.send(JSON.stringify(message));
+
 const CONNECT_TIMEOUT = 4000;
 
 export const disconnect = () => {
@@ -141,14 +176,12 @@ export const sendMove = (move: Move) => {
       const clock = get("clock");
       assign("engine", engineCompute());
       if (clock._tag === "running") {
-        socket.send(
-          JSON.stringify({
-            _tag: "Move",
-            move,
-            white_time: clock.remaining_white,
-            black_time: clock.remaining_black,
-          })
-        );
+        send({
+          _tag: "Move",
+          move,
+          white_time: clock.remaining_white,
+          black_time: clock.remaining_black,
+        });
         assign("input", inputNone());
       }
     }
@@ -156,3 +189,9 @@ export const sendMove = (move: Move) => {
     console.error("game has not started");
   }
 };
+
+export const requestHint = (level: HintLevel) => {
+  if (get("started") && socket !== null) {
+    send({ _tag: "Hint", level });
+  }
+};
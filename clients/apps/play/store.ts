@@ -10,15 +10,16 @@ import {
   MoveHist,
   ClockState,
   clockInitial,
-  Eco,
   defaultEngine,
   Nullable,
   SavedGame,
   FEN_INITIAL_POSITION,
 } from "../lib/ucui/types";
+import type { Eco } from "../lib/ucui/protocol/Eco";
 import { isPrivateIP } from "../lib/util";
 
 import { startingLegalMoves } from "./data";
+import type { MessageHint } from "./play";
 
 export const getTurn = (): Color =>
   get("moveList").length % 2 === 0 ? "white" : "black";
@@ -49,6 +50,7 @@ let state = {
   engineName: "??",
   lockScreen: false,
   outcome: null as Nullable<string>,
+  hint: null as Nullable<MessageHint>,
   gameConfig: defaultGameConfig(),
   ecoResult: defaultEcoList(),
   savedGames: [] as SavedGame[],
@@ -1,18 +1,9 @@
-use serde::{Deserialize, Serialize};
 use shakmaty::Move;
 use std::cmp;
 use std::collections::HashMap;
 use std::sync::OnceLock;
-use ucui_utils::MoveSerde;
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Eco {
-    pub code: String,
-    pub name: String,
-    pub fen: String,
-    pub moves: Vec<MoveSerde>,
-    pub pgn: String,
-}
+
+pub use ucui_protocol::Eco;
 
 const MAX_MOVES: usize = 36;
 
@@ -25,13 +16,28 @@ fn init_table() -> HashMap<String, Eco> {
         .expect("Getting eco data should go smoothly")
 }
 
-pub fn find_eco_from_moves(mlist: &[Move]) -> Option<&Eco> {
-    let slen = cmp::min(MAX_MOVES, mlist.len());
-    let range = 0..=slen;
-    let ucis: Vec<String> = mlist
+/// Moves in UCI notation, as in the table.
+fn ucis(mlist: &[Move]) -> Vec<String> {
+    mlist
         .iter()
         .map(|m| format!("{}", m.to_uci(shakmaty::CastlingMode::Standard)))
-        .collect();
+        .collect()
+}
+
+/// The key of a sequence of moves from the start position, the way
+/// lines are keyed in the table.
+pub fn sequence_key(mlist: &[Move]) -> String {
+    ucis(mlist).concat()
+}
+
+pub fn find_eco_from_moves(mlist: &[Move]) -> Option<&Eco> {
+    find_eco_from_ucis(&ucis(mlist))
+}
+
+/// Like `find_eco_from_moves`, with moves in UCI notation.
+pub fn find_eco_from_ucis(ucis: &[String]) -> Option<&'static Eco> {
+    let slen = cmp::min(MAX_MOVES, ucis.len());
+    let range = 0..=slen;
 
     let table = ECO_TABLE.get_or_init(init_table);
     // make keys from longest to shortest
@@ -53,6 +59,28 @@ pub fn find_eco_from_moves(mlist: &[Move]) -> Option<&Eco> {
     None
 }
 
+/// Piece placement, turn and castling rights, what positions reached by
+/// different move orders have in common.
+fn position_key(fen: &str) -> Vec<&str> {
+    fen.split_whitespace().take(3).collect()
+}
+
+/// The opening reaching the position of `fen`, by any move order, the
+/// shortest line first.
+pub fn find_eco_from_fen(fen: &str) -> Option<&'static Eco> {
+    let key = position_key(fen);
+    let table = ECO_TABLE.get_or_init(init_table);
+    table
+        .values()
+        .filter(|eco| position_key(&eco.fen) == key)
+        .min_by(|a, b| {
+            a.moves
+                .len()
+                .cmp(&b.moves.len())
+                .then_with(|| a.code.cmp(&b.code))
+        })
+}
+
 pub fn lookup_eco_from_name(pat: &str) -> Vec<Eco> {
     let table = ECO_TABLE.get_or_init(init_table);
     let pat_list: Vec<String> = pat
@@ -72,3 +100,35 @@ pub fn lookup_eco_from_name(pat: &str) -> Vec<Eco> {
         .cloned()
         .collect()
 }
+
+/// Lines of ECO `code`, e.g. "C50", the shortest first.
+pub fn lookup_eco_from_code(code: &str) -> Vec<Eco> {
+    let table = ECO_TABLE.get_or_init(init_table);
+    let mut ecos: Vec<Eco> = table
+        .values()
+        .filter(|eco| eco.code.eq_ignore_ascii_case(code.trim()))
+        .cloned()
+        .collect();
+    ecos.sort_by_key(|eco| eco.moves.len());
+    ecos
+}
+
+/// The opening named by `term`, an ECO code or words of its name. The
+/// shortest line wins, so that "italian game" is the Italian Game itself
+/// rather than one of its variations.
+pub fn lookup_opening(term: &str) -> Option<Eco> {
+    if term.trim().is_empty() {
+        return None;
+    }
+    let by_code = lookup_eco_from_code(term);
+    if !by_code.is_empty() {
+        return by_code.into_iter().next();
+    }
+    lookup_eco_from_name(term).into_iter().min_by(|a, b| {
+        a.moves
+            .len()
+            .cmp(&b.moves.len())
+            .then_with(|| a.name.len().cmp(&b.name.len()))
+            .then_with(|| a.code.cmp(&b.code))
+    })
+}
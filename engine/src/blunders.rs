@@ -1,76 +1,80 @@
-use std::{str::FromStr, sync::mpsc::channel, thread};
+use std::{str::FromStr, sync::mpsc::channel};
 
 use blunders_engine::Fen as _;
-use shakmaty::{Chess, FromSetup as _, Move};
+use chrono::Duration;
+use shakmaty::{fen::Fen, Chess, Color, Move, Position};
 use shakmaty_uci::{ParseUciMoveError, UciMove};
 
-use crate::state::Store;
+use crate::{bot::Searcher, Score};
 
-use super::Engine;
+/// The embedded blunders engine, either on the clock or
+/// searching to a fixed depth.
+pub struct BlundersSearcher {
+    depth: Option<u32>,
+}
+
+impl BlundersSearcher {
+    pub fn new(depth: Option<u32>) -> Self {
+        BlundersSearcher { depth }
+    }
 
-pub struct BlunderEngine {
-    store: Store,
+    fn mode(&self, white_time: Duration, black_time: Duration) -> blunders_engine::Mode {
+        match self.depth {
+            Some(depth) => blunders_engine::Mode::depth(depth as _, None),
+            None => blunders_engine::Mode::standard(
+                white_time.num_milliseconds() as i32,
+                black_time.num_milliseconds() as i32,
+                None,
+                None,
+                None,
+                None,
+            ),
+        }
+    }
 }
 
-fn to_move(r: blunders_engine::SearchResult, game: &Chess) -> Option<Move> {
-    let bm = r.best_move;
-    UciMove::from_str(&format!("{}", bm))
+fn to_move(r: &blunders_engine::SearchResult, game: &Chess) -> Option<Move> {
+    UciMove::from_str(&format!("{}", r.best_move))
         .and_then(|um| um.to_move(game).map_err(|_| ParseUciMoveError))
         .ok()
-
-    // match best_move_uci.to_move(game) {
-    //     Err(e) => log::error!(
-    //         "Failed to produce a bestmove from {best_move_uci}: {} ({})",
-    //         e,
-    //         Fen::from_position(game.clone(), shakmaty::EnPassantMode::Always)
-    //     ),
-    //     Ok(m) => self.store.update_engine(EngineState::PendingMove(m)),
-    // }
 }
 
-impl Engine for BlunderEngine {
-    fn new_game(&self) {}
+/// blunders scores positions from white's point of view, while
+/// UCI engines, and therefore our clients, expect the side to move's.
+fn to_score(r: &blunders_engine::SearchResult, game: &Chess) -> Score {
+    let score = i32::from(r.score.0);
+    match game.turn() {
+        Color::White => Score::CentiPawns { score },
+        Color::Black => Score::CentiPawns { score: -score },
+    }
+}
 
-    fn go(
-        &self,
-        fen: shakmaty::fen::Fen,
-        white_time: chrono::Duration,
-        black_time: chrono::Duration,
-    ) {
-        if let Ok(pos) = blunders_engine::Position::parse_fen(&fen.to_string()) {
-            let (tx, rx) = channel::<blunders_engine::SearchResult>();
-            let store = self.store.clone();
-            let game = Chess::from_setup(fen.as_setup().clone(), shakmaty::CastlingMode::Standard)
-                .unwrap();
-            let mut engine = blunders_engine::EngineBuilder::new()
-                .position(pos)
-                .transpositions_mb(10)
-                .debug(false)
-                .build();
+impl Searcher for BlundersSearcher {
+    fn search(
+        &mut self,
+        game: &Chess,
+        white_time: Duration,
+        black_time: Duration,
+    ) -> Option<(Move, Score)> {
+        let fen = Fen::from_position(game.clone(), shakmaty::EnPassantMode::Legal);
+        let pos = match blunders_engine::Position::parse_fen(&fen.to_string()) {
+            Ok(pos) => pos,
+            Err(_) => {
+                log::error!("<blunders> failed to parse fen '{fen}'");
+                return None;
+            }
+        };
+        let (tx, rx) = channel::<blunders_engine::SearchResult>();
+        let mut engine = blunders_engine::EngineBuilder::new()
+            .position(pos)
+            .transpositions_mb(10)
+            .debug(false)
+            .build();
 
-            thread::spawn(move || {
-                rx.recv().map(|result| {
-                    let m =
-                        to_move(result, &game).expect("blunders move failed to parse correctly");
-                    store.update_engine(super::EngineState::PendingMove(m));
-                })
-            });
+        let _ = engine.search(self.mode(white_time, black_time), tx);
 
-            let _ = engine.search(
-                blunders_engine::Mode::standard(
-                    white_time.num_milliseconds() as i32,
-                    black_time.num_milliseconds() as i32,
-                    None,
-                    None,
-                    None,
-                    None,
-                ),
-                tx,
-            );
-        }
+        rx.recv()
+            .ok()
+            .and_then(|result| to_move(&result, game).map(|m| (m, to_score(&result, game))))
     }
 }
-
-pub fn connect_engine(store: Store) -> BlunderEngine {
-    BlunderEngine { store }
-}
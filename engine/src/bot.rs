@@ -0,0 +1,317 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use chrono::Duration;
+use shakmaty::{
+    variant::{Variant, VariantPosition},
+    Chess, Color, Move, Position, Role,
+};
+use ucui_utils::variant::parse_position;
+
+use crate::{
+    blunders::BlundersSearcher, connection::EngineConnection, simple::SimpleSearcher,
+    EngineCommand, EngineMessage, Score,
+};
+
+/// A move picker running in process, without an external engine.
+pub trait Searcher {
+    fn new_game(&mut self) {}
+    fn search(
+        &mut self,
+        game: &Chess,
+        white_time: Duration,
+        black_time: Duration,
+    ) -> Option<(Move, Score)>;
+}
+
+/// Built-in bots, from the weakest to the strongest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bot {
+    Random,
+    Greedy,
+    Simple,
+    Blunders { depth: Option<u32> },
+}
+
+pub const BOT_LADDER: [Bot; 7] = [
+    Bot::Random,
+    Bot::Greedy,
+    Bot::Simple,
+    Bot::Blunders { depth: Some(2) },
+    Bot::Blunders { depth: Some(4) },
+    Bot::Blunders { depth: Some(6) },
+    Bot::Blunders { depth: None },
+];
+
+impl Bot {
+    pub fn name(&self) -> String {
+        match self {
+            Bot::Random => "random".into(),
+            Bot::Greedy => "greedy".into(),
+            Bot::Simple => "simple".into(),
+            Bot::Blunders { depth: Some(depth) } => format!("blunders-{depth}"),
+            Bot::Blunders { depth: None } => "blunders".into(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            Bot::Random => "plays any legal move".into(),
+            Bot::Greedy => "grabs the biggest piece it can, or mates when it sees it".into(),
+            Bot::Simple => "counts material and mobility a few plies ahead".into(),
+            Bot::Blunders { depth: Some(depth) } => {
+                format!("blunders engine searching {depth} plies")
+            }
+            Bot::Blunders { depth: None } => "blunders engine playing on the clock".into(),
+        }
+    }
+}
+
+impl Default for Bot {
+    fn default() -> Self {
+        Bot::Blunders { depth: None }
+    }
+}
+
+impl Display for Bot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name())
+    }
+}
+
+impl FromStr for Bot {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BOT_LADDER
+            .iter()
+            .find(|bot| bot.name() == s)
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<String> = BOT_LADDER.iter().map(Bot::name).collect();
+                format!("unknown bot '{s}', expected one of {}", names.join(", "))
+            })
+    }
+}
+
+/// Small xorshift generator, good enough to pick moves.
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        let seed = chrono::Utc::now().timestamp_nanos_opt().unwrap_or(1) as u64;
+        Rng(seed | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            items.get((self.next() % items.len() as u64) as usize)
+        }
+    }
+}
+
+fn role_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight => 300,
+        Role::Bishop => 300,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        Role::King => 0,
+    }
+}
+
+fn material(color: Color, game: &Chess) -> i32 {
+    let board = game.board();
+    board
+        .by_color(color)
+        .into_iter()
+        .filter_map(|sq| board.role_at(sq))
+        .map(role_value)
+        .sum()
+}
+
+struct RandomSearcher {
+    rng: Rng,
+}
+
+impl Searcher for RandomSearcher {
+    fn search(
+        &mut self,
+        game: &Chess,
+        _white_time: Duration,
+        _black_time: Duration,
+    ) -> Option<(Move, Score)> {
+        let moves = game.legal_moves();
+        self.rng.pick(&moves).map(|m| (m.clone(), Score::None))
+    }
+}
+
+struct GreedySearcher {
+    rng: Rng,
+}
+
+impl Searcher for GreedySearcher {
+    fn search(
+        &mut self,
+        game: &Chess,
+        _white_time: Duration,
+        _black_time: Duration,
+    ) -> Option<(Move, Score)> {
+        let moves = game.legal_moves();
+        if let Some(m) = moves.iter().find(|m| {
+            game.clone()
+                .play(m)
+                .map(|pos| pos.is_checkmate())
+                .unwrap_or(false)
+        }) {
+            return Some((m.clone(), Score::Mate { moves: 1 }));
+        }
+
+        let gain = |m: &Move| {
+            m.capture().map(role_value).unwrap_or(0) + m.promotion().map(role_value).unwrap_or(0)
+        };
+        let best = moves.iter().map(gain).max()?;
+        let candidates: Vec<&Move> = moves.iter().filter(|m| gain(m) == best).collect();
+        let m = self.rng.pick(&candidates).map(|m| (*m).clone())?;
+
+        let color = game.turn();
+        let after = game.clone().play(&m).ok()?;
+        let score = material(color, &after) - material(color.other(), &after);
+        Some((m, Score::CentiPawns { score }))
+    }
+}
+
+/// The move to play, or why there is none.
+fn best_move<S: Searcher>(
+    searcher: &mut S,
+    rng: &mut Rng,
+    game: Result<VariantPosition, String>,
+    white_time: Duration,
+    black_time: Duration,
+) -> Result<(Move, Score), String> {
+    let found = match game? {
+        // searchers not knowing about a position, e.g. blunders
+        // in Chess960, should not leave the game hanging
+        VariantPosition::Chess(game) => {
+            searcher.search(&game, white_time, black_time).or_else(|| {
+                log::warn!("<bot> no move found, playing the first legal one");
+                game.legal_moves().first().map(|m| (m.clone(), Score::None))
+            })
+        }
+        // bots only know the rules of standard chess
+        game => {
+            let moves = game.legal_moves();
+            rng.pick(&moves).map(|m| (m.clone(), Score::None))
+        }
+    };
+    found.ok_or_else(|| "no legal move".into())
+}
+
+fn run_searcher<S: Searcher>(
+    mut searcher: S,
+    rx: Receiver<EngineCommand>,
+    tx: Sender<EngineMessage>,
+) {
+    let mut chess960 = false;
+    let mut variant = Variant::Chess;
+    let mut rng = Rng::new();
+    loop {
+        // bots play by the clock, even when searching within a limit
+        let (fen, white_time, black_time) = match rx.recv() {
+            Err(err) => {
+                log::error!("Engine channel error: {}", err);
+                break;
+            }
+            Ok(msg) => match msg {
+                EngineCommand::NewGame => {
+                    searcher.new_game();
+                    continue;
+                }
+                EngineCommand::Chess960(enabled) => {
+                    chess960 = enabled;
+                    continue;
+                }
+                EngineCommand::Variant(v) => {
+                    variant = v.into();
+                    continue;
+                }
+                EngineCommand::Go {
+                    fen,
+                    white_time,
+                    black_time,
+                } => (fen, white_time, black_time),
+                EngineCommand::Search { fen, limit } => (fen, limit.clock(), limit.clock()),
+                EngineCommand::Stop => break,
+            },
+        };
+        let game = parse_position(&fen, variant, chess960)
+            .map(|(game, _)| game)
+            .map_err(|err| format!("failed to produce a position from '{fen}': {err}"));
+        let message = match best_move(&mut searcher, &mut rng, game, white_time, black_time) {
+            Ok((m, score)) => EngineMessage::BestMove {
+                move_: m.into(),
+                score,
+            },
+            Err(reason) => {
+                log::error!("<bot> {reason}");
+                EngineMessage::Resign { reason }
+            }
+        };
+        let _ = tx.send(message);
+    }
+}
+
+pub fn connect_bot(bot: Bot) -> EngineConnection {
+    let (sender_to, receiver_to) = channel::<EngineCommand>();
+    let (sender_from, receiver_from) = channel::<EngineMessage>();
+    thread::spawn(move || match bot {
+        Bot::Random => run_searcher(RandomSearcher { rng: Rng::new() }, receiver_to, sender_from),
+        Bot::Greedy => run_searcher(GreedySearcher { rng: Rng::new() }, receiver_to, sender_from),
+        Bot::Simple => run_searcher(SimpleSearcher, receiver_to, sender_from),
+        Bot::Blunders { depth } => {
+            run_searcher(BlundersSearcher::new(depth), receiver_to, sender_from)
+        }
+    });
+
+    EngineConnection::new(sender_to, receiver_from, Some(bot.name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    fn answer(fen: &str) -> EngineMessage {
+        let engine = connect_bot(Bot::Greedy);
+        engine.go(fen.into(), Duration::seconds(60), Duration::seconds(60));
+        engine.recv().expect("engine message")
+    }
+
+    #[test]
+    fn resigns_without_a_move() {
+        assert!(matches!(
+            answer("nonsense"),
+            EngineMessage::Resign { reason } if reason.starts_with("failed to produce a position")
+        ));
+        assert!(matches!(
+            answer("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3"),
+            EngineMessage::Resign { reason } if reason == "no legal move"
+        ));
+    }
+}
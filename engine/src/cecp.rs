@@ -0,0 +1,752 @@
+//! Chess Engine Communication Protocol, aka XBoard
+//!
+//! See https://www.gnu.org/software/xboard/engine-intf.html
+//!
+//! The engine is kept in force mode and only asked to move with `go`,
+//! so that each `EngineCommand::Go` stands on its own like with UCI.
+
+use std::{
+    io::Write,
+    process::Child,
+    str::FromStr,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+};
+
+use chrono::Duration;
+use shakmaty::{
+    fen::Fen,
+    san::San,
+    variant::{Variant, VariantPosition},
+    CastlingMode, Color, Move, Position,
+};
+use shakmaty_uci::UciMove;
+use ucui_utils::variant::parse_position;
+
+use crate::{
+    connection::EngineConnection,
+    process::{self, read_lines},
+    EngineCommand, EngineMessage, Score, SearchLimit,
+};
+
+/// How long we wait for `feature` lines after `protover 2`.
+const FEATURE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+/// How long we wait after an engine asked for more time with `done=0`.
+const FEATURE_DONE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+/// Features asking for nothing we do not do anyway: we never signal
+/// the engine, always send the clocks and only set options given.
+const HARMLESS_FEATURES: [&str; 7] = [
+    "sigint", "sigterm", "reuse", "colors", "time", "ping", "option",
+];
+/// Mate scores are given as 100000 + N for "mate in N moves".
+const MATE_SCORE: i32 = 100000;
+
+#[derive(Default)]
+struct Features {
+    name: Option<String>,
+    setboard: bool,
+    usermove: bool,
+    san: bool,
+    variants: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Output {
+    Feature(Vec<(String, String)>),
+    Move(String),
+    Thinking { depth: u32, score: i32 },
+    Result(String),
+    Other,
+}
+
+fn parse_features(s: &str) -> Vec<(String, String)> {
+    let mut features = Vec::new();
+    let mut rest = s.trim();
+    while let Some((key, tail)) = rest.split_once('=') {
+        let key = key.trim().to_string();
+        let (value, tail) = match tail.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => tail.split_once(' ').unwrap_or((tail, "")),
+        };
+        features.push((key, value.to_string()));
+        rest = tail.trim_start();
+    }
+    features
+}
+
+fn parse_line(line: &str) -> Output {
+    let line = line.trim();
+    if let Some(features) = line.strip_prefix("feature ") {
+        return Output::Feature(parse_features(features));
+    }
+    if let Some(m) = line.strip_prefix("move ") {
+        return Output::Move(m.trim().to_string());
+    }
+    if ["1-0", "0-1", "1/2-1/2", "resign"]
+        .iter()
+        .any(|r| line.starts_with(r))
+    {
+        return Output::Result(line.to_string());
+    }
+
+    // thinking output: ply score time nodes pv
+    let mut parts = line.split_whitespace();
+    let depth = parts
+        .next()
+        .map(|s| s.trim_end_matches(|c: char| !c.is_ascii_digit()))
+        .and_then(|s| s.parse::<u32>().ok());
+    let score = parts.next().and_then(|s| s.parse::<i32>().ok());
+    let time = parts.next().and_then(|s| s.parse::<i64>().ok());
+    let nodes = parts.next().and_then(|s| s.parse::<u64>().ok());
+    match (depth, score, time, nodes) {
+        (Some(depth), Some(score), Some(_), Some(_)) => Output::Thinking { depth, score },
+        _ => Output::Other,
+    }
+}
+
+fn to_score(score: i32) -> Score {
+    if score >= MATE_SCORE {
+        Score::Mate {
+            moves: i8::try_from(score - MATE_SCORE).unwrap_or(i8::MAX),
+        }
+    } else if score <= -MATE_SCORE {
+        Score::Mate {
+            moves: -i8::try_from(-score - MATE_SCORE).unwrap_or(i8::MAX),
+        }
+    } else {
+        Score::CentiPawns { score }
+    }
+}
+
+fn parse_move(text: &str, game: &VariantPosition) -> Option<Move> {
+    UciMove::from_str(text)
+        .ok()
+        .and_then(|m| m.to_move(game).ok())
+        .or_else(|| {
+            San::from_str(text)
+                .ok()
+                .and_then(|san| san.to_move(game).ok())
+        })
+}
+
+fn centiseconds(time: Duration) -> i64 {
+    std::cmp::max(0, time.num_milliseconds() / 10)
+}
+
+fn fen_key<P: Position + Clone>(game: &P) -> String {
+    Fen::from_position(game.clone(), shakmaty::EnPassantMode::Legal).to_string()
+}
+
+/// Name of `variant` in the `variant` command.
+fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Chess => "normal",
+        Variant::Atomic => "atomic",
+        Variant::Antichess => "giveaway",
+        Variant::KingOfTheHill => "kingofthehill",
+        Variant::ThreeCheck => "3check",
+        Variant::Crazyhouse => "crazyhouse",
+        Variant::RacingKings => "racingkings",
+        Variant::Horde => "horde",
+    }
+}
+
+struct CecpEngine<W: Write> {
+    rx: Receiver<EngineCommand>,
+    tx: Sender<EngineMessage>,
+    writer: W,
+    lines: Receiver<String>,
+    options: Vec<(String, Option<String>)>,
+    features: Features,
+    move_time: Option<u32>,
+    level_sent: bool,
+    last: Option<VariantPosition>,
+    castling_mode: CastlingMode,
+    variant: Variant,
+}
+
+impl<W: Write> CecpEngine<W> {
+    fn new(
+        rx: Receiver<EngineCommand>,
+        tx: Sender<EngineMessage>,
+        writer: W,
+        lines: Receiver<String>,
+        options: Vec<(String, Option<String>)>,
+    ) -> Self {
+        CecpEngine {
+            rx,
+            tx,
+            writer,
+            lines,
+            options,
+            features: Features::default(),
+            move_time: None,
+            level_sent: false,
+            last: None,
+            castling_mode: CastlingMode::Standard,
+            variant: Variant::Chess,
+        }
+    }
+
+    fn send(&mut self, command: &str) {
+        log::debug!("<cecp-engine> > {command}");
+        let _ = writeln!(self.writer, "{command}").and_then(|_| self.writer.flush());
+    }
+
+    /// Records a feature and tells whether the engine is done with them.
+    /// Features we know nothing about are rejected.
+    fn accept(&mut self, key: &str, value: &str) -> Option<bool> {
+        match key {
+            "done" => return Some(value == "1"),
+            "myname" => self.features.name = Some(value.to_string()),
+            "setboard" => self.features.setboard = value == "1",
+            "usermove" => self.features.usermove = value == "1",
+            "san" => self.features.san = value == "1",
+            "variants" => {
+                self.features.variants = value.split(',').map(|v| v.trim().to_string()).collect()
+            }
+            key if HARMLESS_FEATURES.contains(&key) => {}
+            _ => {
+                self.send(&format!("rejected {key}"));
+                return None;
+            }
+        }
+        self.send(&format!("accepted {key}"));
+        None
+    }
+
+    fn send_id(&mut self) {
+        self.send("xboard");
+        self.send("protover 2");
+
+        let mut timeout = FEATURE_TIMEOUT;
+        loop {
+            match self.lines.recv_timeout(timeout) {
+                Err(_) => break,
+                Ok(line) => {
+                    if let Output::Feature(features) = parse_line(&line) {
+                        let mut done = None;
+                        for (key, value) in features {
+                            if let Some(d) = self.accept(&key, &value) {
+                                done = Some(d);
+                            }
+                        }
+                        match done {
+                            Some(true) => break,
+                            Some(false) => timeout = FEATURE_DONE_TIMEOUT,
+                            None => {}
+                        }
+                    } else {
+                        log::debug!("<engine> {line}");
+                    }
+                }
+            }
+        }
+
+        let name = self
+            .features
+            .name
+            .clone()
+            .unwrap_or("XBoard Engine".to_string());
+        let _ = self.tx.send(EngineMessage::Id(name));
+    }
+
+    fn set_options(&mut self) {
+        for (id, value) in self.options.clone() {
+            match (id.as_str(), value) {
+                ("st", Some(value)) => self.move_time = value.parse().ok(),
+                (_, Some(value)) => self.send(&format!("option {id}={value}")),
+                (_, None) => self.send(&format!("option {id}")),
+            }
+        }
+    }
+
+    fn start(&mut self) {
+        self.send("post");
+        self.send("easy");
+        self.set_options();
+        self.new_game();
+
+        loop {
+            match self.rx.recv() {
+                Err(err) => {
+                    log::error!("Engine channel error: {}", err);
+                    break;
+                }
+                Ok(msg) => match msg {
+                    EngineCommand::NewGame => self.new_game(),
+                    EngineCommand::Chess960(chess960) => self.set_chess960(chess960),
+                    EngineCommand::Variant(variant) => self.set_variant(variant.into()),
+                    EngineCommand::Go {
+                        fen,
+                        white_time,
+                        black_time,
+                    } => self.go(fen, white_time, black_time),
+                    EngineCommand::Search { fen, limit } => self.search(fen, limit),
+                    EngineCommand::Stop => {
+                        self.send("quit");
+                        break;
+                    }
+                },
+            }
+        }
+    }
+
+    fn set_chess960(&mut self, chess960: bool) {
+        let mode = CastlingMode::from_chess960(chess960);
+        if mode != self.castling_mode {
+            if chess960 && !self.features.variants.iter().any(|v| v == "fischerandom") {
+                log::warn!("<cecp-engine> engine does not claim to play fischerandom");
+            }
+            self.castling_mode = mode;
+            self.new_game();
+        }
+    }
+
+    fn set_variant(&mut self, variant: Variant) {
+        if variant != self.variant {
+            let name = variant_name(variant);
+            if variant != Variant::Chess && !self.features.variants.iter().any(|v| v == name) {
+                log::warn!("<cecp-engine> engine does not claim to play {name}");
+            }
+            self.variant = variant;
+            self.new_game();
+        }
+    }
+
+    /// There is no variant name for Chess960 rules on top of another
+    /// variant, the variant wins.
+    fn new_game(&mut self) {
+        self.send("new");
+        if self.variant != Variant::Chess {
+            self.send(&format!("variant {}", variant_name(self.variant)));
+        } else if self.castling_mode == CastlingMode::Chess960 {
+            self.send("variant fischerandom");
+        }
+        self.send("force");
+        self.level_sent = false;
+        self.last = None;
+    }
+
+    /// Castling is always O-O or O-O-O in fischerandom, king to
+    /// rook or king two squares would both be ambiguous.
+    fn format_move(&self, game: &VariantPosition, m: &Move) -> String {
+        if self.features.san || (m.is_castle() && self.castling_mode == CastlingMode::Chess960) {
+            San::from_move(game, m).to_string()
+        } else {
+            m.to_uci(self.castling_mode).to_string()
+        }
+    }
+
+    /// Sends the opponent's move when we can find it, the whole
+    /// position otherwise.
+    fn set_position(&mut self, game: &VariantPosition) {
+        let key = fen_key(game);
+        let user_move = self.last.clone().and_then(|last| {
+            last.legal_moves()
+                .into_iter()
+                .find(|m| {
+                    last.clone()
+                        .play(m)
+                        .map(|pos| fen_key(&pos) == key)
+                        .unwrap_or(false)
+                })
+                .map(|m| self.format_move(&last, &m))
+        });
+
+        match user_move {
+            Some(m) if self.features.usermove => self.send(&format!("usermove {m}")),
+            Some(m) => self.send(&m),
+            None if self.features.setboard => self.send(&format!("setboard {key}")),
+            None => {
+                if key != fen_key(&VariantPosition::new(self.variant)) {
+                    log::warn!("<cecp-engine> engine does not support setboard, playing from the initial position");
+                }
+                self.new_game();
+            }
+        }
+    }
+
+    fn send_time(&mut self, game: &VariantPosition, white_time: Duration, black_time: Duration) {
+        if let Some(seconds) = self.move_time {
+            self.send(&format!("st {seconds}"));
+            return;
+        }
+        let (own, other) = match game.turn() {
+            Color::White => (white_time, black_time),
+            Color::Black => (black_time, white_time),
+        };
+        if !self.level_sent {
+            let seconds = centiseconds(own) / 100;
+            self.send(&format!("level 0 {}:{:02} 0", seconds / 60, seconds % 60));
+            self.level_sent = true;
+        }
+        self.send(&format!("time {}", centiseconds(own)));
+        self.send(&format!("otim {}", centiseconds(other)));
+    }
+
+    fn parse(&self, fen_string: &str) -> Option<VariantPosition> {
+        let chess960 = self.castling_mode == CastlingMode::Chess960;
+        parse_position(fen_string, self.variant, chess960)
+            .map(|(game, _)| game)
+            .map_err(|_| {
+                log::error!(
+                    "<cecp-engine> failed to produce a position from fen string: '{fen_string}'"
+                )
+            })
+            .ok()
+    }
+
+    fn go(&mut self, fen_string: String, white_time: Duration, black_time: Duration) {
+        let Some(game) = self.parse(&fen_string) else {
+            self.resign(format!("invalid position '{fen_string}'"));
+            return;
+        };
+
+        self.send("force");
+        self.set_position(&game);
+        self.send_time(&game, white_time, black_time);
+        self.send("go");
+        self.wait_move(&game);
+    }
+
+    /// `sd` and `st` stay in force until changed, the clock is set
+    /// again by the next `go` of a game.
+    fn search(&mut self, fen_string: String, limit: SearchLimit) {
+        let Some(game) = self.parse(&fen_string) else {
+            self.resign(format!("invalid position '{fen_string}'"));
+            return;
+        };
+
+        self.send("force");
+        self.set_position(&game);
+        match limit {
+            SearchLimit::Depth { depth } => self.send(&format!("sd {depth}")),
+            SearchLimit::MoveTime { millis } => {
+                self.send(&format!("st {}", millis.div_ceil(1000).max(1)))
+            }
+        }
+        self.level_sent = false;
+        self.send("go");
+        self.wait_move(&game);
+    }
+
+    /// Tells that no move is coming, so that nobody waits for it.
+    fn resign(&self, reason: String) {
+        log::error!("<cecp-engine> {reason}");
+        let _ = self.tx.send(EngineMessage::Resign { reason });
+    }
+
+    /// Always answers, with the engine's move or why there is none.
+    fn wait_move(&mut self, game: &VariantPosition) {
+        let mut score = Score::None;
+        loop {
+            let Ok(line) = self.lines.recv() else {
+                self.resign("engine output closed".into());
+                return;
+            };
+            match parse_line(&line) {
+                Output::Thinking { depth, score: s } => {
+                    log::debug!("<engine> depth {depth} score {s}");
+                    score = to_score(s);
+                }
+                Output::Move(text) => {
+                    match parse_move(&text, game) {
+                        Some(m) => {
+                            self.last = game.clone().play(&m).ok();
+                            let _ = self.tx.send(EngineMessage::BestMove {
+                                move_: m.into(),
+                                score,
+                            });
+                        }
+                        None => self.resign(format!("illegal move {text} ({})", fen_key(game))),
+                    }
+                    return;
+                }
+                Output::Result(result) => {
+                    self.resign(format!("engine ended the game: {result}"));
+                    return;
+                }
+                Output::Feature(_) | Output::Other => log::debug!("<engine> {line}"),
+            }
+        }
+    }
+}
+
+/// Talks to the engine on its own thread, and reaps `child`, the engine
+/// process if any, once done with it.
+fn connect<W: Write + Send + 'static>(
+    writer: W,
+    lines: Receiver<String>,
+    options: Vec<(String, Option<String>)>,
+    child: Option<Child>,
+) -> EngineConnection {
+    let (sender_to, receiver_to) = channel::<EngineCommand>();
+    let (sender_from, receiver_from) = channel::<EngineMessage>();
+    thread::spawn(move || {
+        let mut engine = CecpEngine::new(receiver_to, sender_from, writer, lines, options);
+        engine.send_id();
+        engine.start();
+        drop(engine);
+        if let Some(mut child) = child {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    });
+
+    EngineConnection::identify(sender_to, receiver_from)
+}
+
+pub fn connect_engine(
+    path: &str,
+    args: Option<Vec<String>>,
+    options: Vec<(String, Option<String>)>,
+) -> EngineConnection {
+    let mut child = process::spawn(path, args).expect("engine should be OK");
+    let stdin = child.stdin.take().expect("engine should be OK");
+    let stdout = child.stdout.take().expect("engine should be OK");
+
+    connect(stdin, read_lines(stdout), options, Some(child))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+    use shakmaty::Chess;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    };
+
+    /// Hands over each written line to the fake engine.
+    struct LineWriter {
+        tx: Sender<String>,
+        buf: Vec<u8>,
+    }
+
+    impl Write for LineWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(buf);
+            while let Some(i) = self.buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buf.drain(..=i).collect();
+                let _ = self
+                    .tx
+                    .send(String::from_utf8_lossy(&line).trim_end().to_string());
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Starts a fake engine answering each command with the lines
+    /// given by `script`, and returns the commands it received.
+    fn fake_engine<F>(script: F) -> (EngineConnection, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(&str) -> Vec<String> + Send + 'static,
+    {
+        let (command_tx, command_rx) = channel::<String>();
+        let (line_tx, line_rx) = channel::<String>();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        thread::spawn(move || {
+            for command in command_rx {
+                let lines = script(&command);
+                log.lock().unwrap().push(command);
+                for line in lines {
+                    let _ = line_tx.send(line);
+                }
+            }
+        });
+        let writer = LineWriter {
+            tx: command_tx,
+            buf: Vec::new(),
+        };
+        (connect(writer, line_rx, Vec::new(), None), received)
+    }
+
+    fn script(command: &str) -> Vec<String> {
+        match command {
+            "protover 2" => vec![
+                "feature myname=\"Fake Engine 1.0\" setboard=1".into(),
+                "feature usermove=1 pause=1 done=1".into(),
+            ],
+            "go" => vec![
+                "1 12 0 20 e2e4".into(),
+                "4 25 10 1234 e2e4 e7e5 g1f3".into(),
+                "move e2e4".into(),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    fn best_move(engine: &EngineConnection) -> (Move, Score) {
+        match engine.recv().expect("engine message") {
+            EngineMessage::BestMove { move_, score } => (move_.into(), score),
+            EngineMessage::Resign { reason } => panic!("unexpected resignation: {reason}"),
+            EngineMessage::Id(_) => panic!("unexpected Id"),
+        }
+    }
+
+    fn uci(s: &str, game: &Chess) -> Move {
+        UciMove::from_str(s).unwrap().to_move(game).unwrap()
+    }
+
+    #[test]
+    fn parse_feature_line() {
+        assert_eq!(
+            parse_line("feature myname=\"Some Engine 2\" setboard=1 done=0"),
+            Output::Feature(vec![
+                ("myname".into(), "Some Engine 2".into()),
+                ("setboard".into(), "1".into()),
+                ("done".into(), "0".into()),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_output_lines() {
+        assert_eq!(parse_line("move e7e5"), Output::Move("e7e5".into()));
+        assert_eq!(
+            parse_line("9. -35 120 40000 Nf6 Nc3"),
+            Output::Thinking {
+                depth: 9,
+                score: -35
+            }
+        );
+        assert_eq!(
+            parse_line("0-1 {White resigns}"),
+            Output::Result("0-1 {White resigns}".into())
+        );
+        assert_eq!(parse_line("Illegal move: e2e5"), Output::Other);
+        assert!(matches!(to_score(100003), Score::Mate { moves: 3 }));
+        assert!(matches!(to_score(-100002), Score::Mate { moves: -2 }));
+    }
+
+    #[test]
+    fn negotiates_features() {
+        let (engine, received) = fake_engine(script);
+        assert_eq!(engine.name(), "Fake Engine 1.0");
+
+        engine.go(
+            fen_key(&Chess::default()),
+            Duration::seconds(60),
+            Duration::seconds(60),
+        );
+        let _ = best_move(&engine);
+        let received = received.lock().unwrap();
+        assert!(received.contains(&"accepted setboard".to_string()));
+        assert!(received.contains(&"accepted usermove".to_string()));
+        assert!(received.contains(&"rejected pause".to_string()));
+    }
+
+    #[test]
+    fn plays_from_fen() {
+        let (engine, received) = fake_engine(script);
+        let game = Chess::default();
+        engine.go(
+            fen_key(&game),
+            Duration::seconds(60),
+            Duration::milliseconds(45_500),
+        );
+        let (m, score) = best_move(&engine);
+        assert_eq!(m, uci("e2e4", &game));
+        assert!(matches!(score, Score::CentiPawns { score: 25 }));
+
+        let received = received.lock().unwrap();
+        let go = received.iter().position(|c| c == "go").unwrap();
+        assert_eq!(
+            received[go - 5..go],
+            [
+                "force".to_string(),
+                format!("setboard {}", fen_key(&game)),
+                "level 0 1:00 0".to_string(),
+                "time 6000".to_string(),
+                "otim 4550".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn sends_user_moves() {
+        let goes = AtomicUsize::new(0);
+        let (engine, received) = fake_engine(move |command| match command {
+            "protover 2" => vec!["feature setboard=1 usermove=1 san=1 done=1".into()],
+            "go" if goes.fetch_add(1, Ordering::SeqCst) == 0 => vec!["move Nf3".into()],
+            "go" => vec!["move e4".into()],
+            _ => Vec::new(),
+        });
+        let game = Chess::default();
+        engine.go(fen_key(&game), Duration::seconds(60), Duration::seconds(60));
+        let (m, score) = best_move(&engine);
+        assert_eq!(m, uci("g1f3", &game));
+        assert!(matches!(score, Score::None));
+
+        let game = game.play(&m).unwrap();
+        let reply = uci("d7d5", &game);
+        let game = game.play(&reply).unwrap();
+        engine.go(fen_key(&game), Duration::seconds(59), Duration::seconds(58));
+        assert_eq!(best_move(&engine).0, uci("e2e4", &game));
+
+        let received = received.lock().unwrap();
+        assert!(received.contains(&"usermove d5".to_string()));
+        assert_eq!(
+            received
+                .iter()
+                .filter(|c| c.starts_with("setboard"))
+                .count(),
+            1
+        );
+        assert_eq!(
+            received.iter().filter(|c| c.starts_with("level")).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn resigns_instead_of_illegal_moves() {
+        let (engine, _) = fake_engine(|command| match command {
+            "protover 2" => vec!["feature setboard=1 done=1".into()],
+            "go" => vec!["move e2e5".into()],
+            _ => Vec::new(),
+        });
+        engine.go(
+            fen_key(&Chess::default()),
+            Duration::seconds(60),
+            Duration::seconds(60),
+        );
+        assert!(matches!(
+            engine.recv(),
+            Ok(EngineMessage::Resign { reason }) if reason.starts_with("illegal move e2e5")
+        ));
+    }
+
+    #[test]
+    fn plays_variants() {
+        let (engine, received) = fake_engine(|command| match command {
+            "protover 2" => vec!["feature setboard=1 variants=\"normal,crazyhouse\" done=1".into()],
+            "go" => vec!["move P@e4".into()],
+            _ => Vec::new(),
+        });
+        engine.set_variant(Variant::Crazyhouse);
+        engine.go(
+            "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[Pp] w KQkq - 0 3".into(),
+            Duration::seconds(60),
+            Duration::seconds(60),
+        );
+        let (m, _) = best_move(&engine);
+        assert_eq!(
+            m,
+            Move::Put {
+                role: shakmaty::Role::Pawn,
+                to: shakmaty::Square::E4,
+            }
+        );
+
+        let received = received.lock().unwrap();
+        assert!(received.contains(&"variant crazyhouse".to_string()));
+    }
+}
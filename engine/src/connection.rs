@@ -0,0 +1,98 @@
+use std::sync::mpsc::{Receiver, RecvError, RecvTimeoutError, Sender};
+
+use chrono::Duration;
+use shakmaty::variant::Variant;
+
+use crate::{transcript::Transcript, Engine, EngineCommand, EngineMessage, SearchLimit};
+
+pub struct EngineConnection {
+    tx: Sender<EngineCommand>,
+    receiver: Receiver<EngineMessage>,
+    engine_id: Option<String>,
+    transcript: Option<Transcript>,
+}
+
+impl EngineConnection {
+    pub(crate) fn new(
+        tx: Sender<EngineCommand>,
+        rx: Receiver<EngineMessage>,
+        engine_id: Option<String>,
+    ) -> Self {
+        Self {
+            tx,
+            receiver: rx,
+            engine_id,
+            transcript: None,
+        }
+    }
+
+    pub(crate) fn with_transcript(mut self, transcript: Transcript) -> Self {
+        self.transcript = Some(transcript);
+        self
+    }
+
+    /// Waits for the engine thread to send its `Id` first.
+    pub(crate) fn identify(tx: Sender<EngineCommand>, rx: Receiver<EngineMessage>) -> Self {
+        let id = rx
+            .recv()
+            .map(|msg| {
+                if let EngineMessage::Id(id) = msg {
+                    id
+                } else {
+                    String::from("NN")
+                }
+            })
+            .ok();
+
+        EngineConnection::new(tx, rx, id)
+    }
+}
+
+impl Engine for EngineConnection {
+    fn name(&self) -> String {
+        self.engine_id.clone().unwrap_or(String::from("-"))
+    }
+
+    fn new_game(&self) {
+        let _ = self.tx.send(EngineCommand::NewGame);
+    }
+
+    fn set_chess960(&self, chess960: bool) {
+        let _ = self.tx.send(EngineCommand::Chess960(chess960));
+    }
+
+    fn set_variant(&self, variant: Variant) {
+        let _ = self.tx.send(EngineCommand::Variant(variant.into()));
+    }
+
+    fn stop(&self) {
+        let _ = self.tx.send(EngineCommand::Stop);
+    }
+
+    fn go(&self, fen_string: String, white_time: Duration, black_time: Duration) {
+        let _ = self.tx.send(EngineCommand::Go {
+            fen: fen_string,
+            white_time,
+            black_time,
+        });
+    }
+
+    fn search(&self, fen: String, limit: SearchLimit) {
+        let _ = self.tx.send(EngineCommand::Search { fen, limit });
+    }
+
+    fn recv(&self) -> Result<EngineMessage, RecvError> {
+        self.receiver.recv()
+    }
+
+    fn recv_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<EngineMessage, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    fn transcript(&self) -> Option<Transcript> {
+        self.transcript.clone()
+    }
+}
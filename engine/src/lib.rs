@@ -1,11 +1,27 @@
-use std::sync::mpsc::RecvError;
+use std::{
+    str::FromStr,
+    sync::mpsc::{RecvError, RecvTimeoutError},
+};
 
 use chrono::Duration;
 use serde::{Deserialize, Serialize};
-use shakmaty::Move;
-use shakmaty_uci::UciInfo;
+use shakmaty::{variant::Variant, Move};
+mod blunders;
+mod bot;
+mod cecp;
+mod connection;
+mod process;
+mod profile;
+mod simple;
+mod tcp;
+mod transcript;
 mod uci;
 
+pub use bot::{Bot, BOT_LADDER};
+pub use profile::Profile;
+pub use transcript::{Direction, Transcript, TranscriptLine};
+pub use ucui_protocol::Score;
+
 #[derive(Clone, Eq, PartialEq, Debug, Default)]
 pub enum EngineState {
     #[default]
@@ -23,31 +39,34 @@ pub enum EngineCommand {
         white_time: Duration,
         black_time: Duration,
     },
+    /// Analysis of a position, as opposed to a move in a game.
+    Search {
+        fen: String,
+        limit: SearchLimit,
+    },
     NewGame,
+    /// Castling as in Chess960 from now on, or back to standard.
+    Chess960(bool),
+    /// Rules to play by from now on, e.g. crazyhouse.
+    Variant(ucui_utils::variant::VariantSerde),
     Stop,
 }
 
-#[derive(Deserialize, Serialize)]
+/// How far to search a position when analysing it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(tag = "_tag")]
-pub enum Score {
-    CentiPawns { score: i32 },
-    Mate { moves: i8 },
-    None,
+pub enum SearchLimit {
+    Depth { depth: u32 },
+    MoveTime { millis: u64 },
 }
 
-impl From<UciInfo> for Score {
-    fn from(value: UciInfo) -> Self {
-        if let UciInfo {
-            score: Some(score), ..
-        } = value
-        {
-            match (score.cp, score.mate) {
-                (None, None) => Score::None,
-                (Some(score), None) => Score::CentiPawns { score },
-                (_, Some(moves)) => Score::Mate { moves },
-            }
-        } else {
-            Score::None
+impl SearchLimit {
+    /// Time left on both clocks for engines that only play by the
+    /// clock, so that they spend about as long on the position.
+    pub fn clock(&self) -> Duration {
+        match self {
+            SearchLimit::Depth { depth } => Duration::seconds(10 * i64::from(*depth)),
+            SearchLimit::MoveTime { millis } => Duration::milliseconds(30 * *millis as i64),
         }
     }
 }
@@ -60,20 +79,74 @@ pub enum EngineMessage {
         move_: ucui_utils::MoveSerde,
         score: Score,
     },
+    /// The engine gives up instead of moving: it resigned, or failed
+    /// to come up with a legal move.
+    Resign {
+        reason: String,
+    },
 }
 
 pub trait Engine {
     fn name(&self) -> String;
     fn new_game(&self) {}
+    fn set_chess960(&self, _chess960: bool) {}
+    fn set_variant(&self, _variant: Variant) {}
     fn stop(&self) {}
     fn go(&self, fen: String, white_time: Duration, black_time: Duration);
+    /// Like `go`, searching within `limit` rather than by the clock.
+    fn search(&self, fen: String, limit: SearchLimit) {
+        let clock = limit.clock();
+        self.go(fen, clock, clock);
+    }
     fn recv(&self) -> Result<EngineMessage, RecvError>;
+    /// Like `recv`, giving up after `timeout`.
+    fn recv_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<EngineMessage, RecvTimeoutError> {
+        let _ = timeout;
+        self.recv().map_err(|_| RecvTimeoutError::Disconnected)
+    }
+    /// Lines exchanged with the engine so far, for engines
+    /// running out of process.
+    fn transcript(&self) -> Option<Transcript> {
+        None
+    }
+}
+
+/// The protocol spoken by an external engine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Uci,
+    /// Chess Engine Communication Protocol, aka XBoard
+    Cecp,
+}
+
+impl FromStr for Protocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "uci" => Ok(Protocol::Uci),
+            "cecp" | "xboard" => Ok(Protocol::Cecp),
+            _ => Err(format!("unknown protocol '{s}', expected uci or xboard")),
+        }
+    }
 }
 
 pub fn connect_engine(
+    protocol: Protocol,
     engine_path: &str,
     args: Option<Vec<String>>,
     options: Vec<(String, Option<String>)>,
 ) -> Box<dyn Engine + Send> {
-    Box::new(uci::connect_engine(engine_path, args, options))
+    match protocol {
+        Protocol::Uci => Box::new(uci::connect_engine(engine_path, args, options)),
+        Protocol::Cecp => Box::new(cecp::connect_engine(engine_path, args, options)),
+    }
+}
+
+pub fn connect_bot(bot: Bot) -> Box<dyn Engine + Send> {
+    Box::new(bot::connect_bot(bot))
 }
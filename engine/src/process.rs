@@ -0,0 +1,139 @@
+//! Engines running as a local process, talking over stdin/stdout
+//!
+//! Lines are read on their own thread so that waiting for output can
+//! time out, and so that an engine exiting shows up as an error rather
+//! than as an endless stream of empty reads.
+
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, BufReader, Read, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{channel, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::{transcript::Transcript, uci::Transport};
+
+/// What `uci::Engine::command` waits for output.
+const COMMAND_DURATION: Duration = Duration::from_millis(100);
+
+pub(crate) fn read_lines<R: Read + Send + 'static>(reader: R) -> Receiver<String> {
+    let (tx, rx) = channel::<String>();
+    thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            match line {
+                Ok(line) => {
+                    if tx.send(line).is_err() {
+                        break;
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+    });
+    rx
+}
+
+pub(crate) fn spawn(path: &str, args: Option<Vec<String>>) -> io::Result<Child> {
+    Command::new(path)
+        .args(args.unwrap_or_default())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+}
+
+fn engine_gone() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "engine process exited")
+}
+
+pub struct ProcessTransport {
+    child: Child,
+    stdin: RefCell<ChildStdin>,
+    lines: Receiver<String>,
+    transcript: Transcript,
+}
+
+impl ProcessTransport {
+    pub fn new(path: &str, args: Option<Vec<String>>, transcript: Transcript) -> io::Result<Self> {
+        let mut child = spawn(path, args)?;
+        let stdin = child.stdin.take().ok_or_else(engine_gone)?;
+        let stdout = child.stdout.take().ok_or_else(engine_gone)?;
+        Ok(ProcessTransport {
+            child,
+            stdin: RefCell::new(stdin),
+            lines: read_lines(stdout),
+            transcript,
+        })
+    }
+
+    fn write_line(&self, line: &str) -> io::Result<()> {
+        self.transcript.sent(line);
+        let mut stdin = self.stdin.borrow_mut();
+        writeln!(stdin, "{line}")?;
+        stdin.flush()
+    }
+
+    fn read_for(&self, duration: Duration) -> io::Result<String> {
+        let mut lines: Vec<String> = Vec::new();
+        loop {
+            match self.lines.recv_timeout(duration) {
+                Ok(line) => {
+                    self.transcript.received(&line);
+                    lines.push(line);
+                }
+                Err(RecvTimeoutError::Timeout) => return Ok(lines.join("\n")),
+                Err(RecvTimeoutError::Disconnected) => return Err(engine_gone()),
+            }
+        }
+    }
+
+    fn read_until(&self, prefix: &str) -> io::Result<String> {
+        let mut lines: Vec<String> = Vec::new();
+        loop {
+            let line = self.lines.recv().map_err(|_| engine_gone())?;
+            self.transcript.received(&line);
+            let done = line.starts_with(prefix);
+            lines.push(line);
+            if done {
+                return Ok(lines.join("\n"));
+            }
+        }
+    }
+}
+
+impl Drop for ProcessTransport {
+    fn drop(&mut self) {
+        let _ = self.write_line("quit");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl Transport for ProcessTransport {
+    fn command(&self, cmd: &str) -> io::Result<String> {
+        self.command_with_duration(cmd, COMMAND_DURATION)
+    }
+
+    fn command_and_wait_for(&self, cmd: &str, wait_for: &str) -> io::Result<String> {
+        self.write_line(cmd)?;
+        self.read_until(wait_for)
+    }
+
+    fn command_with_duration(&self, cmd: &str, duration: Duration) -> io::Result<String> {
+        self.write_line(cmd)?;
+        self.read_for(duration)
+    }
+
+    fn set_option(&self, name: &str, value: &str) -> io::Result<()> {
+        let output = self.command(&format!("setoption name {name} value {value}"))?;
+        if output.contains("No such option") {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown option {name}"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
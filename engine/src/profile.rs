@@ -0,0 +1,150 @@
+use std::str::FromStr;
+
+use crate::{connect_bot, connect_engine, Bot, Engine, Protocol};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Kind {
+    Engine {
+        path: String,
+        protocol: Protocol,
+        args: Option<Vec<String>>,
+        options: Vec<(String, Option<String>)>,
+    },
+    Bot(Bot),
+}
+
+/// An engine and how to run it, given as comma separated KEY=VALUE
+/// pairs, e.g. `name=sf-12,engine=/usr/games/stockfish,option=Skill Level:12`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    kind: Kind,
+}
+
+impl Profile {
+    /// An external engine, named after its executable.
+    pub fn engine(
+        path: &str,
+        protocol: Protocol,
+        args: Option<Vec<String>>,
+        options: Vec<(String, Option<String>)>,
+    ) -> Self {
+        Profile {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            kind: Kind::Engine {
+                path: path.to_string(),
+                protocol,
+                args,
+                options,
+            },
+        }
+    }
+
+    pub fn bot(bot: Bot) -> Self {
+        Profile {
+            name: bot.name(),
+            kind: Kind::Bot(bot),
+        }
+    }
+
+    /// Options the engine is set up with, none for bots.
+    pub fn options(&self) -> Vec<(String, Option<String>)> {
+        match &self.kind {
+            Kind::Engine { options, .. } => options.clone(),
+            Kind::Bot(_) => Vec::new(),
+        }
+    }
+
+    pub fn connect(&self) -> Box<dyn Engine + Send> {
+        match &self.kind {
+            Kind::Engine {
+                path,
+                protocol,
+                args,
+                options,
+            } => connect_engine(*protocol, path, args.clone(), options.clone()),
+            Kind::Bot(bot) => connect_bot(*bot),
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut name = None;
+        let mut path = None;
+        let mut bot = None;
+        let mut protocol = Protocol::Uci;
+        let mut args = None;
+        let mut options = Vec::new();
+        for pair in s.split(',').filter(|pair| !pair.trim().is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected KEY=VALUE, got '{pair}'"))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "engine" => path = Some(value.to_string()),
+                "bot" => bot = Some(Bot::from_str(value)?),
+                "protocol" => protocol = Protocol::from_str(value)?,
+                "args" => args = Some(value.split(';').map(String::from).collect()),
+                "option" => options.push(match value.split_once(':') {
+                    Some((id, value)) => (id.to_string(), Some(value.to_string())),
+                    None => (value.to_string(), None),
+                }),
+                key => return Err(format!("unknown key '{key}'")),
+            }
+        }
+
+        let profile = match (path, bot) {
+            (Some(path), None) => Profile::engine(&path, protocol, args, options),
+            (None, Some(bot)) => Profile::bot(bot),
+            (None, None) => return Err("a profile needs an engine or a bot".into()),
+            (Some(_), Some(_)) => return Err("a profile is an engine or a bot, not both".into()),
+        };
+        Ok(match name {
+            Some(name) => Profile { name, ..profile },
+            None => profile,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_engines() {
+        let profile = Profile::from_str(
+            "name=sf,engine=/usr/games/stockfish,args=--a;--b,option=Threads:2,option=Clear Hash",
+        )
+        .unwrap();
+        assert_eq!(profile.name, "sf");
+        assert_eq!(
+            profile.kind,
+            Kind::Engine {
+                path: "/usr/games/stockfish".into(),
+                protocol: Protocol::Uci,
+                args: Some(vec!["--a".into(), "--b".into()]),
+                options: vec![
+                    ("Threads".into(), Some("2".into())),
+                    ("Clear Hash".into(), None)
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn names_default_to_the_engine() {
+        assert_eq!(
+            Profile::from_str("engine=/usr/games/crafty,protocol=xboard")
+                .unwrap()
+                .name,
+            "crafty"
+        );
+        assert_eq!(Profile::from_str("bot=greedy").unwrap().name, "greedy");
+        assert!(Profile::from_str("name=nobody").is_err());
+        assert!(Profile::from_str("bot=greedy,engine=crafty").is_err());
+    }
+}
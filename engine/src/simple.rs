@@ -1,19 +1,13 @@
-use std::thread;
+use chrono::{Duration, Utc};
+use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Move, MoveList, Outcome, Position, Role};
 
-use chrono::Utc;
-use shakmaty::{
-    fen::Fen, CastlingMode, Chess, Color, FromSetup, Move, MoveList, Outcome, Position, Role,
-};
-
-use crate::state::Store;
-
-use super::Engine;
+use crate::{bot::Searcher, Score};
 
 const DEPTH: usize = 3;
 const MIN_KEEP: usize = 3;
 
 enum ScoreResult {
-    Score(i32, MoveList, Chess),
+    Score(i32, Box<MoveList>, Chess),
     Outcome(Outcome),
 }
 
@@ -28,26 +22,18 @@ fn piece_score(color: Color, game: &Chess) -> i32 {
 }
 
 fn score_move(color: Color, mut game: Chess, m: &Move, len: i32) -> ScoreResult {
-    // log::debug!("score_move {}", m);
-    // if !game.is_legal(&m) {
-    //     log::error!(
-    //         "Illegal move?? {} {}",
-    //         Fen::from_position(game, shakmaty::EnPassantMode::Always),
-    //         m
-    //     );
-    //     return ScoreResult::Outcome(Outcome::Decisive {
-    //         winner: color.other(),
-    //     });
-    // }
     game.play_unchecked(m);
     if let Some(outcome) = game.outcome() {
         return ScoreResult::Outcome(outcome);
     }
     let legal_moves = game.legal_moves();
-    // let capture_moves = game.capture_moves();
     let (us, them) = (piece_score(color, &game), piece_score(color.other(), &game));
     let count = legal_moves.len();
-    ScoreResult::Score((len + us) - ((count as i32) + them), legal_moves, game)
+    ScoreResult::Score(
+        (len + us) - ((count as i32) + them),
+        Box::new(legal_moves),
+        game,
+    )
 }
 
 fn eval_outcome(color: Color, outcome: Outcome) -> i32 {
@@ -64,8 +50,6 @@ fn eval_outcome(color: Color, outcome: Outcome) -> i32 {
 }
 
 fn eval_move(color: Color, depth: usize, game: Chess, m: &Move, from: i32) -> i32 {
-    // log::debug!("eval_move {}", m);
-
     match score_move(color, game.clone(), m, from) {
         ScoreResult::Outcome(outcome) => eval_outcome(color, outcome),
         ScoreResult::Score(score, oponent_moves, game) => {
@@ -73,13 +57,13 @@ fn eval_move(color: Color, depth: usize, game: Chess, m: &Move, from: i32) -> i3
                 return score;
             }
             let mut variant_scores: Vec<i32> = Vec::new();
-            for op_move in oponent_moves {
+            for op_move in *oponent_moves {
                 let mut op_game = game.clone();
 
                 op_game.play_unchecked(&op_move);
                 let legal_moves = op_game.legal_moves();
                 let len = legal_moves.len();
-                let n_keep = MIN_KEEP; //std::cmp::max(MIN_KEEP, len / DEPTH);
+                let n_keep = MIN_KEEP;
                 let mut scores: Vec<(Move, i32)> = Vec::with_capacity(len);
                 for m in legal_moves {
                     match score_move(color, op_game.clone(), &m, len as i32) {
@@ -105,33 +89,13 @@ fn eval_move(color: Color, depth: usize, game: Chess, m: &Move, from: i32) -> i3
     }
 }
 
-// fn eval(color: Color, mut game: Chess, m: &Move) -> i32 {
-
-// if depth == 0 {
-//     game.play_unchecked(m);
-//     game.legal_moves().len()
-// } else {
-//     let score: usize = {
-//     game.play_unchecked(m);
-//     game.legal_moves()
-//     .iter()
-//             .map(|m| eval(depth - 1, game.clone(), m, base))
-//             .sum()
-//     };
-//     if depth == DEPTH {
-//         log::debug!("{} {} ", m, base + score);
-//     }
-//     base + score
-// }
-// }
-
 fn bestmove(game: Chess) -> Option<Move> {
     let color = game.turn();
     let moves = game.legal_moves();
     let len = moves.len();
     let fen = Fen::from_position(game.clone(), shakmaty::EnPassantMode::Always);
     log::debug!("bestmove {}", fen);
-    if moves.len() == 0 {
+    if moves.is_empty() {
         None
     } else {
         let scores: Vec<(&Move, i32)> = moves
@@ -156,32 +120,17 @@ fn bestmove(game: Chess) -> Option<Move> {
     }
 }
 
-pub struct SimpleEngine {
-    store: Store,
-}
-
-impl Engine for SimpleEngine {
-    fn new_game(&self) {}
-
-    fn go(
-        &self,
-        fen: shakmaty::fen::Fen,
-        _white_time: chrono::Duration,
-        _black_time: chrono::Duration,
-    ) {
-        // let (tx, rx) = channel::<Move>();
-        let store = self.store.clone();
-        thread::spawn(move || {
-            Chess::from_setup(fen.as_setup().clone(), shakmaty::CastlingMode::Standard)
-                .ok()
-                .and_then(bestmove)
-                .map(|mv| {
-                    store.update_engine(super::EngineState::PendingMove(mv));
-                });
-        });
+/// Material and mobility evaluator, searching a handful of
+/// candidate lines a few plies deep.
+pub struct SimpleSearcher;
+
+impl Searcher for SimpleSearcher {
+    fn search(
+        &mut self,
+        game: &Chess,
+        _white_time: Duration,
+        _black_time: Duration,
+    ) -> Option<(Move, Score)> {
+        bestmove(game.clone()).map(|m| (m, Score::None))
     }
 }
-
-pub fn connect_engine(store: Store) -> SimpleEngine {
-    SimpleEngine { store }
-}
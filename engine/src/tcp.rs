@@ -0,0 +1,324 @@
+//! UCI over a TCP socket, e.g. to an engine exposed by `ucui-engine-relay`
+//!
+//! The connection is opened lazily and reopened when it breaks. Since a
+//! new connection usually means a fresh engine process, options and the
+//! last position are sent again before retrying the failed command.
+
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use crate::{transcript::Transcript, uci::Transport};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest silence we accept while waiting for a reply, engines
+/// usually send `info` lines way more often while searching.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// What `uci::Engine::command` waits for output.
+const COMMAND_DURATION: Duration = Duration::from_millis(100);
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+struct Connection {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+    pending: String,
+    transcript: Transcript,
+}
+
+impl Connection {
+    fn open(addr: &str, transcript: Transcript) -> io::Result<Self> {
+        let mut last_err =
+            io::Error::new(io::ErrorKind::NotFound, format!("no address for {addr}"));
+        for sock_addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&sock_addr, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    stream.set_nodelay(true)?;
+                    return Ok(Connection {
+                        writer: stream.try_clone()?,
+                        reader: BufReader::new(stream),
+                        pending: String::new(),
+                        transcript,
+                    });
+                }
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        self.transcript.sent(line);
+        writeln!(self.writer, "{line}")?;
+        self.writer.flush()
+    }
+
+    /// Reads a full line, keeping what came before a timeout for later.
+    fn read_line(&mut self, timeout: Duration) -> io::Result<String> {
+        self.reader.get_ref().set_read_timeout(Some(timeout))?;
+        match self.reader.read_line(&mut self.pending) {
+            Ok(0) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "engine closed the connection",
+            )),
+            Ok(_) if self.pending.ends_with('\n') => {
+                let line = std::mem::take(&mut self.pending).trim_end().to_string();
+                self.transcript.received(&line);
+                Ok(line)
+            }
+            Ok(_) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "engine closed the connection",
+            )),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_for(&mut self, duration: Duration) -> io::Result<String> {
+        let mut lines: Vec<String> = Vec::new();
+        loop {
+            match self.read_line(duration) {
+                Ok(line) => lines.push(line),
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(lines.join("\n"))
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn read_until(&mut self, prefix: &str) -> io::Result<String> {
+        let mut lines: Vec<String> = Vec::new();
+        loop {
+            let line = self.read_line(READ_TIMEOUT)?;
+            let done = line.starts_with(prefix);
+            lines.push(line);
+            if done {
+                return Ok(lines.join("\n"));
+            }
+        }
+    }
+}
+
+pub struct TcpTransport {
+    addr: String,
+    conn: RefCell<Option<Connection>>,
+    connected: Cell<bool>,
+    options: RefCell<Vec<String>>,
+    position: RefCell<Option<String>>,
+    transcript: Transcript,
+}
+
+impl TcpTransport {
+    pub fn new(addr: &str, transcript: Transcript) -> Self {
+        TcpTransport {
+            addr: addr.to_string(),
+            conn: RefCell::new(None),
+            connected: Cell::new(false),
+            options: RefCell::new(Vec::new()),
+            position: RefCell::new(None),
+            transcript,
+        }
+    }
+
+    /// Opens a connection, bringing a new engine on par with the
+    /// one we lost if we were connected before.
+    fn open(&self) -> io::Result<Connection> {
+        let mut conn = Connection::open(&self.addr, self.transcript.clone())?;
+        if self.connected.get() {
+            log::info!("<tcp-engine> reconnected to {}", self.addr);
+            conn.write_line("uci")?;
+            conn.read_until("uciok")?;
+            for option in self.options.borrow().iter() {
+                conn.write_line(option)?;
+            }
+            conn.write_line("isready")?;
+            conn.read_until("readyok")?;
+            if let Some(position) = self.position.borrow().as_ref() {
+                conn.write_line(position)?;
+            }
+        }
+        self.connected.set(true);
+        Ok(conn)
+    }
+
+    fn remember(&self, cmd: &str) {
+        if cmd.starts_with("setoption") {
+            self.options.borrow_mut().push(cmd.to_string());
+        } else if cmd.starts_with("position") {
+            let _ = self.position.borrow_mut().replace(cmd.to_string());
+        } else if cmd.starts_with("ucinewgame") {
+            let _ = self.position.borrow_mut().take();
+        }
+    }
+
+    fn exchange<F>(&self, cmd: &str, read: F) -> io::Result<String>
+    where
+        F: Fn(&mut Connection) -> io::Result<String>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = {
+                let mut conn = self.conn.borrow_mut();
+                if conn.is_none() {
+                    match self.open() {
+                        Ok(opened) => *conn = Some(opened),
+                        Err(err) => log::warn!("<tcp-engine> {}: {err}", self.addr),
+                    }
+                }
+                match conn.as_mut() {
+                    None => Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        format!("cannot connect to {}", self.addr),
+                    )),
+                    Some(conn) => conn.write_line(cmd).and_then(|_| read(conn)),
+                }
+            };
+
+            match result {
+                Ok(output) => {
+                    self.remember(cmd);
+                    return Ok(output);
+                }
+                Err(err) if attempt <= RECONNECT_ATTEMPTS => {
+                    log::warn!("<tcp-engine> {}: {err}, reconnecting", self.addr);
+                    let _ = self.conn.borrow_mut().take();
+                    thread::sleep(RECONNECT_DELAY * attempt);
+                    attempt += 1;
+                }
+                Err(err) => {
+                    log::error!("<tcp-engine> {}: {err}, giving up", self.addr);
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn command(&self, cmd: &str) -> io::Result<String> {
+        self.command_with_duration(cmd, COMMAND_DURATION)
+    }
+
+    fn command_and_wait_for(&self, cmd: &str, wait_for: &str) -> io::Result<String> {
+        self.exchange(cmd, |conn| conn.read_until(wait_for))
+    }
+
+    fn command_with_duration(&self, cmd: &str, duration: Duration) -> io::Result<String> {
+        self.exchange(cmd, |conn| conn.read_for(duration))
+    }
+
+    fn set_option(&self, name: &str, value: &str) -> io::Result<()> {
+        let output = self.command(&format!("setoption name {name} value {value}"))?;
+        if output.contains("No such option") {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unknown option {name}"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpListener,
+        str::FromStr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    use chrono::Duration;
+    use shakmaty::{fen::Fen, Chess};
+    use shakmaty_uci::UciMove;
+
+    use super::Connection;
+    use crate::transcript::Transcript;
+    use crate::{uci::connect_engine, Engine, EngineMessage, Score};
+
+    /// A scripted UCI engine on localhost, dropping the first
+    /// connection on `go` when asked to.
+    fn fake_engine(drop_first: bool) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let n = counter.fetch_add(1, Ordering::SeqCst);
+                let mut writer = stream.unwrap();
+                let reader = BufReader::new(writer.try_clone().unwrap());
+                for line in reader.lines() {
+                    let line = line.unwrap();
+                    let reply = match line.as_str() {
+                        "uci" => "id name Fake TCP\nuciok\n",
+                        "isready" => "readyok\n",
+                        l if l.starts_with("go") && drop_first && n == 0 => break,
+                        l if l.starts_with("go") => {
+                            "info depth 3 score cp 31 pv e2e4 e7e5\nbestmove e2e4\n"
+                        }
+                        _ => "",
+                    };
+                    writer.write_all(reply.as_bytes()).unwrap();
+                }
+            }
+        });
+        (format!("tcp://{addr}"), connections)
+    }
+
+    fn play(engine: &dyn Engine) {
+        let game = Chess::default();
+        engine.go(
+            Fen::from_position(game.clone(), shakmaty::EnPassantMode::Legal).to_string(),
+            Duration::seconds(60),
+            Duration::seconds(60),
+        );
+        match engine.recv().expect("engine message") {
+            EngineMessage::BestMove { move_, score } => {
+                let expected = UciMove::from_str("e2e4").unwrap().to_move(&game).unwrap();
+                assert_eq!(move_.0, expected);
+                assert!(matches!(score, Score::CentiPawns { score: 31 }));
+            }
+            EngineMessage::Resign { reason } => panic!("unexpected resignation: {reason}"),
+            EngineMessage::Id(_) => panic!("unexpected Id"),
+        }
+    }
+
+    #[test]
+    fn plays_over_tcp() {
+        let (addr, connections) = fake_engine(false);
+        let engine = connect_engine(&addr, None, Vec::new());
+        assert_eq!(engine.name(), "Fake TCP");
+        play(&engine);
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn reconnects_when_dropped() {
+        let (addr, connections) = fake_engine(true);
+        let engine = connect_engine(&addr, None, vec![("Hash".into(), Some("16".into()))]);
+        play(&engine);
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn fails_without_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        assert!(Connection::open(&addr, Transcript::new()).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Timestamped record of the lines exchanged with an engine
+//!
+//! A transcript reads as one line per engine line, `>` for what we
+//! sent and `<` for what the engine answered:
+//!
+//! ```text
+//! 2025-01-20T18:03:11.392Z > uci
+//! 2025-01-20T18:03:11.401Z < id name Stockfish 17
+//! ```
+
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Sent => f.write_str(">"),
+            Direction::Received => f.write_str("<"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptLine {
+    pub at: DateTime<Utc>,
+    pub direction: Direction,
+    pub line: String,
+}
+
+impl Display for TranscriptLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            self.direction,
+            self.line
+        )
+    }
+}
+
+impl FromStr for TranscriptLine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ' ');
+        let at = parts
+            .next()
+            .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            .ok_or_else(|| format!("missing timestamp in '{s}'"))?
+            .with_timezone(&Utc);
+        let direction = match parts.next() {
+            Some(">") => Direction::Sent,
+            Some("<") => Direction::Received,
+            _ => return Err(format!("missing direction in '{s}'")),
+        };
+        Ok(TranscriptLine {
+            at,
+            direction,
+            line: parts.next().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// Shared between the engine thread writing to it and whoever
+/// wants to read it, e.g. the server once a game is over.
+#[derive(Clone, Default)]
+pub struct Transcript(Arc<Mutex<Vec<TranscriptLine>>>);
+
+impl Transcript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&self, direction: Direction, line: &str) {
+        if let Ok(mut lines) = self.0.lock() {
+            lines.push(TranscriptLine {
+                at: Utc::now(),
+                direction,
+                line: line.to_string(),
+            });
+        }
+    }
+
+    pub fn sent(&self, line: &str) {
+        self.push(Direction::Sent, line);
+    }
+
+    pub fn received(&self, line: &str) {
+        self.push(Direction::Received, line);
+    }
+
+    pub fn lines(&self) -> Vec<TranscriptLine> {
+        self.0.lock().map(|lines| lines.clone()).unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.lock().map(|lines| lines.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Transcript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(TranscriptLine::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Transcript(Arc::new(Mutex::new(lines))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_as_text() {
+        let transcript = Transcript::new();
+        transcript.sent("uci");
+        transcript.received("id name Some Engine 1.0");
+        transcript.received("uciok");
+
+        let parsed = Transcript::from_str(&transcript.to_string()).unwrap();
+        let lines = parsed.lines();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].direction, Direction::Sent);
+        assert_eq!(lines[1].line, "id name Some Engine 1.0");
+        // timestamps are kept to the millisecond
+        assert_eq!(
+            lines[2].at.timestamp_millis(),
+            transcript.lines()[2].at.timestamp_millis()
+        );
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(Transcript::from_str("uci\nuciok\n").is_err());
+    }
+}
@@ -1,23 +1,43 @@
 use std::{
+    cell::Cell,
+    io,
     str::FromStr,
-    sync::mpsc::{channel, Receiver, RecvError, Sender},
+    sync::mpsc::{channel, Receiver, Sender},
     thread,
 };
 
 use chrono::Duration;
-use shakmaty::{fen::Fen, Chess, Color, FromSetup, Position};
+use shakmaty::{
+    fen::Fen,
+    variant::{Variant, VariantPosition},
+    Color, Position,
+};
 use shakmaty_uci::{UciInfo, UciInfoScore, UciMessage, UciMove};
+use ucui_utils::variant::parse_position;
 
-use crate::Score;
+use crate::{
+    connection::EngineConnection, process::ProcessTransport, tcp::TcpTransport,
+    transcript::Transcript, EngineCommand, EngineMessage, Score, SearchLimit,
+};
 
-use super::{Engine, EngineCommand, EngineMessage};
+/// Where UCI lines go to and come from, a local process
+/// or a remote engine.
+pub(crate) trait Transport {
+    fn command(&self, cmd: &str) -> io::Result<String>;
+    fn command_and_wait_for(&self, cmd: &str, wait_for: &str) -> io::Result<String>;
+    fn command_with_duration(&self, cmd: &str, duration: std::time::Duration)
+        -> io::Result<String>;
+    fn set_option(&self, name: &str, value: &str) -> io::Result<()>;
+}
 
 struct UciEngine {
     rx: Receiver<EngineCommand>,
     tx: Sender<EngineMessage>,
 
-    engine: uci::Engine,
+    engine: Box<dyn Transport>,
     options: Vec<(String, Option<String>)>,
+    chess960: Cell<bool>,
+    variant: Cell<Variant>,
 }
 
 impl UciEngine {
@@ -27,16 +47,21 @@ impl UciEngine {
         tx: Sender<EngineMessage>,
         args: Option<Vec<String>>,
         options: Vec<(String, Option<String>)>,
+        transcript: Transcript,
     ) -> Self {
-        let engine = match args {
-            None => uci::Engine::new(path).expect("engine should be OK"),
-            Some(args) => uci::Engine::with_args(path, args).expect("engine should be OK"),
+        let engine: Box<dyn Transport> = match path.strip_prefix("tcp://") {
+            Some(addr) => Box::new(TcpTransport::new(addr, transcript)),
+            None => Box::new(
+                ProcessTransport::new(path, args, transcript).expect("engine should be OK"),
+            ),
         };
         UciEngine {
             rx,
             tx,
             engine,
             options,
+            chess960: Cell::new(false),
+            variant: Cell::new(Variant::Chess),
         }
     }
 
@@ -80,23 +105,39 @@ impl UciEngine {
                 }
                 Ok(msg) => match msg {
                     EngineCommand::NewGame => self.new_game(),
+                    EngineCommand::Chess960(chess960) => self.set_chess960(chess960),
+                    EngineCommand::Variant(variant) => self.set_variant(variant.into()),
                     EngineCommand::Go {
                         fen,
                         white_time,
                         black_time,
-                    } => self.go(fen, white_time, black_time),
+                    } => {
+                        if let Err(err) = self.go(fen, white_time, black_time) {
+                            log::error!("<uci-engine> {err}, stopping");
+                            break;
+                        }
+                    }
+                    EngineCommand::Search { fen, limit } => {
+                        if let Err(err) = self.search(fen, limit) {
+                            log::error!("<uci-engine> {err}, stopping");
+                            break;
+                        }
+                    }
                     EngineCommand::Stop => break,
                 },
             }
         }
     }
 
-    pub fn update_move(&self, best_move_uci: UciMove, game: Chess, score: Score) {
+    /// Tells that no move is coming, so that nobody waits for it.
+    fn resign(&self, reason: String) {
+        log::error!("<uci-engine> {reason}");
+        let _ = self.tx.send(EngineMessage::Resign { reason });
+    }
+
+    pub fn update_move(&self, best_move_uci: UciMove, game: VariantPosition, score: Score) {
         match best_move_uci.to_move(&game) {
-            Err(e) => log::error!(
-                "<uci-engine> Failed to produce a bestmove from {best_move_uci}: {} ",
-                e,
-            ),
+            Err(e) => self.resign(format!("illegal move {best_move_uci}: {e}")),
             Ok(m) => {
                 let _ = self.tx.send(EngineMessage::BestMove {
                     move_: m.into(),
@@ -112,57 +153,89 @@ impl UciEngine {
             .command_with_duration("ucinewgame", std::time::Duration::from_millis(100));
     }
 
-    fn go(&self, fen_string: String, white_time: Duration, black_time: Duration) {
-        if let Ok(fen) = Fen::from_str(&fen_string) {
-            let setpos = shakmaty_uci::UciMessage::Position {
-                startpos: false,
-                fen: Some(fen.clone()),
-                moves: Vec::new(),
-            };
-            let goc = shakmaty_uci::UciMessage::Go {
-                time_control: Some(shakmaty_uci::UciTimeControl::TimeLeft {
-                    white_time: Some(white_time.to_std().expect("positive duration")),
-                    black_time: Some(black_time.to_std().expect("positive duration")),
-                    white_increment: None,
-                    black_increment: None,
-                    moves_to_go: None,
-                }),
-                search_control: None,
-            };
-            if self.engine.command(&setpos.to_string()).is_ok() {
-                let _ = self
-                    .engine
-                    .command_and_wait_for(&goc.to_string(), "bestmove")
-                    .map(|lines| {
-                        let mut infos: Vec<UciInfo> = Vec::new();
-                        for line in lines.split("\n") {
-                            if let Ok(UciMessage::BestMove { best_move, .. }) =
-                                UciMessage::from_str(line)
-                            {
-                                let game = Chess::from_setup(
-                                    fen.as_setup().clone(),
-                                    shakmaty::CastlingMode::Standard,
-                                )
-                                .expect("argh!");
+    fn set_chess960(&self, chess960: bool) {
+        if self
+            .engine
+            .set_option("UCI_Chess960", if chess960 { "true" } else { "false" })
+            .is_err()
+        {
+            log::warn!("<uci-engine> engine does not support UCI_Chess960");
+        }
+        self.chess960.set(chess960);
+    }
 
-                                let score = get_score(&infos, game.turn(), &best_move);
+    fn set_variant(&self, variant: Variant) {
+        if self
+            .engine
+            .set_option("UCI_Variant", variant.uci())
+            .is_err()
+            && variant != Variant::Chess
+        {
+            log::warn!(
+                "<uci-engine> engine does not support UCI_Variant, it may not play {}",
+                variant.uci()
+            );
+        }
+        self.variant.set(variant);
+    }
 
-                                self.update_move(best_move, game, score);
-                            } else if let Ok(UciMessage::Info(info)) = UciMessage::from_str(line) {
-                                infos.push(info);
-                            }
-                        }
-                        "OK".to_string()
-                    });
-            };
-        } else {
-            log::error!("<uci-engine> failed to produce a `Fen` from fen string:  '{fen_string}'");
+    fn go(&self, fen_string: String, white_time: Duration, black_time: Duration) -> io::Result<()> {
+        let goc = shakmaty_uci::UciMessage::Go {
+            time_control: Some(shakmaty_uci::UciTimeControl::TimeLeft {
+                // a flag fell, the engine still has to answer
+                white_time: Some(white_time.to_std().unwrap_or_default()),
+                black_time: Some(black_time.to_std().unwrap_or_default()),
+                white_increment: None,
+                black_increment: None,
+                moves_to_go: None,
+            }),
+            search_control: None,
+        };
+        self.think(fen_string, &goc.to_string())
+    }
+
+    fn search(&self, fen_string: String, limit: SearchLimit) -> io::Result<()> {
+        let goc = match limit {
+            SearchLimit::Depth { depth } => format!("go depth {depth}"),
+            SearchLimit::MoveTime { millis } => format!("go movetime {millis}"),
+        };
+        self.think(fen_string, &goc)
+    }
+
+    /// Sets the position and sends `goc`, then reports the best move.
+    /// Fails only when the engine cannot be talked to anymore.
+    fn think(&self, fen_string: String, goc: &str) -> io::Result<()> {
+        let (Ok(fen), Ok((game, _))) = (
+            Fen::from_str(&fen_string),
+            parse_position(&fen_string, self.variant.get(), self.chess960.get()),
+        ) else {
+            self.resign(format!("invalid position '{fen_string}'"));
+            return Ok(());
+        };
+        let setpos = shakmaty_uci::UciMessage::Position {
+            startpos: false,
+            fen: Some(fen),
+            moves: Vec::new(),
+        };
+        self.engine.command(&setpos.to_string())?;
+        let lines = self.engine.command_and_wait_for(goc, "bestmove")?;
+
+        let mut infos: Vec<UciInfo> = Vec::new();
+        for line in lines.split("\n") {
+            if let Ok(UciMessage::BestMove { best_move, .. }) = UciMessage::from_str(line) {
+                let score = get_score(&infos, game.turn(), &best_move);
+
+                self.update_move(best_move, game.clone(), score);
+            } else if let Ok(UciMessage::Info(info)) = UciMessage::from_str(line) {
+                infos.push(info);
+            }
         }
+        Ok(())
     }
 }
 
 /// lookup a possible score in infos list
-fn get_score(infos: &Vec<UciInfo>, color: Color, best_move: &UciMove) -> Score {
+fn get_score(infos: &[UciInfo], color: Color, best_move: &UciMove) -> Score {
     let mut candidates = infos
         .iter()
         .filter(|info| {
@@ -181,7 +254,7 @@ fn get_score(infos: &Vec<UciInfo>, color: Color, best_move: &UciMove) -> Score {
         .max()
         .unwrap_or(0);
 
-    let score = candidates
+    candidates
         .into_iter()
         .filter(|c| c.pv.len() == max_len)
         .reduce(|acc, info| {
@@ -194,9 +267,19 @@ fn get_score(infos: &Vec<UciInfo>, color: Color, best_move: &UciMove) -> Score {
                 _ => acc,
             }
         })
-        .map(|info| Score::from(info.clone()))
-        .unwrap_or(Score::None);
-    score
+        .map(info_score)
+        .unwrap_or(Score::None)
+}
+
+fn info_score(info: &UciInfo) -> Score {
+    match &info.score {
+        Some(score) => match (score.cp, score.mate) {
+            (None, None) => Score::None,
+            (Some(score), None) => Score::CentiPawns { score },
+            (_, Some(moves)) => Score::Mate { moves },
+        },
+        None => Score::None,
+    }
 }
 
 enum CompScore {
@@ -241,52 +324,6 @@ fn comp_score(a: UciInfoScore, b: UciInfoScore, color: Color) -> CompScore {
     }
 }
 
-pub struct EngineConnection {
-    tx: Sender<EngineCommand>,
-    receiver: Receiver<EngineMessage>,
-    engine_id: Option<String>,
-}
-
-impl EngineConnection {
-    fn new(
-        tx: Sender<EngineCommand>,
-        rx: Receiver<EngineMessage>,
-        engine_id: Option<String>,
-    ) -> Self {
-        Self {
-            tx,
-            receiver: rx,
-            engine_id,
-        }
-    }
-}
-
-impl Engine for EngineConnection {
-    fn name(&self) -> String {
-        self.engine_id.clone().unwrap_or(String::from("-"))
-    }
-
-    fn new_game(&self) {
-        let _ = self.tx.send(EngineCommand::NewGame);
-    }
-
-    fn stop(&self) {
-        let _ = self.tx.send(EngineCommand::Stop);
-    }
-
-    fn go(&self, fen_string: String, white_time: Duration, black_time: Duration) {
-        let _ = self.tx.send(EngineCommand::Go {
-            fen: fen_string,
-            white_time,
-            black_time,
-        });
-    }
-
-    fn recv(&self) -> Result<EngineMessage, RecvError> {
-        self.receiver.recv()
-    }
-}
-
 pub fn connect_engine(
     path: &str,
     args: Option<Vec<String>>,
@@ -295,22 +332,20 @@ pub fn connect_engine(
     let (sender_to, receiver_to) = channel::<EngineCommand>();
     let (sender_from, receiver_from) = channel::<EngineMessage>();
     let cloned_path = String::from(path);
+    let transcript = Transcript::new();
+    let engine_transcript = transcript.clone();
     thread::spawn(move || {
-        let engine = UciEngine::new(&cloned_path, receiver_to, sender_from, args, options);
+        let engine = UciEngine::new(
+            &cloned_path,
+            receiver_to,
+            sender_from,
+            args,
+            options,
+            engine_transcript,
+        );
         engine.send_id();
         engine.start();
     });
 
-    let id = receiver_from
-        .recv()
-        .map(|msg| {
-            if let EngineMessage::Id(id) = msg {
-                id
-            } else {
-                String::from("NN")
-            }
-        })
-        .ok();
-
-    EngineConnection::new(sender_to, receiver_from, id)
+    EngineConnection::identify(sender_to, receiver_from).with_transcript(transcript)
 }
@@ -0,0 +1,117 @@
+use std::{path::PathBuf, sync::OnceLock, time::Duration};
+
+use clap::Parser;
+use ucui_engine::Profile;
+
+use crate::game::{Adjudication, TimeControl};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Config {
+    /// An engine taking part in the match
+    ///
+    /// This argument must be given at least twice. Players are comma
+    /// separated KEY=VALUE pairs: name, engine (path to the engine),
+    /// bot (a built-in bot instead), protocol (uci or xboard), args
+    /// (separated by ";") and option, as "ID[:VALUE]", which can be
+    /// repeated.
+    ///
+    /// Example: --player 'name=sf-12,engine=/usr/games/stockfish,option=Skill Level:12'
+    #[arg(short, long, value_name = "PLAYER", required = true)]
+    player: Vec<Profile>,
+
+    /// Number of games per pairing
+    #[arg(short = 'n', long, value_name = "GAMES", default_value = "2")]
+    games: u32,
+
+    /// Pair the first player with each of the others, rather than
+    /// everyone with everyone
+    #[arg(long)]
+    gauntlet: bool,
+
+    /// Time per player in milliseconds
+    #[arg(short, long, value_name = "TIME", default_value = "60000")]
+    time: u64,
+
+    /// Increment per move in milliseconds
+    #[arg(long, value_name = "TIME", default_value = "0")]
+    increment: u64,
+
+    /// File with start positions, one FEN per line
+    ///
+    /// Each position is played twice in a row, the players swapping colors.
+    #[arg(long, value_name = "FILE")]
+    openings: Option<PathBuf>,
+
+    /// Start from the opening line with ECO code CODE
+    ///
+    /// This argument can be repeated.
+    #[arg(long, value_name = "CODE")]
+    eco: Vec<String>,
+
+    /// Adjudicate a draw after this many moves
+    #[arg(long, value_name = "MOVES", default_value = "200")]
+    max_moves: u32,
+
+    /// Adjudicate a loss when a player's score is CP centipawns
+    /// below zero for --resign-moves moves in a row
+    #[arg(long, value_name = "CP")]
+    resign_score: Option<i32>,
+
+    /// See --resign-score
+    #[arg(long, value_name = "MOVES", default_value = "3")]
+    resign_moves: u32,
+
+    /// Where to write the games
+    #[arg(long, value_name = "FILE", default_value = "match.pgn")]
+    pgn: PathBuf,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+fn init_table() -> Config {
+    Config::parse()
+}
+
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(init_table)
+}
+
+pub fn get_players() -> &'static [Profile] {
+    &config().player
+}
+
+pub fn get_games() -> u32 {
+    config().games
+}
+
+pub fn get_gauntlet() -> bool {
+    config().gauntlet
+}
+
+pub fn get_time_control() -> TimeControl {
+    TimeControl {
+        time: Duration::from_millis(config().time),
+        increment: Duration::from_millis(config().increment),
+    }
+}
+
+pub fn get_openings() -> Option<PathBuf> {
+    config().openings.clone()
+}
+
+pub fn get_ecos() -> &'static [String] {
+    &config().eco
+}
+
+pub fn get_adjudication() -> Adjudication {
+    Adjudication {
+        max_moves: config().max_moves,
+        resign_score: config().resign_score,
+        resign_moves: config().resign_moves.max(1),
+    }
+}
+
+pub fn get_pgn() -> PathBuf {
+    config().pgn.clone()
+}
@@ -0,0 +1,79 @@
+/// Results of a player, or of a pairing from the first player's side.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Two-sided 95% confidence.
+const Z: f64 = 1.96;
+
+fn elo_difference(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+impl Tally {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, from 0 to 1.
+    pub fn score(&self) -> Option<f64> {
+        let games = self.games();
+        (games > 0).then(|| (self.wins as f64 + self.draws as f64 / 2.0) / games as f64)
+    }
+
+    /// Elo difference with the opponents and its 95% error margin,
+    /// unless the score is all or nothing.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score()?;
+        if score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let n = self.games() as f64;
+        let variance = (self.wins as f64 * (1.0 - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n;
+        let deviation = (variance / n).sqrt();
+        let high = (score + Z * deviation).min(1.0 - f64::EPSILON);
+        let low = (score - Z * deviation).max(f64::EPSILON);
+        let margin = (elo_difference(high) - elo_difference(low)) / 2.0;
+        Some((elo_difference(score), margin))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_from_scores() {
+        let even = Tally {
+            wins: 3,
+            draws: 4,
+            losses: 3,
+        };
+        let (diff, margin) = even.elo().unwrap();
+        assert!(diff.abs() < 1e-9);
+        assert!(margin > 0.0);
+
+        let ahead = Tally {
+            wins: 3,
+            draws: 0,
+            losses: 1,
+        };
+        let (diff, _) = ahead.elo().unwrap();
+        assert!((diff - 190.85).abs() < 0.01);
+
+        let sweep = Tally {
+            wins: 4,
+            draws: 0,
+            losses: 0,
+        };
+        assert_eq!(sweep.score(), Some(1.0));
+        assert_eq!(sweep.elo(), None);
+        assert_eq!(Tally::default().score(), None);
+    }
+}
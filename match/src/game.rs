@@ -0,0 +1,326 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    sync::mpsc::RecvTimeoutError,
+    time::{Duration, Instant},
+};
+
+use shakmaty::{fen::Fen, ByColor, CastlingMode, Chess, EnPassantMode, Move, Outcome, Position};
+use ucui_engine::{Engine, EngineMessage, Profile, Score};
+
+use crate::opening::Opening;
+
+/// Slack given to engines on top of their clock, for the time it
+/// takes to pass messages around.
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy)]
+pub struct TimeControl {
+    pub time: Duration,
+    pub increment: Duration,
+}
+
+impl Display for TimeControl {
+    /// As in the PGN TimeControl tag, seconds plus increment.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}+{}",
+            self.time.as_secs_f64(),
+            self.increment.as_secs_f64()
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Adjudication {
+    /// Draw once the game reaches this many moves.
+    pub max_moves: u32,
+    /// A player whose own score stays this many centipawns below
+    /// zero for `resign_moves` moves in a row loses.
+    pub resign_score: Option<i32>,
+    pub resign_moves: u32,
+}
+
+/// How a game ended, as in the PGN Termination tag.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Normal,
+    Adjudication,
+    TimeForfeit,
+    RulesInfraction,
+    Abandoned,
+}
+
+impl Display for Termination {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Termination::Normal => "normal",
+            Termination::Adjudication => "adjudication",
+            Termination::TimeForfeit => "time forfeit",
+            Termination::RulesInfraction => "rules infraction",
+            Termination::Abandoned => "abandoned",
+        })
+    }
+}
+
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub opening: Opening,
+    /// The opening's moves included.
+    pub moves: Vec<Move>,
+    pub outcome: Outcome,
+    pub termination: Termination,
+    pub reason: String,
+}
+
+/// The position reached, with what is needed to write the PGN and
+/// spot repetitions.
+struct Game {
+    game: Chess,
+    moves: Vec<Move>,
+    repetitions: HashMap<String, u32>,
+}
+
+impl Game {
+    /// The starting position counts as the first occurrence.
+    fn new(game: Chess) -> Self {
+        let mut game = Game {
+            game,
+            moves: Vec::new(),
+            repetitions: HashMap::new(),
+        };
+        game.record();
+        game
+    }
+
+    /// Counts the current position.
+    fn record(&mut self) {
+        let fen = Fen::from_position(self.game.clone(), EnPassantMode::Legal).to_string();
+        let key: Vec<&str> = fen.split(' ').take(4).collect();
+        *self.repetitions.entry(key.join(" ")).or_insert(0) += 1;
+    }
+
+    fn play(&mut self, m: &Move) {
+        self.game.play_unchecked(m);
+        self.moves.push(m.clone());
+        self.record();
+    }
+
+    /// The outcome of the game by the rules, if over.
+    fn outcome(&self) -> Option<(Outcome, &'static str)> {
+        if let Some(outcome) = self.game.outcome() {
+            let reason = if self.game.is_checkmate() {
+                "checkmate"
+            } else if self.game.is_stalemate() {
+                "stalemate"
+            } else {
+                "insufficient material"
+            };
+            Some((outcome, reason))
+        } else if self.game.halfmoves() >= 100 {
+            Some((Outcome::Draw, "fifty moves rule"))
+        } else if self.repetitions.values().any(|n| *n >= 3) {
+            Some((Outcome::Draw, "threefold repetition"))
+        } else {
+            None
+        }
+    }
+}
+
+fn is_losing(score: &Score, threshold: i32) -> bool {
+    match score {
+        Score::CentiPawns { score } => *score <= -threshold,
+        Score::Mate { moves } => *moves < 0,
+        Score::None => false,
+    }
+}
+
+fn chrono_duration(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or_else(|_| chrono::Duration::zero())
+}
+
+/// Waits for the best move of `engine` or its resignation, skipping
+/// anything else.
+fn answer(
+    engine: &(dyn Engine + Send),
+    timeout: Duration,
+) -> Result<EngineMessage, RecvTimeoutError> {
+    let start = Instant::now();
+    loop {
+        let left = timeout
+            .checked_sub(start.elapsed())
+            .ok_or(RecvTimeoutError::Timeout)?;
+        match engine.recv_timeout(left)? {
+            EngineMessage::Id(_) => {}
+            message => return Ok(message),
+        }
+    }
+}
+
+pub fn play(
+    white: &Profile,
+    black: &Profile,
+    opening: &Opening,
+    time_control: TimeControl,
+    adjudication: Adjudication,
+) -> GameRecord {
+    let engines = ByColor {
+        white: white.connect(),
+        black: black.connect(),
+    };
+    let names = ByColor {
+        white: white.name.clone(),
+        black: black.name.clone(),
+    };
+    for engine in engines.iter() {
+        engine.set_chess960(opening.castling_mode == CastlingMode::Chess960);
+        engine.new_game();
+    }
+
+    let mut game = Game::new(opening.position.clone());
+    for m in opening.moves.iter() {
+        game.play(m);
+    }
+
+    let mut clock = ByColor {
+        white: time_control.time,
+        black: time_control.time,
+    };
+    let mut losing = ByColor { white: 0, black: 0 };
+    let fen = |game: &Chess| Fen::from_position(game.clone(), EnPassantMode::Legal).to_string();
+
+    let (outcome, termination, reason) = loop {
+        if let Some((outcome, reason)) = game.outcome() {
+            break (outcome, Termination::Normal, reason.to_string());
+        }
+        if game.game.fullmoves().get() > adjudication.max_moves {
+            break (
+                Outcome::Draw,
+                Termination::Adjudication,
+                format!("{} moves", adjudication.max_moves),
+            );
+        }
+
+        let color = game.game.turn();
+        let engine = engines.get(color);
+        let name = names.get(color);
+        let loss = Outcome::Decisive {
+            winner: color.other(),
+        };
+        let left = *clock.get(color);
+        engine.go(
+            fen(&game.game),
+            chrono_duration(clock.white),
+            chrono_duration(clock.black),
+        );
+        let start = Instant::now();
+        let (m, score): (Move, Score) = match answer(engine.as_ref(), left + TIME_MARGIN) {
+            Ok(EngineMessage::BestMove { move_, score }) => (move_.into(), score),
+            Ok(EngineMessage::Resign { reason }) => {
+                break (
+                    loss,
+                    Termination::Normal,
+                    format!("{name} resigns, {reason}"),
+                )
+            }
+            Ok(EngineMessage::Id(_)) => unreachable!("identification is skipped"),
+            Err(RecvTimeoutError::Timeout) => {
+                break (
+                    loss,
+                    Termination::TimeForfeit,
+                    format!("{name} lost on time"),
+                )
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                break (loss, Termination::Abandoned, format!("{name} is gone"))
+            }
+        };
+        let elapsed = start.elapsed();
+        if elapsed > left + TIME_MARGIN {
+            break (
+                loss,
+                Termination::TimeForfeit,
+                format!("{name} lost on time"),
+            );
+        }
+        if !game.game.is_legal(&m) {
+            break (
+                loss,
+                Termination::RulesInfraction,
+                format!(
+                    "{name} played an illegal move, {}",
+                    m.to_uci(opening.castling_mode)
+                ),
+            );
+        }
+        *clock.get_mut(color) = left.saturating_sub(elapsed) + time_control.increment;
+        game.play(&m);
+
+        if let Some(threshold) = adjudication.resign_score {
+            let count = losing.get_mut(color);
+            *count = if is_losing(&score, threshold) {
+                *count + 1
+            } else {
+                0
+            };
+            if *count >= adjudication.resign_moves {
+                break (loss, Termination::Adjudication, format!("{name} resigns"));
+            }
+        }
+    };
+
+    for engine in engines.iter() {
+        engine.stop();
+    }
+
+    GameRecord {
+        white: names.white,
+        black: names.black,
+        opening: opening.clone(),
+        moves: game.moves,
+        outcome,
+        termination,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::variant::{Variant, VariantPosition};
+    use ucui_utils::notation::movetext;
+
+    #[test]
+    fn adjudicates_scores() {
+        assert!(is_losing(&Score::CentiPawns { score: -800 }, 700));
+        assert!(!is_losing(&Score::CentiPawns { score: -600 }, 700));
+        assert!(is_losing(&Score::Mate { moves: -3 }, 700));
+        assert!(!is_losing(&Score::Mate { moves: 3 }, 700));
+        assert!(!is_losing(&Score::None, 700));
+    }
+
+    #[test]
+    fn repetitions_draw() {
+        let mut game = Game::new(Chess::default());
+        for _ in 0..2 {
+            for uci in ["g1f3", "g8f6", "f3g1", "f6g8"] {
+                let m = uci
+                    .parse::<shakmaty::uci::UciMove>()
+                    .unwrap()
+                    .to_move(&game.game)
+                    .unwrap();
+                game.play(&m);
+            }
+        }
+        assert_eq!(
+            movetext(&VariantPosition::new(Variant::Chess), &game.moves[..4]),
+            "1. Nf3 Nf6 2. Ng1 Ng8"
+        );
+        assert_eq!(
+            game.outcome(),
+            Some((Outcome::Draw, "threefold repetition"))
+        );
+    }
+}
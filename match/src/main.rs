@@ -0,0 +1,147 @@
+//! Engine matches and gauntlets
+//!
+//! Plays games between engine configurations, alternating colors and
+//! start positions, writing each game to a PGN file as it ends, then
+//! prints a table of results with Elo differences.
+
+use std::{fs::File, io::Write, process::ExitCode};
+
+use shakmaty::{Color, Outcome};
+
+mod config;
+mod elo;
+mod game;
+mod opening;
+mod pgn;
+
+use crate::{
+    config::{
+        get_adjudication, get_ecos, get_games, get_gauntlet, get_openings, get_pgn, get_players,
+        get_time_control,
+    },
+    elo::Tally,
+    opening::load_openings,
+};
+
+/// Pairs of player indices, each playing `get_games` games.
+fn pairings(players: usize, gauntlet: bool) -> Vec<(usize, usize)> {
+    if gauntlet {
+        (1..players).map(|other| (0, other)).collect()
+    } else {
+        (0..players)
+            .flat_map(|a| (a + 1..players).map(move |b| (a, b)))
+            .collect()
+    }
+}
+
+fn count(tally: &mut Tally, outcome: Outcome, color: Color) {
+    match outcome {
+        Outcome::Draw => tally.draws += 1,
+        Outcome::Decisive { winner } if winner == color => tally.wins += 1,
+        Outcome::Decisive { .. } => tally.losses += 1,
+    }
+}
+
+fn print_table(names: &[String], tallies: &[Tally]) {
+    let mut rows: Vec<(&String, &Tally)> = names.iter().zip(tallies).collect();
+    rows.sort_by(|a, b| {
+        b.1.score()
+            .unwrap_or(0.0)
+            .total_cmp(&a.1.score().unwrap_or(0.0))
+    });
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(4);
+    println!(
+        "{:>4}  {:<width$}  {:>5}  {:>11}  {:>6}  {:>7}  {:>6}",
+        "Rank", "Name", "Games", "W-D-L", "Score", "Elo", "+/-"
+    );
+    for (rank, (name, tally)) in rows.into_iter().enumerate() {
+        let score = tally
+            .score()
+            .map(|score| format!("{:.1}%", score * 100.0))
+            .unwrap_or_default();
+        let (elo, margin) = match tally.elo() {
+            Some((elo, margin)) => (format!("{elo:.0}"), format!("{margin:.0}")),
+            None => ("-".into(), "-".into()),
+        };
+        println!(
+            "{:>4}  {:<width$}  {:>5}  {:>11}  {:>6}  {:>7}  {:>6}",
+            rank + 1,
+            name,
+            tally.games(),
+            format!("{}-{}-{}", tally.wins, tally.draws, tally.losses),
+            score,
+            elo,
+            margin
+        );
+    }
+}
+
+fn main() -> ExitCode {
+    let players = get_players();
+    if players.len() < 2 {
+        eprintln!("A match needs at least two players");
+        return ExitCode::FAILURE;
+    }
+    let openings = match load_openings(get_openings().as_deref(), get_ecos()) {
+        Ok(openings) => openings,
+        Err(err) => {
+            eprintln!("{err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let time_control = get_time_control();
+    let adjudication = get_adjudication();
+
+    let path = get_pgn();
+    let mut pgn_file = match File::create(&path) {
+        Ok(file) => file,
+        Err(err) => {
+            eprintln!("Failed to create {}: {err}", path.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut tallies = vec![Tally::default(); players.len()];
+    let mut round = 0;
+    for (a, b) in pairings(players.len(), get_gauntlet()) {
+        for k in 0..get_games() as usize {
+            let (white, black) = if k % 2 == 0 { (a, b) } else { (b, a) };
+            let opening = &openings[(k / 2) % openings.len()];
+            round += 1;
+            let record = game::play(
+                &players[white],
+                &players[black],
+                opening,
+                time_control,
+                adjudication,
+            );
+            println!(
+                "Game {round}: {} - {} {} ({})",
+                record.white, record.black, record.outcome, record.reason
+            );
+            count(&mut tallies[white], record.outcome, Color::White);
+            count(&mut tallies[black], record.outcome, Color::Black);
+            let game = pgn::export_pgn(&record, round, time_control);
+            if let Err(err) = pgn_file.write_all(game.as_bytes()) {
+                eprintln!("Failed to write {}: {err}", path.display());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    println!();
+    let names: Vec<String> = players.iter().map(|player| player.name.clone()).collect();
+    print_table(&names, &tallies);
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_players() {
+        assert_eq!(pairings(3, false), vec![(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(pairings(3, true), vec![(0, 1), (0, 2)]);
+    }
+}
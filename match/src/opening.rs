@@ -0,0 +1,102 @@
+use std::{fs, path::Path};
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, EnPassantMode, Move, Position};
+use ucui_eco::lookup_eco_from_code;
+use ucui_utils::{chess960::parse_position, MoveSerde};
+
+/// Where games of a match start: a position, and moves played from
+/// there before the engines take over.
+#[derive(Clone)]
+pub struct Opening {
+    pub name: Option<String>,
+    pub eco: Option<String>,
+    pub position: Chess,
+    pub castling_mode: CastlingMode,
+    pub moves: Vec<Move>,
+}
+
+impl Default for Opening {
+    fn default() -> Self {
+        Opening {
+            name: None,
+            eco: None,
+            position: Chess::default(),
+            castling_mode: CastlingMode::Standard,
+            moves: Vec::new(),
+        }
+    }
+}
+
+impl Opening {
+    /// The FEN to write in the PGN when not starting from the
+    /// standard position.
+    pub fn fen(&self) -> Option<String> {
+        let fen = |game: Chess| Fen::from_position(game, EnPassantMode::Legal).to_string();
+        Some(fen(self.position.clone())).filter(|start| *start != fen(Chess::default()))
+    }
+
+    fn from_fen(fen: &str) -> Result<Self, String> {
+        let (position, castling_mode) = parse_position(fen, false)?;
+        Ok(Opening {
+            position,
+            castling_mode,
+            ..Opening::default()
+        })
+    }
+
+    fn from_eco(code: &str) -> Result<Self, String> {
+        let eco = lookup_eco_from_code(code)
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("no opening with ECO code '{code}'"))?;
+        Ok(Opening {
+            name: Some(eco.name),
+            eco: Some(eco.code),
+            moves: eco
+                .moves
+                .into_iter()
+                .map(<Move as From<MoveSerde>>::from)
+                .collect(),
+            ..Opening::default()
+        })
+    }
+}
+
+/// Openings read from `path`, one FEN per line, followed by those of
+/// the ECO codes in `ecos`. Only the standard start position when
+/// there is none.
+pub fn load_openings(path: Option<&Path>, ecos: &[String]) -> Result<Vec<Opening>, String> {
+    let mut openings = Vec::new();
+    if let Some(path) = path {
+        let source =
+            fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        for (n, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let opening = Opening::from_fen(line)
+                .map_err(|err| format!("{}:{}: {err}", path.display(), n + 1))?;
+            openings.push(opening);
+        }
+    }
+    for code in ecos {
+        let opening = Opening::from_eco(code)?;
+        after_moves(&opening).map_err(|err| format!("ECO {code}: {err}"))?;
+        openings.push(opening);
+    }
+    if openings.is_empty() {
+        openings.push(Opening::default());
+    }
+    Ok(openings)
+}
+
+/// The position after the opening moves, for the engines to play from.
+fn after_moves(opening: &Opening) -> Result<Chess, String> {
+    opening
+        .moves
+        .iter()
+        .try_fold(opening.position.clone(), |game, m| {
+            game.play(m).map_err(|err| err.to_string())
+        })
+}
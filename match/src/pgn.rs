@@ -0,0 +1,85 @@
+use shakmaty::{variant::VariantPosition, CastlingMode};
+use ucui_utils::{notation, pgn::write_pgn};
+
+use crate::game::{GameRecord, TimeControl};
+
+/// The moves and how the game ended.
+fn movetext(record: &GameRecord) -> String {
+    let start = VariantPosition::Chess(record.opening.position.clone());
+    let moves = notation::movetext(&start, &record.moves);
+    let reason = format!("{{{}}}", record.reason);
+    if moves.is_empty() {
+        reason
+    } else {
+        format!("{moves} {reason}")
+    }
+}
+
+/// A game in PGN, `round` counting from 1.
+pub fn export_pgn(record: &GameRecord, round: usize, time_control: TimeControl) -> String {
+    let date = chrono::Utc::now().format("%Y.%m.%d");
+    let mut headers = vec![
+        ("Event", "ucui match".to_string()),
+        ("Date", date.to_string()),
+        ("Round", round.to_string()),
+        ("White", record.white.clone()),
+        ("Black", record.black.clone()),
+        ("Result", record.outcome.to_string()),
+        ("TimeControl", time_control.to_string()),
+    ];
+    if let Some(eco) = &record.opening.eco {
+        headers.push(("ECO", eco.clone()));
+    }
+    if let Some(name) = &record.opening.name {
+        headers.push(("Opening", name.clone()));
+    }
+    if record.opening.castling_mode == CastlingMode::Chess960 {
+        headers.push(("Variant", "Chess960".to_string()));
+    }
+    if let Some(fen) = record.opening.fen() {
+        headers.push(("FEN", fen));
+        headers.push(("SetUp", "1".to_string()));
+    }
+    headers.push(("Termination", record.termination.to_string()));
+
+    // a blank line between games
+    let mut pgn = write_pgn(&headers, &movetext(record), &record.outcome.to_string());
+    pgn.push('\n');
+    pgn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::Termination, opening::Opening};
+    use shakmaty::{variant::Variant, Color, Outcome};
+    use std::time::Duration;
+
+    #[test]
+    fn writes_games() {
+        let record = GameRecord {
+            white: "greedy".into(),
+            black: "random".into(),
+            opening: Opening::default(),
+            moves: notation::parse_moves(&VariantPosition::new(Variant::Chess), "f3 e5 g4 Qh4#")
+                .unwrap(),
+            outcome: Outcome::Decisive {
+                winner: Color::Black,
+            },
+            termination: Termination::Normal,
+            reason: "checkmate".into(),
+        };
+        let pgn = export_pgn(
+            &record,
+            3,
+            TimeControl {
+                time: Duration::from_secs(60),
+                increment: Duration::from_millis(500),
+            },
+        );
+        assert!(pgn.contains("[Round \"3\"]\n"));
+        assert!(pgn.contains("[TimeControl \"60+0.5\"]\n"));
+        assert!(!pgn.contains("[FEN "));
+        assert!(pgn.ends_with("\n1. f3 e5 2. g4 Qh4# {checkmate} 0-1\n\n"));
+    }
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use ucui_utils::notation::Ply;
+
+/// A list of moves as `/convert` writes it.
+#[derive(Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Conversion {
+    /// The position the moves are played from.
+    pub fen: String,
+    pub moves: Vec<Ply>,
+    /// The moves as PGN movetext, in SAN.
+    pub movetext: String,
+}
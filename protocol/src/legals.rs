@@ -0,0 +1,62 @@
+use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
+use ts_rs::TS;
+use ucui_utils::{MoveSerde, RoleSerde};
+
+/// A move of `/legals`, with the details asked for. Details not asked
+/// for are left out.
+#[derive(Clone, Deserialize, TS)]
+#[ts(export)]
+pub struct LegalMove {
+    #[serde(rename = "move")]
+    pub _move: MoveSerde,
+    /// With "+" or "#" when the move gives check or mates.
+    #[ts(optional)]
+    pub san: Option<String>,
+    #[ts(optional)]
+    pub uci: Option<String>,
+    /// The position after the move.
+    #[ts(optional)]
+    pub fen: Option<String>,
+    #[ts(optional)]
+    pub flags: Option<MoveFlags>,
+}
+
+impl Serialize for LegalMove {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("move", &self._move)?;
+        if let Some(san) = &self.san {
+            map.serialize_entry("san", san)?;
+        }
+        if let Some(uci) = &self.uci {
+            map.serialize_entry("uci", uci)?;
+        }
+        if let Some(fen) = &self.fen {
+            map.serialize_entry("fen", fen)?;
+        }
+        if let Some(flags) = &self.flags {
+            map.serialize_entry("flags", flags)?;
+        }
+        map.end()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct MoveFlags {
+    pub capture: bool,
+    pub check: bool,
+    pub checkmate: bool,
+    pub promotion: bool,
+}
+
+/// The moves of a piece role, as `/legals` groups them.
+#[derive(Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct RoleMoves {
+    pub role: RoleSerde,
+    pub moves: Vec<LegalMove>,
+}
@@ -0,0 +1,108 @@
+//! Messages exchanged between the server and its clients
+//!
+//! `/engine` speaks `ClientMessage` and `ServerMessage`, `/games` and
+//! `/games/{id}` send `MonitorMessage`, `/legals` answers with
+//! `LegalMove` or `RoleMoves`, `/position/validate` with `PositionCheck`
+//! and `/convert` with `Conversion`. The TypeScript definitions of the
+//! clients are generated from these types by `cargo test -p
+//! ucui-protocol`, in `clients/apps/lib/ucui/protocol`, along with the
+//! protocol version.
+//!
+//! Clients give the version they speak with `protocol=VERSION` when
+//! connecting, and get the one of the server in `Ready`. Any change to
+//! the shape of a message bumps `PROTOCOL_VERSION`, and a change older
+//! clients cannot cope with also bumps `MIN_PROTOCOL_VERSION`.
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{Color, Outcome};
+use ts_rs::TS;
+use ucui_utils::MoveSerde;
+
+mod convert;
+mod legals;
+mod monitor;
+mod play;
+mod position;
+
+pub use convert::*;
+pub use legals::*;
+pub use monitor::*;
+pub use play::*;
+pub use position::*;
+
+/// Version of the protocol spoken by the server.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest version of the protocol the server still speaks.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Whether the server can talk to a client speaking `version`.
+pub fn compatible(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, TS)]
+#[serde(tag = "_tag")]
+#[ts(export)]
+pub enum Score {
+    CentiPawns { score: i32 },
+    Mate { moves: i8 },
+    None,
+}
+
+/// An opening of the ECO classification.
+#[derive(Serialize, Deserialize, Clone, TS)]
+#[ts(export)]
+pub struct Eco {
+    pub code: String,
+    pub name: String,
+    pub fen: String,
+    pub moves: Vec<MoveSerde>,
+    pub pgn: String,
+}
+
+pub fn outcome_string(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Draw => "½-½",
+        Outcome::Decisive { winner } => {
+            if winner == Color::White {
+                "1-0"
+            } else {
+                "0-1"
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes the versions next to the type definitions, the clients
+    /// send `PROTOCOL_VERSION` and check the one in `Ready`.
+    #[test]
+    fn export_version() {
+        let Some(dir) = std::env::var_os("TS_RS_EXPORT_DIR") else {
+            return;
+        };
+        let dir = std::path::PathBuf::from(dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("version.ts"),
+            format!(
+                "// This file was generated by ucui-protocol. Do not edit this file manually.\n\n\
+                 export const PROTOCOL_VERSION = {PROTOCOL_VERSION};\n\
+                 export const MIN_PROTOCOL_VERSION = {MIN_PROTOCOL_VERSION};\n"
+            ),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn versions() {
+        assert!(compatible(PROTOCOL_VERSION));
+        assert!(compatible(MIN_PROTOCOL_VERSION));
+        assert!(!compatible(PROTOCOL_VERSION + 1));
+        assert!(!compatible(0));
+    }
+}
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::{san::SanPlus, variant::VariantPosition, CastlingMode, Move, Position};
+use ts_rs::TS;
+use ucui_utils::variant::{fen, VariantSerde};
+
+use crate::Score;
+
+/// Time left to each player, in milliseconds.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct Clocks {
+    #[ts(type = "number")]
+    pub white: i64,
+    #[ts(type = "number")]
+    pub black: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct MonitorMove {
+    pub san: String,
+    pub uci: String,
+    /// The position after the move.
+    pub fen: String,
+    pub clock: Option<Clocks>,
+    pub score: Score,
+}
+
+impl MonitorMove {
+    pub fn new(
+        before: &VariantPosition,
+        m: &Move,
+        castling_mode: CastlingMode,
+        clock: Option<Clocks>,
+        score: Score,
+    ) -> Self {
+        let mut after = before.clone();
+        let san = SanPlus::from_move_and_play_unchecked(&mut after, m).to_string();
+        MonitorMove {
+            san,
+            uci: m.to_uci(castling_mode).to_string(),
+            fen: fen(&after),
+            clock,
+            score,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct GameOutcome {
+    pub outcome: String,
+    pub reason: String,
+}
+
+/// Everything a spectator gets to know about a game.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct GameRecord {
+    pub id: String,
+    pub white: String,
+    pub black: String,
+    pub variant: VariantSerde,
+    pub chess960: bool,
+    #[serde(rename = "startFen")]
+    pub start_fen: String,
+    pub fen: String,
+    pub moves: Vec<MonitorMove>,
+    pub clock: Option<Clocks>,
+    pub outcome: Option<GameOutcome>,
+    /// When the game started, RFC 3339.
+    pub started: String,
+    /// Version of the monitor when the game last changed.
+    #[ts(type = "number")]
+    pub version: u128,
+}
+
+impl GameRecord {
+    pub fn new(
+        id: String,
+        white: String,
+        black: String,
+        game: &VariantPosition,
+        chess960: bool,
+        clock: Option<Clocks>,
+    ) -> Self {
+        GameRecord {
+            id,
+            white,
+            black,
+            variant: game.variant().into(),
+            chess960,
+            start_fen: fen(game),
+            fen: fen(game),
+            moves: Vec::new(),
+            clock,
+            outcome: None,
+            started: chrono::Utc::now().to_rfc3339(),
+            version: 0,
+        }
+    }
+
+    /// Adds the moves played from `first` before the game came to
+    /// the server, `first` being the position the record was made from.
+    pub fn with_history(
+        mut self,
+        first: &VariantPosition,
+        moves: &[Move],
+        castling_mode: CastlingMode,
+    ) -> Self {
+        let mut game = first.clone();
+        for m in moves {
+            self.moves
+                .push(MonitorMove::new(&game, m, castling_mode, None, Score::None));
+            game.play_unchecked(m);
+        }
+        self.fen = fen(&game);
+        self
+    }
+
+    pub fn summary(&self) -> GameSummary {
+        GameSummary {
+            id: self.id.clone(),
+            white: self.white.clone(),
+            black: self.black.clone(),
+            variant: self.variant,
+            fen: self.fen.clone(),
+            moves: self.moves.len(),
+            outcome: self.outcome.as_ref().map(|o| o.outcome.clone()),
+            version: self.version,
+        }
+    }
+}
+
+/// A game in the list of `/games`.
+#[derive(Clone, Debug, Serialize, TS)]
+#[ts(export)]
+pub struct GameSummary {
+    pub id: String,
+    pub white: String,
+    pub black: String,
+    pub variant: VariantSerde,
+    pub fen: String,
+    pub moves: usize,
+    pub outcome: Option<String>,
+    #[ts(type = "number")]
+    pub version: u128,
+}
+
+#[derive(Clone, Serialize, TS)]
+#[serde(tag = "_tag")]
+#[ts(export)]
+pub enum MonitorMessage {
+    /// All games, sent first and again after falling behind.
+    Init {
+        #[ts(type = "number")]
+        version: u128,
+        games: Vec<GameSummary>,
+    },
+    /// Games started, changed or gone since the last message.
+    Diff {
+        #[ts(type = "number")]
+        version: u128,
+        added: Vec<GameSummary>,
+        changed: Vec<GameSummary>,
+        removed: Vec<String>,
+    },
+    /// The game followed, as it stands when following starts.
+    Game {
+        game: GameRecord,
+    },
+    /// A move in the game followed, `ply` counting from 0.
+    Move {
+        ply: usize,
+        #[serde(rename = "move")]
+        _move: MonitorMove,
+    },
+    Outcome {
+        outcome: GameOutcome,
+    },
+    /// The game followed is over and gone from the server.
+    Gone,
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use shakmaty::{san::SanPlus, variant::VariantPosition, Move, Outcome, Position};
+use ts_rs::TS;
+use ucui_utils::{
+    variant::{self, fen, PocketsSerde, RemainingChecksSerde, VariantSerde},
+    ColorSerde, MoveSerde, RoleSerde, SquareSerde,
+};
+
+use crate::{outcome_string, Score, PROTOCOL_VERSION};
+
+#[derive(Serialize, Deserialize, TS)]
+#[serde(tag = "_tag")]
+#[ts(export)]
+pub enum ServerMessage {
+    /// Waiting for another client to join with `code`.
+    Waiting {
+        code: String,
+    },
+    /// Both clients are there, this one playing `color`.
+    Joined {
+        color: ColorSerde,
+    },
+    Ready {
+        /// Version of the protocol spoken by the server.
+        protocol: u32,
+        name: String,
+        fen: String,
+        chess960: bool,
+        handicap: Option<String>,
+        variant: VariantSerde,
+        turn: ColorSerde,
+        #[serde(rename = "legalMoves")]
+        legal_moves: Vec<MoveSerde>,
+        pockets: Option<PocketsSerde>,
+        #[serde(rename = "remainingChecks")]
+        remaining_checks: Option<RemainingChecksSerde>,
+    },
+    Position {
+        #[serde(rename = "legalMoves")]
+        legal_moves: Vec<MoveSerde>,
+        fen: String,
+        pockets: Option<PocketsSerde>,
+        #[serde(rename = "remainingChecks")]
+        remaining_checks: Option<RemainingChecksSerde>,
+    },
+    EngineMove {
+        #[serde(rename = "move")]
+        _move: MoveSerde,
+        from: Vec<MoveSerde>,
+        check: String,
+        fen: String,
+        score: Score,
+        pockets: Option<PocketsSerde>,
+        #[serde(rename = "remainingChecks")]
+        remaining_checks: Option<RemainingChecksSerde>,
+    },
+    Outcome {
+        outcome: String,
+        reason: String,
+    },
+    /// The engine's move for the player, as much of it as `level`
+    /// gives away, and the number of hints given so far.
+    Hint {
+        level: HintLevel,
+        role: Option<RoleSerde>,
+        from: Option<SquareSerde>,
+        to: Option<SquareSerde>,
+        #[serde(rename = "move")]
+        _move: Option<MoveSerde>,
+        san: Option<String>,
+        score: Score,
+        hints: u32,
+    },
+    /// The player left the repertoire with `played`, instead of one of
+    /// the `expected` moves, all in SAN. The training is over.
+    Deviation {
+        line: usize,
+        played: String,
+        expected: Vec<String>,
+    },
+    /// The player went through a line of the repertoire, the training
+    /// is over.
+    LineCompleted {
+        line: usize,
+    },
+    /// What the client sent could not be taken, `context` being the
+    /// culprit when there is one. The game goes on, unless the socket
+    /// gets closed next.
+    Error {
+        code: ErrorCode,
+        message: String,
+        context: Option<String>,
+    },
+}
+
+/// What was wrong with what the client sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum ErrorCode {
+    /// Not a message of the protocol.
+    InvalidMessage,
+    /// A move that cannot be played in the position.
+    IllegalMove,
+    /// A negative clock time.
+    InvalidTime,
+    /// A move or a hint when it is not the client's turn.
+    OutOfTurn,
+    /// A hint the engine did not come up with in time.
+    NoHint,
+    /// A game that cannot start as asked, the socket gets closed.
+    InvalidStart,
+    /// A version of the protocol the server does not speak, the socket
+    /// gets closed.
+    UnsupportedProtocol,
+}
+
+/// How much of the engine's move a hint shows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum HintLevel {
+    /// The piece to move.
+    Piece,
+    /// The square to move to.
+    Destination,
+    #[default]
+    Move,
+}
+
+impl ServerMessage {
+    pub fn waiting(code: String) -> Self {
+        ServerMessage::Waiting { code }
+    }
+
+    pub fn joined(color: ColorSerde) -> Self {
+        ServerMessage::Joined { color }
+    }
+
+    pub fn ready(
+        name: String,
+        game: &VariantPosition,
+        chess960: bool,
+        handicap: Option<String>,
+        legal_moves: Vec<MoveSerde>,
+    ) -> Self {
+        ServerMessage::Ready {
+            protocol: PROTOCOL_VERSION,
+            name,
+            fen: fen(game),
+            chess960,
+            handicap,
+            variant: game.variant().into(),
+            turn: game.turn().into(),
+            legal_moves,
+            pockets: variant::pockets(game),
+            remaining_checks: variant::remaining_checks(game),
+        }
+    }
+
+    pub fn hint(
+        level: HintLevel,
+        game: &VariantPosition,
+        m: Move,
+        score: Score,
+        hints: u32,
+    ) -> Self {
+        let piece = level != HintLevel::Destination;
+        let destination = level != HintLevel::Piece;
+        let full = level == HintLevel::Move;
+        ServerMessage::Hint {
+            level,
+            role: piece.then(|| m.role().into()),
+            from: m.from().filter(|_| piece).map(SquareSerde::from),
+            to: destination.then(|| m.to().into()),
+            san: full.then(|| SanPlus::from_move(game.clone(), &m).to_string()),
+            _move: full.then(|| m.into()),
+            score,
+            hints,
+        }
+    }
+
+    pub fn error(code: ErrorCode, message: &str, context: Option<String>) -> Self {
+        ServerMessage::Error {
+            code,
+            message: message.into(),
+            context,
+        }
+    }
+
+    pub fn invalid_message(err: &serde_json::Error, text: &str) -> Self {
+        ServerMessage::error(
+            ErrorCode::InvalidMessage,
+            &format!("invalid message: {err}"),
+            Some(text.into()),
+        )
+    }
+
+    /// With the move in UCI, and the position it was played in.
+    pub fn illegal_move(m: &Move, game: &VariantPosition) -> Self {
+        let castling_mode = game.castles().mode();
+        ServerMessage::error(
+            ErrorCode::IllegalMove,
+            &format!("illegal move in {}", fen(game)),
+            Some(m.to_uci(castling_mode).to_string()),
+        )
+    }
+
+    pub fn position(legal_moves: Vec<MoveSerde>, game: &VariantPosition) -> Self {
+        ServerMessage::Position {
+            legal_moves,
+            fen: fen(game),
+            pockets: variant::pockets(game),
+            remaining_checks: variant::remaining_checks(game),
+        }
+    }
+
+    pub fn engine_move(
+        m: Move,
+        from: Vec<MoveSerde>,
+        check: String,
+        game: &VariantPosition,
+        score: Score,
+    ) -> Self {
+        ServerMessage::EngineMove {
+            _move: m.into(),
+            from,
+            check,
+            fen: fen(game),
+            score,
+            pockets: variant::pockets(game),
+            remaining_checks: variant::remaining_checks(game),
+        }
+    }
+
+    pub fn outcome_with_reason(outcome: Outcome, reason: &str) -> Self {
+        ServerMessage::Outcome {
+            outcome: outcome_string(outcome).into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, TS)]
+#[serde(tag = "_tag")]
+#[ts(export)]
+pub enum ClientMessage {
+    Move {
+        #[serde(rename = "move")]
+        _move: MoveSerde,
+        #[ts(type = "number")]
+        white_time: i64,
+        #[ts(type = "number")]
+        black_time: i64,
+    },
+    /// Asks the engine for a move on the player's turn.
+    Hint {
+        #[serde(default)]
+        level: HintLevel,
+    },
+}
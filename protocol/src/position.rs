@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+use ucui_utils::ColorSerde;
+
+use crate::{Eco, GameOutcome};
+
+/// What `/position/validate` finds out about a FEN.
+#[derive(Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PositionCheck {
+    /// A game can start from the position.
+    pub valid: bool,
+    pub errors: Vec<PositionError>,
+    /// The FEN as the server writes it, when valid.
+    pub fen: Option<String>,
+    /// Games from the position are Chess960, when asked for or when its
+    /// castling rights are only possible in Chess960.
+    pub chess960: bool,
+    /// The side to move, when the FEN can be read.
+    pub turn: Option<ColorSerde>,
+    /// Set when the game is already over in the position.
+    pub outcome: Option<GameOutcome>,
+    /// The opening reaching the position, by any move order.
+    pub eco: Option<Eco>,
+}
+
+#[derive(Clone, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub struct PositionError {
+    pub code: PositionErrorCode,
+    pub message: String,
+}
+
+/// What is wrong with a FEN, the first ones when it cannot be read, the
+/// others when the position it describes is not possible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[ts(export)]
+pub enum PositionErrorCode {
+    InvalidFen,
+    /// Piece placement that cannot be read.
+    InvalidBoard,
+    InvalidPocket,
+    InvalidTurn,
+    InvalidCastling,
+    InvalidEnPassant,
+    InvalidRemainingChecks,
+    /// Halfmove clock or move number.
+    InvalidCounters,
+    EmptyBoard,
+    MissingKing,
+    TooManyKings,
+    PawnsOnBackrank,
+    /// The side not to move is in check.
+    OppositeCheck,
+    /// A check no move could have given.
+    ImpossibleCheck,
+    /// More pieces than promotions allow.
+    TooMuchMaterial,
+    /// Not a position of the variant.
+    Variant,
+}
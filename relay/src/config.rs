@@ -0,0 +1,50 @@
+use clap::Parser;
+use std::{net::IpAddr, sync::OnceLock};
+
+#[derive(Parser)]
+#[command(version, about, long_about = None)]
+pub struct Config {
+    /// Interface to bind to
+    #[arg(short, long, value_name = "INTERFACE", default_value = "127.0.0.1")]
+    interface: IpAddr,
+
+    /// Port to bind to
+    #[arg(short, long, value_name = "PORT", default_value = "8010")]
+    port: u16,
+
+    /// Path to a UCI engine
+    #[arg(short, long, value_name = "ENGINE")]
+    engine: String,
+
+    /// Optional arguments to pass to the engine (separated by ";")
+    ///
+    /// Example: --engine-args '--uci;--quiet'
+    #[arg(long, value_name = "ARGS", allow_hyphen_values = true)]
+    engine_args: Option<String>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(Config::parse)
+}
+
+pub fn get_interface() -> IpAddr {
+    config().interface
+}
+
+pub fn get_port() -> u16 {
+    config().port
+}
+
+pub fn get_engine() -> String {
+    config().engine.clone()
+}
+
+pub fn get_engine_args() -> Vec<String> {
+    config()
+        .engine_args
+        .clone()
+        .map(|args| args.split(";").map(|arg| arg.to_string()).collect())
+        .unwrap_or_default()
+}
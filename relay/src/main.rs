@@ -0,0 +1,73 @@
+//! Exposes a local engine on a TCP port, one engine process per
+//! connection, for `ucui-server --engine tcp://HOST:PORT`.
+
+use std::{
+    io,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    process::{Command, Stdio},
+    thread,
+};
+
+use crate::config::{get_engine, get_engine_args, get_interface, get_port};
+
+mod config;
+
+fn relay(stream: TcpStream) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut child = Command::new(get_engine())
+        .args(get_engine_args())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    log::info!("[{peer}] connected, engine pid {}", child.id());
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| io::Error::other("engine has no stdin"))?;
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| io::Error::other("engine has no stdout"))?;
+    let mut from_client = stream.try_clone()?;
+    let mut to_client = stream;
+
+    let output = thread::spawn(move || {
+        let _ = io::copy(&mut stdout, &mut to_client);
+        let _ = to_client.shutdown(Shutdown::Both);
+    });
+
+    let _ = io::copy(&mut from_client, &mut stdin);
+    drop(stdin);
+    let _ = child.kill();
+    let _ = child.wait();
+    let _ = output.join();
+    log::info!("[{peer}] disconnected");
+    Ok(())
+}
+
+fn main() {
+    let _ = crate::config::config();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| format!("{}=info", env!("CARGO_CRATE_NAME")).into()),
+        )
+        .init();
+    let addr = SocketAddr::from((get_interface(), get_port()));
+    let listener = TcpListener::bind(addr).expect("failed to bind");
+    log::info!("relaying {} on {}", get_engine(), addr);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                thread::spawn(move || {
+                    if let Err(err) = relay(stream) {
+                        log::error!("relay failed: {err}");
+                    }
+                });
+            }
+            Err(err) => log::warn!("connection failed: {err}"),
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! Engine review of finished games
+//!
+//! `POST /annotate` queues a game, given as PGN or as the id of an
+//! archived game, and answers with a job id. Jobs run one after the
+//! other, each with its own engine searching every position within
+//! the same limit. `/annotate/{id}` tells how far the job is and holds
+//! the annotated game once done, `/annotate/{id}/pgn` has it in PGN
+//! with `[%eval]` comments.
+
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    san::{San, SanPlus},
+    variant::VariantPosition,
+    CastlingMode, Color, Move, Outcome, Position,
+};
+use tokio::sync::{Mutex, Semaphore};
+use ucui_engine::{EngineMessage, Score, SearchLimit};
+use ucui_protocol::outcome_string;
+use ucui_utils::{
+    notation::annotated_movetext,
+    pgn::{parse_pgn, write_pgn, PgnGame},
+    variant::{fen, VariantSerde},
+};
+use uuid::Uuid;
+
+use crate::{config::get_default_profile, state::UcuiState};
+
+/// Oldest jobs are dropped past this number.
+const MAX_JOBS: usize = 100;
+
+const DEFAULT_DEPTH: u32 = 12;
+const MAX_DEPTH: u32 = 40;
+const MAX_MOVE_TIME: u64 = 60_000;
+
+/// Evaluations beyond this many centipawns, mates included, are all
+/// about as winning.
+const MAX_CENTIPAWNS: i32 = 1000;
+
+/// Drops in evaluation, from the point of view of the side that
+/// moved, from which a move is an inaccuracy, a mistake or a blunder.
+/// Win chances go from 0 to 100.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(tag = "_tag")]
+pub enum Thresholds {
+    CentiPawns {
+        inaccuracy: i32,
+        mistake: i32,
+        blunder: i32,
+    },
+    WinChance {
+        inaccuracy: f64,
+        mistake: f64,
+        blunder: f64,
+    },
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds::CentiPawns {
+            inaccuracy: 50,
+            mistake: 100,
+            blunder: 300,
+        }
+    }
+}
+
+impl Thresholds {
+    fn classify(&self, loss: &Loss) -> Option<Classification> {
+        let (loss, [inaccuracy, mistake, blunder]) = match *self {
+            Thresholds::CentiPawns {
+                inaccuracy,
+                mistake,
+                blunder,
+            } => (
+                f64::from(loss.centipawns),
+                [inaccuracy, mistake, blunder].map(f64::from),
+            ),
+            Thresholds::WinChance {
+                inaccuracy,
+                mistake,
+                blunder,
+            } => (loss.win_chance, [inaccuracy, mistake, blunder]),
+        };
+        if loss >= blunder {
+            Some(Classification::Blunder)
+        } else if loss >= mistake {
+            Some(Classification::Mistake)
+        } else if loss >= inaccuracy {
+            Some(Classification::Inaccuracy)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum Classification {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    fn nag(&self) -> &'static str {
+        match self {
+            Classification::Inaccuracy => "$6",
+            Classification::Mistake => "$2",
+            Classification::Blunder => "$4",
+        }
+    }
+}
+
+/// An evaluation from white's point of view.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "_tag")]
+pub enum Eval {
+    CentiPawns {
+        score: i32,
+    },
+    /// Negative when black mates.
+    Mate {
+        moves: i32,
+    },
+    /// The game is over, no move to evaluate.
+    Over {
+        result: String,
+    },
+}
+
+impl Eval {
+    /// Engines score from the point of view of the side to move.
+    fn from_score(score: Score, turn: Color) -> Option<Self> {
+        let sign = turn.fold_wb(1, -1);
+        match score {
+            Score::CentiPawns { score } => Some(Eval::CentiPawns {
+                score: sign * score,
+            }),
+            Score::Mate { moves: 0 } => Some(Eval::Over {
+                result: outcome_string(Outcome::Decisive {
+                    winner: turn.other(),
+                })
+                .into(),
+            }),
+            Score::Mate { moves } => Some(Eval::Mate {
+                moves: sign * i32::from(moves),
+            }),
+            Score::None => None,
+        }
+    }
+
+    fn centipawns(&self, color: Color) -> i32 {
+        let white = match self {
+            Eval::CentiPawns { score } => (*score).clamp(-MAX_CENTIPAWNS, MAX_CENTIPAWNS),
+            Eval::Mate { moves } => moves.signum() * MAX_CENTIPAWNS,
+            Eval::Over { result } => match result.as_str() {
+                "1-0" => MAX_CENTIPAWNS,
+                "0-1" => -MAX_CENTIPAWNS,
+                _ => 0,
+            },
+        };
+        color.fold_wb(white, -white)
+    }
+
+    /// As on lichess, from 0 to 100.
+    fn win_chance(&self, color: Color) -> f64 {
+        let cp = f64::from(self.centipawns(color));
+        50.0 + 50.0 * (2.0 / (1.0 + (-0.003_682_08 * cp).exp()) - 1.0)
+    }
+
+    /// As in `[%eval]` comments, `None` once the game is over.
+    fn pgn(&self) -> Option<String> {
+        match self {
+            Eval::CentiPawns { score } => Some(format!("{:.2}", f64::from(*score) / 100.0)),
+            Eval::Mate { moves } => Some(format!("#{moves}")),
+            Eval::Over { .. } => None,
+        }
+    }
+}
+
+/// What the move played cost, compared to the best move.
+#[derive(Clone, Debug, Serialize)]
+pub struct Loss {
+    pub centipawns: i32,
+    #[serde(rename = "winChance")]
+    pub win_chance: f64,
+}
+
+impl Loss {
+    fn new(best: &Eval, played: &Eval, color: Color) -> Self {
+        Loss {
+            centipawns: (best.centipawns(color) - played.centipawns(color)).max(0),
+            win_chance: (best.win_chance(color) - played.win_chance(color)).max(0.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AnnotatedMove {
+    pub san: String,
+    pub uci: String,
+    /// Position after the move.
+    pub fen: String,
+    /// Evaluation after the move, `None` when the engine gave none.
+    pub eval: Option<Eval>,
+    /// The engine's move when it is not the one played.
+    #[serde(rename = "bestMove")]
+    pub best_move: Option<String>,
+    #[serde(rename = "bestEval")]
+    pub best_eval: Option<Eval>,
+    pub loss: Option<Loss>,
+    pub classification: Option<Classification>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AnnotatedGame {
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<String>,
+    pub variant: VariantSerde,
+    pub chess960: bool,
+    #[serde(rename = "startFen")]
+    pub start_fen: String,
+    pub limit: SearchLimit,
+    pub thresholds: Thresholds,
+    pub moves: Vec<AnnotatedMove>,
+    /// The game as read, to write it back.
+    #[serde(skip)]
+    game: PgnGame,
+}
+
+impl AnnotatedGame {
+    fn pgn(&self) -> String {
+        let mut headers = self.game.headers.clone();
+        headers.retain(|(tag, _)| tag != "Annotator");
+        headers.push(("Annotator".into(), "ucui".into()));
+        let movetext = annotated_movetext(&self.game.start, &self.game.moves, |i, _| {
+            let m = &self.moves[i];
+            let mut annotations: Vec<String> = Vec::new();
+            if let Some(classification) = m.classification {
+                annotations.push(classification.nag().into());
+            }
+            let mut comment: Vec<String> = Vec::new();
+            if let Some(eval) = m.eval.as_ref().and_then(Eval::pgn) {
+                comment.push(format!("[%eval {eval}]"));
+            }
+            if let (Some(classification), Some(best)) = (m.classification, &m.best_move) {
+                comment.push(format!("{classification:?}. {best} was best."));
+            }
+            if !comment.is_empty() {
+                annotations.push(format!("{{{}}}", comment.join(" ")));
+            }
+            annotations
+        });
+        write_pgn(&headers, &movetext, self.result.as_deref().unwrap_or("*"))
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "_tag")]
+pub enum JobState {
+    /// Waiting for `ahead` jobs to finish first.
+    Queued {
+        ahead: usize,
+    },
+    Running {
+        done: usize,
+        total: usize,
+    },
+    Done {
+        game: Box<AnnotatedGame>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// Annotation jobs, the newest last.
+#[derive(Clone)]
+pub struct Annotations {
+    jobs: Arc<Mutex<VecDeque<(String, JobState)>>>,
+    /// One engine at a time, jobs get it in the order they came.
+    engine: Arc<Semaphore>,
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Annotations {
+            jobs: Arc::default(),
+            engine: Arc::new(Semaphore::new(1)),
+        }
+    }
+}
+
+impl Annotations {
+    async fn set(&self, id: &str, state: JobState) {
+        let mut jobs = self.jobs.lock().await;
+        if let Some((_, job)) = jobs.iter_mut().find(|(job_id, _)| job_id == id) {
+            *job = state;
+        }
+    }
+
+    async fn get(&self, id: &str) -> Option<JobState> {
+        let jobs = self.jobs.lock().await;
+        jobs.iter()
+            .find(|(job_id, _)| job_id == id)
+            .map(|(_, state)| match state {
+                JobState::Queued { .. } => JobState::Queued {
+                    ahead: jobs
+                        .iter()
+                        .take_while(|(job_id, _)| job_id != id)
+                        .filter(|(_, state)| {
+                            matches!(state, JobState::Queued { .. } | JobState::Running { .. })
+                        })
+                        .count(),
+                },
+                state => state.clone(),
+            })
+    }
+
+    async fn queue(&self, game: PgnGame, limit: SearchLimit, thresholds: Thresholds) -> String {
+        let id = Uuid::new_v4().to_string();
+        {
+            let mut jobs = self.jobs.lock().await;
+            if jobs.len() >= MAX_JOBS {
+                // unfinished jobs are still being worked on
+                if let Some(index) = jobs.iter().position(|(_, state)| {
+                    matches!(state, JobState::Done { .. } | JobState::Failed { .. })
+                }) {
+                    let _ = jobs.remove(index);
+                }
+            }
+            jobs.push_back((id.clone(), JobState::Queued { ahead: 0 }));
+        }
+
+        let annotations = self.clone();
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = annotations.engine.clone().acquire_owned().await else {
+                return;
+            };
+            let total = game.moves.len() + 1;
+            annotations
+                .set(&job_id, JobState::Running { done: 0, total })
+                .await;
+            let progress = annotations.clone();
+            let progress_id = job_id.clone();
+            let handle = tokio::runtime::Handle::current();
+            let annotated = tokio::task::spawn_blocking(move || {
+                annotate(game, limit, thresholds, |done| {
+                    handle.block_on(progress.set(&progress_id, JobState::Running { done, total }))
+                })
+            })
+            .await;
+            let state = match annotated {
+                Ok(Ok(game)) => JobState::Done {
+                    game: Box::new(game),
+                },
+                Ok(Err(error)) => JobState::Failed { error },
+                Err(err) => JobState::Failed {
+                    error: err.to_string(),
+                },
+            };
+            annotations.set(&job_id, state).await;
+        });
+        id
+    }
+}
+
+/// Long enough for any engine to answer within `limit`.
+fn search_timeout(limit: SearchLimit) -> Duration {
+    match limit {
+        SearchLimit::Depth { .. } => Duration::from_secs(300),
+        SearchLimit::MoveTime { millis } => Duration::from_millis(millis) + Duration::from_secs(30),
+    }
+}
+
+/// Searches every position of `game`, calling `progress` with the
+/// number of positions done.
+fn annotate<F: Fn(usize)>(
+    game: PgnGame,
+    limit: SearchLimit,
+    thresholds: Thresholds,
+    progress: F,
+) -> Result<AnnotatedGame, String> {
+    let castling_mode = CastlingMode::from_chess960(game.chess960);
+    let mut positions: Vec<VariantPosition> = vec![game.start.clone()];
+    for m in &game.moves {
+        let mut next = positions[positions.len() - 1].clone();
+        next.play_unchecked(m);
+        positions.push(next);
+    }
+
+    let engine = get_default_profile().connect();
+    if game.variant != shakmaty::variant::Variant::Chess {
+        engine.set_variant(game.variant);
+    }
+    if game.chess960 {
+        engine.set_chess960(true);
+    }
+    engine.new_game();
+
+    // the engine's move and evaluation of each position
+    let mut searched: Vec<(Option<Move>, Option<Eval>)> = Vec::new();
+    for (done, position) in positions.iter().enumerate() {
+        if let Some(outcome) = position.outcome() {
+            searched.push((
+                None,
+                Some(Eval::Over {
+                    result: outcome_string(outcome).into(),
+                }),
+            ));
+        } else {
+            engine.search(fen(position), limit);
+            match engine.recv_timeout(search_timeout(limit)) {
+                Ok(EngineMessage::BestMove { move_, score }) => {
+                    searched.push((Some(move_.into()), Eval::from_score(score, position.turn())))
+                }
+                Ok(_) | Err(_) => {
+                    engine.stop();
+                    return Err(format!("the engine did not answer at ply {done}"));
+                }
+            }
+        }
+        progress(done + 1);
+    }
+    engine.stop();
+
+    let moves = game
+        .moves
+        .iter()
+        .enumerate()
+        .map(|(ply, m)| {
+            let before = &positions[ply];
+            let color = before.turn();
+            let mut after = before.clone();
+            let san = SanPlus::from_move_and_play_unchecked(&mut after, m).to_string();
+            let (best, best_eval) = searched[ply].clone();
+            let eval = searched[ply + 1].1.clone();
+            let best_move = best
+                .filter(|best| best != m)
+                .map(|best| San::from_move(before, &best).to_string());
+            let loss = match (&best_eval, &eval) {
+                (Some(_), Some(_)) if best_move.is_none() => Some(Loss {
+                    centipawns: 0,
+                    win_chance: 0.0,
+                }),
+                (Some(best), Some(played)) => Some(Loss::new(best, played, color)),
+                _ => None,
+            };
+            AnnotatedMove {
+                san,
+                uci: m.to_uci(castling_mode).to_string(),
+                fen: fen(&after),
+                classification: loss.as_ref().and_then(|loss| thresholds.classify(loss)),
+                eval,
+                best_move,
+                best_eval,
+                loss,
+            }
+        })
+        .collect();
+
+    Ok(AnnotatedGame {
+        white: game.header("White").map(String::from),
+        black: game.header("Black").map(String::from),
+        result: game.header("Result").map(String::from),
+        variant: game.variant.into(),
+        chess960: game.chess960,
+        start_fen: fen(&game.start),
+        limit,
+        thresholds,
+        moves,
+        game,
+    })
+}
+
+/// Body of `POST /annotate`, with either `pgn` or `archive`, and
+/// either `depth` or `movetime` in milliseconds.
+#[derive(Deserialize)]
+pub struct AnnotateRequest {
+    pgn: Option<String>,
+    /// Id of an archived game.
+    archive: Option<String>,
+    depth: Option<u32>,
+    movetime: Option<u64>,
+    #[serde(default)]
+    thresholds: Thresholds,
+}
+
+#[derive(Serialize)]
+struct Queued {
+    id: String,
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+pub async fn queue(
+    State(state): State<UcuiState>,
+    Json(request): Json<AnnotateRequest>,
+) -> Response {
+    let limit = match (request.depth, request.movetime) {
+        (Some(depth), _) if depth == 0 || depth > MAX_DEPTH => {
+            return bad_request(format!("depth should be from 1 to {MAX_DEPTH}"))
+        }
+        (Some(depth), _) => SearchLimit::Depth { depth },
+        (None, Some(millis)) if millis == 0 || millis > MAX_MOVE_TIME => {
+            return bad_request(format!("movetime should be from 1 to {MAX_MOVE_TIME} ms"))
+        }
+        (None, Some(millis)) => SearchLimit::MoveTime { millis },
+        (None, None) => SearchLimit::Depth {
+            depth: DEFAULT_DEPTH,
+        },
+    };
+    let pgn = match (request.pgn, request.archive) {
+        (Some(pgn), _) => pgn,
+        (None, Some(id)) => match state.archive.get(&id).await {
+            Some(game) => game.pgn(),
+            None => {
+                return (StatusCode::NOT_FOUND, format!("no archived game {id}")).into_response()
+            }
+        },
+        (None, None) => return bad_request("expected a pgn or an archive id".into()),
+    };
+    let game = match parse_pgn(&pgn) {
+        Ok(game) => game,
+        Err(err) => return bad_request(err),
+    };
+    let id = state
+        .annotations
+        .queue(game, limit, request.thresholds)
+        .await;
+    (StatusCode::ACCEPTED, Json(Queued { id })).into_response()
+}
+
+fn not_found(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("no annotation job {id}")).into_response()
+}
+
+pub async fn fetch(State(state): State<UcuiState>, Path(id): Path<String>) -> Response {
+    match state.annotations.get(&id).await {
+        Some(job) => Json(job).into_response(),
+        None => not_found(&id),
+    }
+}
+
+pub async fn fetch_pgn(State(state): State<UcuiState>, Path(id): Path<String>) -> Response {
+    match state.annotations.get(&id).await {
+        Some(JobState::Done { game }) => (
+            [(
+                header::CONTENT_TYPE,
+                "application/x-chess-pgn; charset=utf-8",
+            )],
+            game.pgn(),
+        )
+            .into_response(),
+        Some(_) => (
+            StatusCode::CONFLICT,
+            format!("annotation job {id} is not done"),
+        )
+            .into_response(),
+        None => not_found(&id),
+    }
+}
@@ -0,0 +1,407 @@
+//! Finished games, kept on disk
+//!
+//! With `--archive DIR`, each game is saved when it ends as `ID.pgn`,
+//! next to `ID.json` with what the PGN cannot hold, e.g. the engine
+//! options. `/archive` lists them, `/archive/{id}` and
+//! `/archive/{id}/pgn` fetch one, and `DELETE /archive/{id}` deletes it.
+//! The engine's transcript, when there is one, is saved as
+//! `ID.transcript` for `/admin/transcripts/{id}`.
+
+use std::{cmp::Reverse, path::PathBuf, sync::Arc};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    variant::{Variant, VariantPosition},
+    Chess, Color, EnPassantMode, Move, Position,
+};
+use ucui_eco::find_eco_from_ucis;
+use ucui_engine::Transcript;
+use ucui_utils::{
+    notation::{annotated_movetext, parse_move},
+    pgn::{variant_tag, write_pgn},
+    variant::{parse_position, VariantSerde},
+};
+
+use crate::{
+    monitor::{GameRecord, MonitorMove},
+    state::UcuiState,
+    transcripts::{authorized, unauthorized},
+};
+
+/// The engine a game was played against, and how it was set up.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EngineProfile {
+    pub name: String,
+    pub options: Vec<(String, Option<String>)>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ArchivedGame {
+    pub id: String,
+    pub started: String,
+    pub ended: DateTime<Utc>,
+    pub white: String,
+    pub black: String,
+    pub engine: Option<EngineProfile>,
+    pub variant: VariantSerde,
+    pub chess960: bool,
+    pub handicap: Option<String>,
+    pub eco: Option<String>,
+    pub opening: Option<String>,
+    #[serde(rename = "startFen")]
+    pub start_fen: String,
+    pub moves: Vec<MonitorMove>,
+    /// "1-0", "0-1", "½-½", or "*" when the game was left unfinished.
+    pub result: String,
+    pub reason: Option<String>,
+    /// Hints the player asked for.
+    #[serde(default)]
+    pub hints: u32,
+}
+
+fn standard_start() -> String {
+    shakmaty::fen::Fen::from_position(Chess::default(), EnPassantMode::Legal).to_string()
+}
+
+impl ArchivedGame {
+    pub fn new(
+        record: GameRecord,
+        engine: Option<EngineProfile>,
+        handicap: Option<String>,
+    ) -> Self {
+        let eco = (record.variant.0 == Variant::Chess
+            && !record.chess960
+            && record.start_fen == standard_start())
+        .then(|| {
+            let ucis: Vec<String> = record.moves.iter().map(|m| m.uci.clone()).collect();
+            find_eco_from_ucis(&ucis)
+        })
+        .flatten();
+        let (result, reason) = match record.outcome {
+            Some(outcome) => (outcome.outcome, Some(outcome.reason)),
+            None => ("*".into(), None),
+        };
+        ArchivedGame {
+            id: record.id,
+            started: record.started,
+            ended: Utc::now(),
+            white: record.white,
+            black: record.black,
+            engine,
+            variant: record.variant,
+            chess960: record.chess960,
+            handicap,
+            eco: eco.map(|eco| eco.code.clone()),
+            opening: eco.map(|eco| eco.name.clone()),
+            start_fen: record.start_fen,
+            moves: record.moves,
+            result,
+            reason,
+            hints: 0,
+        }
+    }
+
+    fn summary(&self) -> Summary {
+        Summary {
+            id: self.id.clone(),
+            ended: self.ended,
+            white: self.white.clone(),
+            black: self.black.clone(),
+            engine: self.engine.as_ref().map(|engine| engine.name.clone()),
+            variant: self.variant,
+            eco: self.eco.clone(),
+            opening: self.opening.clone(),
+            result: self.result.clone(),
+            moves: self.moves.len(),
+        }
+    }
+
+    fn pgn_result(&self) -> &str {
+        match self.result.as_str() {
+            "½-½" => "1/2-1/2",
+            result => result,
+        }
+    }
+
+    /// The starting position and the moves, as far as they replay.
+    fn replay(&self) -> (VariantPosition, Vec<Move>) {
+        let start = parse_position(&self.start_fen, self.variant.0, self.chess960)
+            .map(|(start, _)| start)
+            .unwrap_or_else(|err| {
+                log::warn!("Archived game {} has a bad FEN: {err}", self.id);
+                VariantPosition::new(self.variant.0)
+            });
+        let mut position = start.clone();
+        let mut moves = Vec::new();
+        for m in &self.moves {
+            match parse_move(&position, &m.uci) {
+                Ok(m) => {
+                    position.play_unchecked(&m);
+                    moves.push(m);
+                }
+                Err(err) => {
+                    log::warn!("Archived game {} does not replay: {err}", self.id);
+                    break;
+                }
+            }
+        }
+        (start, moves)
+    }
+
+    pub(crate) fn pgn(&self) -> String {
+        let event = match &self.handicap {
+            Some(handicap) => format!("ucui, {handicap}"),
+            None => "ucui".to_string(),
+        };
+        let mut headers = vec![
+            ("Event", event),
+            ("Date", self.ended.format("%Y.%m.%d").to_string()),
+            ("White", self.white.clone()),
+            ("Black", self.black.clone()),
+            ("Result", self.pgn_result().to_string()),
+        ];
+        if let Some(eco) = &self.eco {
+            headers.push(("ECO", eco.clone()));
+        }
+        if let Some(opening) = &self.opening {
+            headers.push(("Opening", opening.clone()));
+        }
+        if self.variant.0 != Variant::Chess {
+            headers.push(("Variant", variant_tag(self.variant.0).into()));
+        } else if self.chess960 {
+            headers.push(("Variant", "Chess960".into()));
+        }
+        if self.start_fen != standard_start() {
+            headers.push(("FEN", self.start_fen.clone()));
+            headers.push(("SetUp", "1".into()));
+        }
+        if let Some(reason) = &self.reason {
+            headers.push(("Termination", reason.clone()));
+        }
+        if self.hints > 0 {
+            headers.push(("Hints", self.hints.to_string()));
+        }
+
+        let (start, moves) = self.replay();
+        let movetext = annotated_movetext(&start, &moves, |i, position| {
+            self.moves[i]
+                .clock
+                .map(|clock| {
+                    let left = match position.turn() {
+                        Color::White => clock.white,
+                        Color::Black => clock.black,
+                    };
+                    format!("{{[%clk {}]}}", clk(left))
+                })
+                .into_iter()
+                .collect()
+        });
+        write_pgn(&headers, &movetext, self.pgn_result())
+    }
+}
+
+/// As in PGN clock comments, H:MM:SS.
+fn clk(millis: i64) -> String {
+    let seconds = millis.max(0) / 1000;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// A game in the list of `/archive`.
+#[derive(Serialize)]
+pub struct Summary {
+    id: String,
+    ended: DateTime<Utc>,
+    white: String,
+    black: String,
+    engine: Option<String>,
+    variant: VariantSerde,
+    eco: Option<String>,
+    opening: Option<String>,
+    result: String,
+    moves: usize,
+}
+
+/// Query of `/archive`, all given filters have to match.
+#[derive(Deserialize)]
+pub struct Filter {
+    /// Games ended on that day or after.
+    from: Option<NaiveDate>,
+    /// Games ended on that day or before.
+    to: Option<NaiveDate>,
+    /// "1-0", "0-1", "½-½" or "1/2-1/2".
+    result: Option<String>,
+    /// Part of the engine's name.
+    engine: Option<String>,
+    /// Start of the ECO code, e.g. "C" or "C50".
+    eco: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, game: &ArchivedGame) -> bool {
+        let day = game.ended.date_naive();
+        self.from.is_none_or(|from| day >= from)
+            && self.to.is_none_or(|to| day <= to)
+            && self
+                .result
+                .as_ref()
+                .is_none_or(|result| *result == game.result || result.as_str() == game.pgn_result())
+            && self.engine.as_ref().is_none_or(|pattern| {
+                game.engine.as_ref().is_some_and(|engine| {
+                    engine.name.to_lowercase().contains(&pattern.to_lowercase())
+                })
+            })
+            && self.eco.as_ref().is_none_or(|prefix| {
+                game.eco
+                    .as_ref()
+                    .is_some_and(|eco| eco.to_uppercase().starts_with(&prefix.to_uppercase()))
+            })
+    }
+}
+
+/// Where games are saved, nowhere when not configured.
+#[derive(Clone, Default)]
+pub struct Archive(Option<Arc<PathBuf>>);
+
+/// Game ids are uuids, anything else does not name a file of the archive.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+impl Archive {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Archive(dir.map(Arc::new))
+    }
+
+    fn path(&self, id: &str, extension: &str) -> Option<PathBuf> {
+        let dir = self.0.as_ref()?;
+        valid_id(id).then(|| dir.join(format!("{id}.{extension}")))
+    }
+
+    pub async fn save(&self, game: ArchivedGame, transcript: Option<Transcript>) {
+        let (Some(json), Some(pgn), Some(log)) = (
+            self.path(&game.id, "json"),
+            self.path(&game.id, "pgn"),
+            self.path(&game.id, "transcript"),
+        ) else {
+            return;
+        };
+        let saved = async {
+            if let Some(dir) = json.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&pgn, game.pgn()).await?;
+            if let Some(transcript) = transcript {
+                tokio::fs::write(&log, transcript.to_string()).await?;
+            }
+            tokio::fs::write(&json, serde_json::to_vec(&game)?).await
+        };
+        match saved.await {
+            Ok(()) => log::info!("Game {} archived", game.id),
+            Err(err) => log::error!("Failed to archive game {}: {err}", game.id),
+        }
+    }
+
+    pub(crate) async fn get(&self, id: &str) -> Option<ArchivedGame> {
+        let bytes = tokio::fs::read(self.path(id, "json")?).await.ok()?;
+        serde_json::from_slice(&bytes)
+            .map_err(|err| log::warn!("Archived game {id} is unreadable: {err}"))
+            .ok()
+    }
+
+    pub(crate) async fn transcript(&self, id: &str) -> Option<String> {
+        tokio::fs::read_to_string(self.path(id, "transcript")?)
+            .await
+            .ok()
+    }
+
+    async fn list(&self, filter: &Filter) -> Vec<Summary> {
+        let Some(dir) = self.0.as_ref() else {
+            return Vec::new();
+        };
+        let mut games = Vec::new();
+        if let Ok(mut entries) = tokio::fs::read_dir(dir.as_ref()).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let path = entry.path();
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                if let Some(game) = self.get(id).await.filter(|game| filter.matches(game)) {
+                    games.push(game.summary());
+                }
+            }
+        }
+        games.sort_by_key(|game| Reverse(game.ended));
+        games
+    }
+
+    async fn delete(&self, id: &str) -> bool {
+        let (Some(json), Some(pgn)) = (self.path(id, "json"), self.path(id, "pgn")) else {
+            return false;
+        };
+        let _ = tokio::fs::remove_file(pgn).await;
+        if let Some(log) = self.path(id, "transcript") {
+            let _ = tokio::fs::remove_file(log).await;
+        }
+        tokio::fs::remove_file(json).await.is_ok()
+    }
+}
+
+fn not_found(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("no archived game {id}")).into_response()
+}
+
+pub async fn list(State(state): State<UcuiState>, Query(filter): Query<Filter>) -> Response {
+    Json(state.archive.list(&filter).await).into_response()
+}
+
+pub async fn fetch(State(state): State<UcuiState>, Path(id): Path<String>) -> Response {
+    match state.archive.get(&id).await {
+        Some(game) => Json(game).into_response(),
+        None => not_found(&id),
+    }
+}
+
+pub async fn fetch_pgn(State(state): State<UcuiState>, Path(id): Path<String>) -> Response {
+    match state.archive.get(&id).await {
+        Some(game) => (
+            [(
+                header::CONTENT_TYPE,
+                "application/x-chess-pgn; charset=utf-8",
+            )],
+            game.pgn(),
+        )
+            .into_response(),
+        None => not_found(&id),
+    }
+}
+
+/// Deleting a game takes its transcript along, so it takes the admin token.
+pub async fn delete(
+    State(state): State<UcuiState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return unauthorized();
+    }
+    if state.archive.delete(&id).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        not_found(&id)
+    }
+}
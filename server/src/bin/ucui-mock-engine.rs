@@ -0,0 +1,306 @@
+//! A scripted UCI engine, for testing
+//!
+//! Usage: ucui-mock-engine [SCRIPT]
+//!        ucui-mock-engine --replay TRANSCRIPT
+//!
+//! The script lists what to answer to `go` for given positions:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! name Mock Engine
+//!
+//! position startpos
+//! info depth 1 score cp 17 pv e2e4
+//! bestmove e2e4
+//!
+//! position rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2
+//! sleep 300
+//! bestmove g1f3
+//!
+//! position *
+//! crash
+//! ```
+//!
+//! Lines after a `position` header are sent as they are, except
+//! `sleep MILLISECONDS` which waits and `crash` which exits on the spot.
+//! `position *` matches any position without its own section. Without
+//! a matching section, the engine plays the first legal move. Positions
+//! of variants start with the `UCI_Variant` name of the variant, e.g.
+//! `position crazyhouse rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1`.
+//!
+//! With `--replay`, the engine answers each command with what the
+//! engine in a transcript fetched from the server's
+//! `/admin/transcripts/{id}` answered, at the same pace. It exits at
+//! the end of the transcript, like the engine did if it crashed.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, BufRead},
+    str::FromStr,
+    thread,
+    time::{Duration, Instant},
+};
+
+use shakmaty::{
+    fen::Fen,
+    variant::{Variant, VariantPosition},
+    CastlingMode, EnPassantMode, Position,
+};
+use shakmaty_uci::UciMove;
+use ucui_engine::{Direction, Transcript};
+use ucui_utils::variant;
+
+#[derive(Default)]
+struct Script {
+    name: Option<String>,
+    sections: HashMap<String, Vec<String>>,
+}
+
+fn position_key(game: &VariantPosition) -> String {
+    format!(
+        "{} {}",
+        game.variant().uci(),
+        Fen::from_position(game.clone(), EnPassantMode::Legal)
+    )
+}
+
+fn parse_position(spec: &str) -> Result<String, String> {
+    let (variant, spec) = match spec.split_once(' ') {
+        Some((name, rest)) => match Variant::from_uci(name) {
+            Ok(variant) => (variant, rest.trim()),
+            Err(_) => (Variant::Chess, spec),
+        },
+        None => (Variant::Chess, spec),
+    };
+    match spec {
+        "*" => Ok("*".into()),
+        "startpos" => Ok(position_key(&VariantPosition::new(variant))),
+        fen => variant::parse_position(fen, variant, false).map(|(game, _)| position_key(&game)),
+    }
+}
+
+impl Script {
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut script = Script::default();
+        let mut current: Option<String> = None;
+        for (n, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(spec) = line.strip_prefix("position ") {
+                let key = parse_position(spec.trim()).map_err(|err| format!("{}: {err}", n + 1))?;
+                let _ = script.sections.insert(key.clone(), Vec::new());
+                current = Some(key);
+            } else if let Some(key) = current.as_ref() {
+                if let Some(lines) = script.sections.get_mut(key) {
+                    lines.push(line.to_string());
+                }
+            } else if let Some(name) = line.strip_prefix("name ") {
+                script.name = Some(name.to_string());
+            } else {
+                return Err(format!("{}: expected a position header", n + 1));
+            }
+        }
+        Ok(script)
+    }
+
+    fn replies(&self, game: &VariantPosition) -> Option<&Vec<String>> {
+        self.sections
+            .get(&position_key(game))
+            .or_else(|| self.sections.get("*"))
+    }
+}
+
+/// The parts of a `position` command. Read here rather than with
+/// `UciMessage`, which does not know the pockets of crazyhouse FENs.
+fn parse_command(line: &str) -> Option<(bool, Option<Fen>, Vec<UciMove>)> {
+    let line = line.strip_prefix("position")?.trim();
+    let (setup, moves) = match line.split_once("moves") {
+        Some((setup, moves)) => (setup.trim(), moves),
+        None => (line, ""),
+    };
+    let moves = moves
+        .split_whitespace()
+        .map(|m| UciMove::from_str(m).ok())
+        .collect::<Option<Vec<_>>>()?;
+    match setup {
+        "startpos" => Some((true, None, moves)),
+        setup => {
+            let fen = Fen::from_str(setup.strip_prefix("fen")?.trim()).ok()?;
+            Some((false, Some(fen), moves))
+        }
+    }
+}
+
+fn set_position(
+    startpos: bool,
+    fen: Option<Fen>,
+    moves: Vec<UciMove>,
+    variant: Variant,
+    mode: CastlingMode,
+) -> Option<VariantPosition> {
+    let mut game = match (startpos, fen) {
+        (true, _) => VariantPosition::new(variant),
+        (false, Some(fen)) => VariantPosition::from_setup(variant, fen.into_setup(), mode).ok()?,
+        (false, None) => return None,
+    };
+    for m in moves {
+        let m = m.to_move(&game).ok()?;
+        game = game.play(&m).ok()?;
+    }
+    Some(game)
+}
+
+fn go(script: &Script, game: &VariantPosition, mode: CastlingMode) {
+    match script.replies(game) {
+        Some(lines) => {
+            for line in lines {
+                if let Some(ms) = line.strip_prefix("sleep ") {
+                    thread::sleep(Duration::from_millis(ms.trim().parse().unwrap_or(0)));
+                } else if line == "crash" {
+                    std::process::exit(101);
+                } else {
+                    println!("{line}");
+                }
+            }
+        }
+        None => match game.legal_moves().first() {
+            Some(m) => println!("bestmove {}", m.to_uci(mode)),
+            None => println!("bestmove 0000"),
+        },
+    }
+}
+
+/// A command from a transcript and what the engine answered,
+/// each answer with its delay after the command.
+struct Exchange {
+    command: Option<String>,
+    replies: Vec<(Duration, String)>,
+}
+
+fn exchanges(transcript: &Transcript) -> VecDeque<Exchange> {
+    let mut exchanges: VecDeque<Exchange> = VecDeque::new();
+    let mut sent_at = None;
+    for line in transcript.lines() {
+        match line.direction {
+            Direction::Sent => {
+                sent_at = Some(line.at);
+                exchanges.push_back(Exchange {
+                    command: Some(line.line),
+                    replies: Vec::new(),
+                });
+            }
+            Direction::Received => {
+                let delay = sent_at
+                    .and_then(|at| (line.at - at).to_std().ok())
+                    .unwrap_or_default();
+                if exchanges.is_empty() {
+                    exchanges.push_back(Exchange {
+                        command: None,
+                        replies: Vec::new(),
+                    });
+                }
+                if let Some(exchange) = exchanges.back_mut() {
+                    exchange.replies.push((delay, line.line));
+                }
+            }
+        }
+    }
+    exchanges
+}
+
+fn send_replies(replies: &[(Duration, String)]) {
+    let start = Instant::now();
+    for (delay, line) in replies {
+        if let Some(wait) = delay.checked_sub(start.elapsed()) {
+            thread::sleep(wait);
+        }
+        println!("{line}");
+    }
+}
+
+fn replay(transcript: Transcript) {
+    let mut exchanges = exchanges(&transcript);
+    if let Some(Exchange { command: None, .. }) = exchanges.front() {
+        if let Some(banner) = exchanges.pop_front() {
+            send_replies(&banner.replies);
+        }
+    }
+
+    let mut lines = io::stdin().lock().lines();
+    while let Some(exchange) = exchanges.pop_front() {
+        let Some(Ok(line)) = lines.next() else {
+            return;
+        };
+        if let Some(command) = exchange.command.as_ref().filter(|c| **c != line) {
+            eprintln!("replay: expected '{command}', got '{line}'");
+        }
+        send_replies(&exchange.replies);
+    }
+    eprintln!("replay: end of transcript");
+}
+
+fn run_script(script: Script) {
+    let mut variant = Variant::Chess;
+    let mut game = VariantPosition::new(variant);
+    let mut mode = CastlingMode::Standard;
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match line.split_whitespace().next() {
+            Some("uci") => {
+                println!(
+                    "id name {}",
+                    script.name.as_deref().unwrap_or("ucui mock engine")
+                );
+                println!("uciok");
+            }
+            Some("isready") => println!("readyok"),
+            Some("setoption") if line.contains("name UCI_Chess960 ") => {
+                mode = CastlingMode::from_chess960(line.ends_with(" true"));
+            }
+            Some("setoption") if line.contains("name UCI_Variant ") => {
+                let name = line.rsplit(' ').next().unwrap_or_default();
+                variant = Variant::from_uci(name).unwrap_or(Variant::Chess);
+                game = VariantPosition::new(variant);
+            }
+            Some("position") => {
+                match parse_command(&line).and_then(|(startpos, fen, moves)| {
+                    set_position(startpos, fen, moves, variant, mode)
+                }) {
+                    Some(position) => game = position,
+                    None => eprintln!("invalid position: {line}"),
+                }
+            }
+            Some("go") => go(&script, &game, mode),
+            Some("ucinewgame") => game = VariantPosition::new(variant),
+            Some("quit") => break,
+            _ => {}
+        }
+    }
+}
+
+fn read_or_exit<T, F>(path: &str, parse: F) -> T
+where
+    F: FnOnce(&str) -> Result<T, String>,
+{
+    fs::read_to_string(path)
+        .map_err(|err| err.to_string())
+        .and_then(|source| parse(&source))
+        .unwrap_or_else(|err| {
+            eprintln!("{path}: {err}");
+            std::process::exit(2);
+        })
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.as_slice() {
+        [flag, path] if flag == "--replay" => replay(read_or_exit(path, Transcript::from_str)),
+        [path] => run_script(read_or_exit(path, Script::parse)),
+        _ => run_script(Script::default()),
+    }
+}
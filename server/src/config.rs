@@ -1,6 +1,7 @@
 use clap::Parser;
 // use log::LevelFilter;
-use std::{net::IpAddr, path::PathBuf, sync::OnceLock};
+use std::{ffi::OsString, net::IpAddr, path::PathBuf, sync::OnceLock};
+use ucui_engine::{Bot, Profile, Protocol};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -17,9 +18,58 @@ pub struct Config {
     #[arg(short, long, value_name = "DIR")]
     static_dir: Option<PathBuf>,
 
+    /// Directory where finished games are saved
+    ///
+    /// Without it, games are not kept once over.
+    #[arg(long, value_name = "DIR")]
+    archive: Option<PathBuf>,
+
+    /// Token giving access to the /admin endpoints
+    ///
+    /// Requests carry it as "Authorization: Bearer TOKEN". Without it,
+    /// the /admin endpoints are not served.
+    #[arg(long, value_name = "TOKEN")]
+    admin_token: Option<String>,
+
+    /// Time in milliseconds the engine thinks about a hint
+    #[arg(long, value_name = "MILLIS", default_value = "1000")]
+    hint_time: u64,
+
     /// Path to a UCI engine
+    ///
+    /// A remote UCI engine can be reached with "tcp://HOST:PORT",
+    /// see ucui-engine-relay. Without an engine, the server plays with its embedded
+    /// blunders engine, or the bot given with --bot.
     #[arg(short, long, value_name = "ENGINE")]
-    engine: String,
+    engine: Option<String>,
+
+    /// Built-in bot to play with instead of a UCI engine
+    ///
+    /// From the weakest to the strongest: random, greedy, simple,
+    /// blunders-2, blunders-4, blunders-6, blunders.
+    #[arg(
+        long,
+        value_name = "BOT",
+        conflicts_with = "engine",
+        default_value = "blunders"
+    )]
+    bot: Bot,
+
+    /// Protocol spoken by the engine, uci or xboard
+    #[arg(long, value_name = "PROTOCOL", default_value = "uci")]
+    protocol: Protocol,
+
+    /// Another engine, which clients choose with engine=NAME
+    ///
+    /// This argument can be repeated. Profiles are comma separated
+    /// KEY=VALUE pairs: name, engine (path to the engine), bot (a
+    /// built-in bot instead), protocol (uci or xboard), args
+    /// (separated by ";") and option, as "ID[:VALUE]", which can be
+    /// repeated.
+    ///
+    /// Example: --engine-profile 'name=crafty,engine=/usr/games/crafty,protocol=xboard'
+    #[arg(long, value_name = "PROFILE")]
+    engine_profile: Vec<Profile>,
 
     /// Optional arguments to pass to the engine (separated by ";")
     ///
@@ -35,7 +85,8 @@ pub struct Config {
     /// This argument can be repeated. UCI options are of the
     /// form "ID[:VALUE]". VALUE can be missing if not needed (buttons).  
     /// See the engine's documentation for available options and their
-    /// default values.
+    /// default values. XBoard engines get them as "option ID=VALUE",
+    /// except "st:SECONDS" which sets a fixed time per move.
     ///
     /// Example: --uci-option 'Threads:2' --uci-option 'Skill Level:12'
     #[arg(long)]
@@ -48,6 +99,17 @@ pub fn config() -> &'static Config {
     CONFIG.get_or_init(Config::parse)
 }
 
+/// Sets the configuration from `args` instead of the command line,
+/// e.g. when running the server from tests.
+/// Has no effect if the configuration is already set.
+pub fn init_config<I, T>(args: I) -> &'static Config
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    CONFIG.get_or_init(|| Config::parse_from(args))
+}
+
 pub fn get_interface() -> IpAddr {
     config().interface
 }
@@ -67,10 +129,30 @@ pub fn get_static_dir() -> String {
         ))
 }
 
-pub fn get_engine() -> String {
+pub fn get_archive_dir() -> Option<PathBuf> {
+    config().archive.clone()
+}
+
+pub fn get_admin_token() -> Option<String> {
+    config().admin_token.clone()
+}
+
+pub fn get_hint_time() -> u64 {
+    config().hint_time
+}
+
+pub fn get_engine() -> Option<String> {
     config().engine.clone()
 }
 
+pub fn get_bot() -> Bot {
+    config().bot
+}
+
+pub fn get_protocol() -> Protocol {
+    config().protocol
+}
+
 pub fn get_engine_args() -> Option<Vec<String>> {
     config()
         .engine_args
@@ -93,6 +175,31 @@ pub fn get_engine_options() -> Vec<(String, Option<String>)> {
         .collect()
 }
 
+/// The server's own engine, given with --engine or --bot.
+pub fn get_default_profile() -> Profile {
+    match get_engine() {
+        Some(path) => Profile::engine(
+            &path,
+            get_protocol(),
+            get_engine_args(),
+            get_engine_options(),
+        ),
+        None => Profile::bot(get_bot()),
+    }
+}
+
+/// The profile called `name`, the server's own engine without a name.
+pub fn get_engine_profile(name: Option<&str>) -> Option<Profile> {
+    match name {
+        Some(name) => config()
+            .engine_profile
+            .iter()
+            .find(|profile| profile.name == name)
+            .cloned(),
+        None => Some(get_default_profile()),
+    }
+}
+
 // #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 // pub enum LogLevel {
 //     /// A level lower than all log levels.
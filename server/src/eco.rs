@@ -1,10 +1,30 @@
-use std::str::FromStr as _;
+use std::str::FromStr;
 
-use axum::{extract::Query, Json};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
 use serde::Deserialize;
-use shakmaty::{fen::Fen, Chess, FromSetup, Position};
-use ucui_eco::{lookup_eco_from_name, Eco};
-use ucui_utils::MoveSerde;
+use shakmaty::{
+    fen::{Fen, ParseFenError},
+    san::SanPlus,
+    variant::{Variant, VariantPosition},
+    CastlingMode, Move, Position, PositionErrorKinds,
+};
+use ucui_eco::{find_eco_from_fen, lookup_eco_from_name, Eco};
+use ucui_protocol::{
+    outcome_string, Conversion, GameOutcome, LegalMove, MoveFlags, PositionCheck, PositionError,
+    PositionErrorCode, RoleMoves,
+};
+use ucui_utils::{
+    notation::{movetext, parse_moves, plies},
+    variant::{fen, parse_position, VariantSerde},
+    MoveSerde, ROLE_LIST,
+};
+
+use crate::play::{outcome_reason, sort_move};
 
 #[derive(Deserialize)]
 pub struct Lookup {
@@ -18,19 +38,299 @@ pub async fn lookup_eco(Query(lookup): Query<Lookup>) -> Json<Vec<Eco>> {
 #[derive(Deserialize)]
 pub struct Pos {
     fen: String,
+    #[serde(default)]
+    chess960: bool,
+    #[serde(default)]
+    variant: VariantSerde,
+    /// What to tell about each move, a comma separated list of "san",
+    /// "uci", "fen" and "flags".
+    with: Option<String>,
+    /// Moves grouped by the role of the piece moved.
+    #[serde(default)]
+    group: bool,
+}
+
+/// What `/legals` tells about a move besides the move itself.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Detail {
+    San,
+    Uci,
+    Fen,
+    Flags,
+}
+
+fn details(with: Option<&str>) -> Result<Vec<Detail>, String> {
+    with.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match name {
+            "san" => Ok(Detail::San),
+            "uci" => Ok(Detail::Uci),
+            "fen" => Ok(Detail::Fen),
+            "flags" => Ok(Detail::Flags),
+            _ => Err(format!(
+                "unknown detail '{name}', expected san, uci, fen or flags"
+            )),
+        })
+        .collect()
+}
+
+fn legal_move(game: &VariantPosition, m: Move, details: &[Detail]) -> LegalMove {
+    let with = |detail| details.contains(&detail);
+    let mut after = game.clone();
+    after.play_unchecked(&m);
+    LegalMove {
+        san: with(Detail::San).then(|| SanPlus::from_move(game.clone(), &m).to_string()),
+        uci: with(Detail::Uci).then(|| m.to_uci(game.castles().mode()).to_string()),
+        fen: with(Detail::Fen).then(|| fen(&after)),
+        flags: with(Detail::Flags).then(|| MoveFlags {
+            capture: m.is_capture(),
+            check: after.is_check(),
+            checkmate: after.is_checkmate(),
+            promotion: m.is_promotion(),
+        }),
+        _move: m.into(),
+    }
+}
+
+/// A FEN that cannot be read is a bad request, a position that cannot
+/// be played from cannot be processed.
+pub async fn legal_moves(Query(pos): Query<Pos>) -> Response {
+    let details = match details(pos.with.as_deref()) {
+        Ok(details) => details,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    if let Err(err) = Fen::from_str(pos.fen.trim()) {
+        let message = format!("invalid FEN '{}': {err}", pos.fen);
+        return (StatusCode::BAD_REQUEST, message).into_response();
+    }
+    let game = match parse_position(&pos.fen, pos.variant.into(), pos.chess960) {
+        Ok((game, _)) => game,
+        Err(err) => {
+            let message = format!("impossible position '{}': {err}", pos.fen);
+            return (StatusCode::UNPROCESSABLE_ENTITY, message).into_response();
+        }
+    };
+    let mut moves: Vec<Move> = game.legal_moves().into_iter().collect();
+    moves.sort_by(sort_move);
+    if pos.group {
+        let groups: Vec<RoleMoves> = ROLE_LIST
+            .iter()
+            .filter_map(|role| {
+                let moves: Vec<LegalMove> = moves
+                    .iter()
+                    .filter(|m| m.role() == *role)
+                    .map(|m| legal_move(&game, m.clone(), &details))
+                    .collect();
+                if moves.is_empty() {
+                    None
+                } else {
+                    Some(RoleMoves {
+                        role: (*role).into(),
+                        moves,
+                    })
+                }
+            })
+            .collect();
+        Json(groups).into_response()
+    } else if details.is_empty() {
+        // bare moves, as before details could be asked for
+        let moves: Vec<MoveSerde> = moves.into_iter().map(MoveSerde::from).collect();
+        Json(moves).into_response()
+    } else {
+        let moves: Vec<LegalMove> = moves
+            .into_iter()
+            .map(|m| legal_move(&game, m, &details))
+            .collect();
+        Json(moves).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Validate {
+    fen: String,
+    #[serde(default)]
+    chess960: bool,
+    #[serde(default)]
+    variant: VariantSerde,
 }
 
-pub async fn legal_moves(Query(pos): Query<Pos>) -> Json<Vec<MoveSerde>> {
-    // Fen::from_position(state.game.clone(), shakmaty::EnPassantMode::Legal).to_string()
-    if let Ok(fen) = Fen::from_str(&pos.fen) {
-        if let Ok(game) = Chess::from_setup(fen.into_setup(), shakmaty::CastlingMode::Standard) {
-            return Json(
-                game.legal_moves()
-                    .into_iter()
-                    .map(MoveSerde::from)
-                    .collect(),
-            );
+fn fen_error(err: &ParseFenError) -> PositionError {
+    let code = match err {
+        ParseFenError::InvalidBoard => PositionErrorCode::InvalidBoard,
+        ParseFenError::InvalidPocket => PositionErrorCode::InvalidPocket,
+        ParseFenError::InvalidTurn => PositionErrorCode::InvalidTurn,
+        ParseFenError::InvalidCastling => PositionErrorCode::InvalidCastling,
+        ParseFenError::InvalidEpSquare => PositionErrorCode::InvalidEnPassant,
+        ParseFenError::InvalidRemainingChecks => PositionErrorCode::InvalidRemainingChecks,
+        ParseFenError::InvalidHalfmoveClock | ParseFenError::InvalidFullmoves => {
+            PositionErrorCode::InvalidCounters
         }
+        _ => PositionErrorCode::InvalidFen,
+    };
+    PositionError {
+        code,
+        message: err.to_string(),
     }
-    Json(Vec::new())
+}
+
+/// Every reason the position cannot be played from.
+fn position_errors(kinds: PositionErrorKinds) -> Vec<PositionError> {
+    [
+        (
+            PositionErrorKinds::EMPTY_BOARD,
+            PositionErrorCode::EmptyBoard,
+            "the board is empty",
+        ),
+        (
+            PositionErrorKinds::MISSING_KING,
+            PositionErrorCode::MissingKing,
+            "a side has no king",
+        ),
+        (
+            PositionErrorKinds::TOO_MANY_KINGS,
+            PositionErrorCode::TooManyKings,
+            "a side has more than one king",
+        ),
+        (
+            PositionErrorKinds::PAWNS_ON_BACKRANK,
+            PositionErrorCode::PawnsOnBackrank,
+            "pawns on the first or the last rank",
+        ),
+        (
+            PositionErrorKinds::INVALID_CASTLING_RIGHTS,
+            PositionErrorCode::InvalidCastling,
+            "castling rights without the king and rook in place",
+        ),
+        (
+            PositionErrorKinds::INVALID_EP_SQUARE,
+            PositionErrorCode::InvalidEnPassant,
+            "en passant square without a pawn that just moved two squares",
+        ),
+        (
+            PositionErrorKinds::OPPOSITE_CHECK,
+            PositionErrorCode::OppositeCheck,
+            "the side not to move is in check",
+        ),
+        (
+            PositionErrorKinds::IMPOSSIBLE_CHECK,
+            PositionErrorCode::ImpossibleCheck,
+            "a check no move could have given",
+        ),
+        (
+            PositionErrorKinds::TOO_MUCH_MATERIAL,
+            PositionErrorCode::TooMuchMaterial,
+            "more pieces than promotions allow",
+        ),
+        (
+            PositionErrorKinds::VARIANT,
+            PositionErrorCode::Variant,
+            "not a position of the variant",
+        ),
+    ]
+    .into_iter()
+    .filter(|(kind, _, _)| kinds.contains(*kind))
+    .map(|(_, code, message)| PositionError {
+        code,
+        message: message.into(),
+    })
+    .collect()
+}
+
+fn check_position(pos: &Validate) -> PositionCheck {
+    let mut check = PositionCheck {
+        valid: false,
+        errors: Vec::new(),
+        fen: None,
+        chess960: pos.chess960,
+        turn: None,
+        outcome: None,
+        eco: None,
+    };
+    let setup = match Fen::from_str(pos.fen.trim()) {
+        Ok(fen) => fen.into_setup(),
+        Err(err) => {
+            check.errors.push(fen_error(&err));
+            return check;
+        }
+    };
+    check.turn = Some(setup.turn.into());
+    // castling rights only possible in Chess960 switch to Chess960,
+    // as when starting a game, which `chess960` tells
+    let variant: Variant = pos.variant.into();
+    let mode = CastlingMode::from_chess960(pos.chess960);
+    let game = match VariantPosition::from_setup(variant, setup.clone(), mode) {
+        Err(err) if mode == CastlingMode::Standard => {
+            VariantPosition::from_setup(variant, setup, CastlingMode::Chess960).map_err(|_| err)
+        }
+        game => game,
+    };
+    let game = match game {
+        Ok(game) => game,
+        Err(err) => {
+            check.errors = position_errors(err.kinds());
+            return check;
+        }
+    };
+    check.valid = true;
+    check.fen = Some(fen(&game));
+    check.chess960 = game.castles().mode() == CastlingMode::Chess960;
+    check.outcome = game.outcome().map(|outcome| GameOutcome {
+        outcome: outcome_string(outcome).into(),
+        reason: outcome_reason(&game).into(),
+    });
+    if variant == Variant::Chess && game.castles().mode() == CastlingMode::Standard {
+        check.eco = find_eco_from_fen(&fen(&game)).cloned();
+    }
+    check
+}
+
+/// Always answers, with what is wrong with the FEN if anything.
+pub async fn validate_position(Query(pos): Query<Validate>) -> Json<PositionCheck> {
+    Json(check_position(&pos))
+}
+
+#[derive(Deserialize)]
+pub struct Convert {
+    /// The start position of the variant when not given.
+    fen: Option<String>,
+    #[serde(default)]
+    chess960: bool,
+    #[serde(default)]
+    variant: VariantSerde,
+    /// Moves in SAN, UCI, LAN or figurine notation, or PGN movetext.
+    #[serde(default)]
+    moves: String,
+}
+
+/// Bad requests and unprocessable positions as on `/legals`, moves
+/// that cannot be read or played are bad requests too.
+pub async fn convert_moves(Query(conv): Query<Convert>) -> Response {
+    let start = match conv.fen.as_deref() {
+        None => VariantPosition::new(conv.variant.into()),
+        Some(start) => {
+            if let Err(err) = Fen::from_str(start.trim()) {
+                let message = format!("invalid FEN '{start}': {err}");
+                return (StatusCode::BAD_REQUEST, message).into_response();
+            }
+            match parse_position(start, conv.variant.into(), conv.chess960) {
+                Ok((game, _)) => game,
+                Err(err) => {
+                    let message = format!("impossible position '{start}': {err}");
+                    return (StatusCode::UNPROCESSABLE_ENTITY, message).into_response();
+                }
+            }
+        }
+    };
+    let moves = match parse_moves(&start, &conv.moves) {
+        Ok(moves) => moves,
+        Err(err) => return (StatusCode::BAD_REQUEST, err).into_response(),
+    };
+    Json(Conversion {
+        fen: fen(&start),
+        moves: plies(&start, &moves),
+        movetext: movetext(&start, &moves),
+    })
+    .into_response()
 }
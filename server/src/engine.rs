@@ -0,0 +1,106 @@
+//! The engine of a game
+//!
+//! Engines answer on blocking channels, waiting on them is done on the
+//! blocking pool so that a thinking engine does not hold up the other
+//! games of the server. A wait gives up once its game is gone.
+
+use std::{
+    sync::{
+        mpsc::{RecvError, RecvTimeoutError},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Instant,
+};
+
+use chrono::Duration;
+use shakmaty::variant::Variant;
+use ucui_engine::{Engine, EngineMessage, Profile, SearchLimit, Transcript};
+
+/// How often a wait on the engine checks that its game is still on.
+const POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+pub(crate) struct GameEngine {
+    name: String,
+    engine: Arc<Mutex<Box<dyn Engine + Send>>>,
+}
+
+fn lock(engine: &Mutex<Box<dyn Engine + Send>>) -> MutexGuard<'_, Box<dyn Engine + Send>> {
+    engine.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Waits for the engine, at most `timeout` when given. The engine is
+/// locked only while polling, and the wait ends as soon as nobody else
+/// holds the engine, when its game ended.
+fn wait(
+    engine: Arc<Mutex<Box<dyn Engine + Send>>>,
+    timeout: Option<std::time::Duration>,
+) -> Result<EngineMessage, RecvTimeoutError> {
+    let start = Instant::now();
+    loop {
+        if Arc::strong_count(&engine) == 1 {
+            return Err(RecvTimeoutError::Disconnected);
+        }
+        match lock(&engine).recv_timeout(POLL) {
+            Err(RecvTimeoutError::Timeout)
+                if timeout.is_none_or(|timeout| start.elapsed() < timeout) => {}
+            result => return result,
+        }
+    }
+}
+
+impl GameEngine {
+    /// Starts the engine of `profile`, which waits for it to tell its
+    /// name.
+    pub(crate) async fn connect(profile: Profile) -> Result<Self, String> {
+        tokio::task::spawn_blocking(move || {
+            let engine = profile.connect();
+            GameEngine {
+                name: engine.name(),
+                engine: Arc::new(Mutex::new(engine)),
+            }
+        })
+        .await
+        .map_err(|err| format!("the engine failed to start: {err}"))
+    }
+
+    pub(crate) fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub(crate) fn set_variant(&self, variant: Variant) {
+        lock(&self.engine).set_variant(variant);
+    }
+
+    pub(crate) fn set_chess960(&self, chess960: bool) {
+        lock(&self.engine).set_chess960(chess960);
+    }
+
+    pub(crate) fn go(&self, fen: String, white_time: Duration, black_time: Duration) {
+        lock(&self.engine).go(fen, white_time, black_time);
+    }
+
+    pub(crate) fn search(&self, fen: String, limit: SearchLimit) {
+        lock(&self.engine).search(fen, limit);
+    }
+
+    pub(crate) fn transcript(&self) -> Option<Transcript> {
+        lock(&self.engine).transcript()
+    }
+
+    pub(crate) async fn recv(&self) -> Result<EngineMessage, RecvError> {
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || wait(engine, None))
+            .await
+            .map_or(Err(RecvError), |result| result.map_err(|_| RecvError))
+    }
+
+    pub(crate) async fn recv_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> Result<EngineMessage, RecvTimeoutError> {
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || wait(engine, Some(timeout)))
+            .await
+            .unwrap_or(Err(RecvTimeoutError::Disconnected))
+    }
+}
@@ -0,0 +1,12 @@
+pub mod annotate;
+pub mod archive;
+pub mod config;
+mod eco;
+mod engine;
+mod monitor;
+mod play;
+pub mod repertoire;
+pub mod server;
+mod session;
+pub mod state;
+mod transcripts;
@@ -1,11 +1,4 @@
-mod config;
-mod eco;
-mod monitor;
-mod play;
-mod server;
-mod state;
-
 fn main() {
-    let _ = crate::config::config();
-    crate::server::start();
+    let _ = ucui_server::config::config();
+    ucui_server::server::start();
 }
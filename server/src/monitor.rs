@@ -1,35 +1,68 @@
-use std::{collections::HashMap, sync::Arc};
+//! Spectating games
+//!
+//! `/games` streams a summary of the games being played, then what
+//! changes, `/games/{id}` the full record of one game, then its moves
+//! as they are played. Clients falling behind get the whole state
+//! again, and clients not answering pings are dropped.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
-        State, WebSocketUpgrade,
+        ws::{CloseFrame, Message, WebSocket},
+        Path, State, WebSocketUpgrade,
     },
     response::Response,
 };
-use serde::Serialize;
-use shakmaty::fen::Fen;
-use tokio::sync::{
-    broadcast::{channel, Receiver, Sender},
-    Mutex,
+use shakmaty::Outcome;
+use tokio::{
+    sync::{
+        broadcast::{
+            channel,
+            error::{RecvError, TryRecvError},
+            Receiver, Sender,
+        },
+        Mutex,
+    },
+    time::{interval_at, Instant, Interval},
+};
+use ucui_protocol::outcome_string;
+pub use ucui_protocol::{
+    Clocks, GameOutcome, GameRecord, GameSummary, MonitorMessage, MonitorMove,
 };
 
-use crate::state::UcuiState;
+use crate::{play::WsMessage, state::UcuiState};
 
 #[derive(Clone)]
 pub enum DBMessage {
-    Version(u128),
+    /// Game `id` has changed, to `game`, or is gone when `None`.
+    Version {
+        version: u128,
+        id: String,
+        game: Option<GameRecord>,
+    },
 }
 
 #[derive(Clone)]
 pub struct MonitorDB {
     version: u128,
-    data: HashMap<String, Fen>,
+    data: HashMap<String, GameRecord>,
 }
 impl MonitorDB {
-    fn set(&mut self, key: String, game: Fen) {
-        let _ = self.data.insert(key, game);
-        self.version += 1;
+    fn update(&mut self, key: &str, f: impl FnOnce(&mut GameRecord)) -> bool {
+        match self.data.get_mut(key) {
+            Some(game) => {
+                f(game);
+                self.version += 1;
+                game.version = self.version;
+                true
+            }
+            None => false,
+        }
     }
 
     fn del(&mut self, key: String) {
@@ -37,11 +70,10 @@ impl MonitorDB {
         self.version += 1;
     }
 
-    fn entries(&self) -> Vec<(String, Fen)> {
-        self.data
-            .iter()
-            .map(|(k, g)| (k.clone(), g.clone()))
-            .collect()
+    fn summaries(&self) -> Vec<GameSummary> {
+        let mut games: Vec<GameSummary> = self.data.values().map(GameRecord::summary).collect();
+        games.sort_by(|a, b| a.id.cmp(&b.id));
+        games
     }
 }
 
@@ -66,24 +98,76 @@ impl Monitor {
         }
     }
 
-    pub async fn set(&self, key: String, game: Fen) {
+    fn notify(&self, db: &MonitorDB, key: &str) {
+        let _ = self.tx.send(DBMessage::Version {
+            version: db.version,
+            id: key.to_string(),
+            game: db.data.get(key).cloned(),
+        });
+    }
+
+    pub async fn start(&self, mut game: GameRecord) {
+        let mut db = self.db.lock().await;
+        let key = game.id.clone();
+        db.version += 1;
+        game.version = db.version;
+        let _ = db.data.insert(key.clone(), game);
+        self.notify(&db, &key);
+    }
+
+    pub async fn play(&self, key: &str, m: MonitorMove) {
+        let mut db = self.db.lock().await;
+        let updated = db.update(key, |game| {
+            game.fen = m.fen.clone();
+            game.clock = m.clock.or(game.clock);
+            game.moves.push(m);
+        });
+        if updated {
+            self.notify(&db, key);
+        }
+    }
+
+    pub async fn end(&self, key: &str, outcome: Outcome, reason: &str) {
         let mut db = self.db.lock().await;
-        db.set(key, game);
-        let _ = self.tx.send(DBMessage::Version(db.version));
+        let updated = db.update(key, |game| {
+            game.outcome = Some(GameOutcome {
+                outcome: outcome_string(outcome).into(),
+                reason: reason.into(),
+            })
+        });
+        if updated {
+            self.notify(&db, key);
+        }
     }
+
     pub async fn del(&self, key: String) {
         let mut db = self.db.lock().await;
-        db.del(key);
-        let _ = self.tx.send(DBMessage::Version(db.version));
+        db.del(key.clone());
+        self.notify(&db, &key);
     }
 
-    pub async fn entries(&self) -> Vec<(String, Fen)> {
+    /// All games, as of the version returned.
+    pub async fn snapshot(&self) -> (u128, Vec<GameSummary>) {
         let db = self.db.lock().await;
-        db.entries()
+        (db.version, db.summaries())
     }
 
-    pub async fn recv(&mut self) -> Option<DBMessage> {
-        self.rx.recv().await.ok()
+    pub async fn game(&self, key: &str) -> Option<GameRecord> {
+        let db = self.db.lock().await;
+        db.data.get(key).cloned()
+    }
+
+    /// Waits for changes, and takes those that follow right away.
+    pub async fn changes(&mut self) -> Result<Vec<DBMessage>, RecvError> {
+        let mut changes = vec![self.rx.recv().await?];
+        loop {
+            match self.rx.try_recv() {
+                Ok(message) => changes.push(message),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Empty) => return Ok(changes),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+            }
+        }
     }
 }
 
@@ -97,56 +181,211 @@ impl Clone for Monitor {
     }
 }
 
-#[derive(Clone, Serialize)]
-#[serde(tag = "_tag")]
-pub enum MonitorMessage {
-    Init { games: Vec<(String, String)> },
-    Update { games: Vec<(String, String)> },
+/// Time between pings, a client not answering in that time is dropped.
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+enum Wake {
+    Changes(Vec<DBMessage>),
+    /// Changes were missed, the state has to be sent again.
+    Lagged,
+    Leave,
 }
 
-impl MonitorMessage {
-    fn init(games: Vec<(String, Fen)>) -> Message {
-        Message::text(
-            serde_json::to_string(&MonitorMessage::Init {
-                games: games
-                    .into_iter()
-                    .map(|(id, fen)| (id, fen.to_string()))
-                    .collect(),
-            })
-            .unwrap(),
-        )
-    }
-    fn update(games: Vec<(String, Fen)>) -> Message {
-        Message::text(
-            serde_json::to_string(&MonitorMessage::Update {
-                games: games
-                    .into_iter()
-                    .map(|(id, fen)| (id, fen.to_string()))
-                    .collect(),
-            })
-            .unwrap(),
-        )
+/// Waits on the monitor on behalf of a client, keeping an eye on
+/// the client itself.
+struct Watch {
+    ping: Interval,
+    awaiting_pong: bool,
+}
+
+impl Watch {
+    fn new() -> Self {
+        Watch {
+            ping: interval_at(Instant::now() + PING_INTERVAL, PING_INTERVAL),
+            awaiting_pong: false,
+        }
+    }
+
+    async fn next(&mut self, socket: &mut WebSocket, monitor: &mut Monitor) -> Wake {
+        loop {
+            tokio::select! {
+                changes = monitor.changes() => return match changes {
+                    Ok(changes) => Wake::Changes(changes),
+                    Err(RecvError::Lagged(n)) => {
+                        log::warn!("Monitor client missed {n} changes");
+                        Wake::Lagged
+                    }
+                    Err(RecvError::Closed) => Wake::Leave,
+                },
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Pong(_))) => self.awaiting_pong = false,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return Wake::Leave,
+                    Some(Ok(_)) => {}
+                },
+                _ = self.ping.tick() => {
+                    if self.awaiting_pong {
+                        log::info!("Monitor client does not answer pings");
+                        return Wake::Leave;
+                    }
+                    self.awaiting_pong = true;
+                    if socket.send(Message::Ping(Default::default())).await.is_err() {
+                        return Wake::Leave;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What a client of `/games` has been told.
+#[derive(Default)]
+struct Subscriber {
+    version: u128,
+    known: HashSet<String>,
+}
+
+impl Subscriber {
+    fn init(&mut self, version: u128, games: Vec<GameSummary>) -> MonitorMessage {
+        self.version = version;
+        self.known = games.iter().map(|game| game.id.clone()).collect();
+        MonitorMessage::Init { version, games }
+    }
+
+    /// The difference `changes` make, if any.
+    fn diff(&mut self, changes: Vec<DBMessage>) -> Option<MonitorMessage> {
+        let mut latest: Vec<(String, Option<GameSummary>)> = Vec::new();
+        for DBMessage::Version { version, id, game } in changes {
+            if version <= self.version {
+                continue;
+            }
+            self.version = version;
+            let summary = game.as_ref().map(GameRecord::summary);
+            match latest.iter_mut().find(|(key, _)| *key == id) {
+                Some(entry) => entry.1 = summary,
+                None => latest.push((id, summary)),
+            }
+        }
+
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        let mut removed = Vec::new();
+        for (id, summary) in latest {
+            match summary {
+                Some(summary) if self.known.insert(id.clone()) => added.push(summary),
+                Some(summary) => changed.push(summary),
+                None if self.known.remove(&id) => removed.push(id),
+                None => {}
+            }
+        }
+        if added.is_empty() && changed.is_empty() && removed.is_empty() {
+            return None;
+        }
+        Some(MonitorMessage::Diff {
+            version: self.version,
+            added,
+            changed,
+            removed,
+        })
     }
 }
 
 async fn handle_socket(mut socket: WebSocket, mut state: UcuiState) {
-    let _ = socket
-        .send(MonitorMessage::init(state.monitor.entries().await))
-        .await;
+    let mut subscriber = Subscriber::default();
+    let (version, games) = state.monitor.snapshot().await;
+    let mut watch = Watch::new();
+    let mut next = Some(subscriber.init(version, games));
 
-    let mut last_seen = 0u128;
     loop {
-        if let Some(DBMessage::Version(version)) = state.monitor.recv().await {
-            if version > last_seen {
-                last_seen = version;
-                let _ = socket
-                    .send(MonitorMessage::update(state.monitor.entries().await))
-                    .await;
+        if let Some(message) = next.take() {
+            if socket.send(message.message()).await.is_err() {
+                break;
             }
         }
+        next = match watch.next(&mut socket, &mut state.monitor).await {
+            Wake::Changes(changes) => subscriber.diff(changes),
+            Wake::Lagged => {
+                let (version, games) = state.monitor.snapshot().await;
+                Some(subscriber.init(version, games))
+            }
+            Wake::Leave => break,
+        };
     }
+    log::debug!("Monitor client left");
 }
 
 pub async fn handler(ws: WebSocketUpgrade, State(server_state): State<UcuiState>) -> Response {
     ws.on_upgrade(move |socket| handle_socket(socket, server_state))
 }
+
+/// What a client following one game has been told.
+struct Follower {
+    sent: usize,
+    ended: bool,
+}
+
+impl Follower {
+    /// What happened to `game` since the last call, `Gone` when
+    /// it is gone.
+    fn follow(&mut self, game: Option<GameRecord>) -> Vec<MonitorMessage> {
+        let Some(game) = game else {
+            return vec![MonitorMessage::Gone];
+        };
+        let mut messages = Vec::new();
+        for (ply, m) in game.moves.into_iter().enumerate().skip(self.sent) {
+            messages.push(MonitorMessage::Move { ply, _move: m });
+            self.sent = ply + 1;
+        }
+        if let (Some(outcome), false) = (game.outcome, self.ended) {
+            self.ended = true;
+            messages.push(MonitorMessage::Outcome { outcome });
+        }
+        messages
+    }
+}
+
+async fn handle_game_socket(mut socket: WebSocket, id: String, mut state: UcuiState) {
+    let Some(game) = state.monitor.game(&id).await else {
+        let _ = socket
+            .send(Message::Close(Some(CloseFrame {
+                code: axum::extract::ws::close_code::POLICY,
+                reason: format!("no game {id}").into(),
+            })))
+            .await;
+        return;
+    };
+    let mut follower = Follower {
+        sent: game.moves.len(),
+        ended: game.outcome.is_some(),
+    };
+    let mut watch = Watch::new();
+    let mut next = vec![MonitorMessage::Game { game }];
+
+    'follow: loop {
+        for message in next.drain(..) {
+            let gone = matches!(message, MonitorMessage::Gone);
+            if socket.send(message.message()).await.is_err() || gone {
+                break 'follow;
+            }
+        }
+        match watch.next(&mut socket, &mut state.monitor).await {
+            Wake::Changes(changes) => {
+                for DBMessage::Version { id: key, game, .. } in changes {
+                    if key == id {
+                        next.extend(follower.follow(game));
+                    }
+                }
+            }
+            Wake::Lagged => next = follower.follow(state.monitor.game(&id).await),
+            Wake::Leave => break,
+        }
+    }
+    log::debug!("Spectator of game {id} left");
+}
+
+pub async fn game_handler(
+    ws: WebSocketUpgrade,
+    State(server_state): State<UcuiState>,
+    Path(id): Path<String>,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_game_socket(socket, id, server_state))
+}
@@ -1,8 +1,8 @@
-use std::{cmp::Ordering, str::FromStr};
+use std::{cmp::Ordering, collections::HashMap, sync::mpsc::RecvTimeoutError, time::Instant};
 
 use axum::{
     extract::{
-        ws::{Message, WebSocket},
+        ws::{CloseFrame, Message, WebSocket},
         Query, State, WebSocketUpgrade,
     },
     response::Response,
@@ -12,51 +12,408 @@ use chrono::Duration;
 ///
 /// from https://docs.rs/axum/latest/axum/extract/ws/index.html
 use serde::{Deserialize, Serialize};
-use shakmaty::{fen::Fen, Chess, Color, FromSetup, Move, Outcome, Position, Square};
-use ucui_engine::{EngineMessage, Score};
-use ucui_utils::ColorSerde;
+use shakmaty::{
+    fen::Fen,
+    uci::UciMove,
+    variant::{Variant, VariantPosition},
+    CastlingMode, Color, Move, Outcome, Position, Square,
+};
+use ucui_eco::lookup_opening;
+use ucui_engine::{EngineMessage, Profile, Score, SearchLimit};
+use ucui_protocol::{
+    compatible, ClientMessage, ErrorCode, HintLevel, MonitorMessage, ServerMessage,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
+};
+use ucui_utils::{
+    chess960,
+    handicap::Handicap,
+    notation,
+    pgn::parse_pgn,
+    variant::{self, fen, VariantSerde},
+    ColorSerde,
+};
 use uuid::Uuid;
 
 use crate::{
-    config::{get_engine, get_engine_args, get_engine_options},
+    archive::{ArchivedGame, EngineProfile},
+    config::{get_engine_profile, get_hint_time},
+    engine::GameEngine,
+    monitor::{Clocks, GameRecord, MonitorMove},
+    repertoire::{Choice, Training},
+    session,
     state::UcuiState,
 };
 
 struct GameState {
-    game: Chess,
+    game: VariantPosition,
+    castling_mode: CastlingMode,
+    handicap: Option<Handicap>,
     engine_color: Color,
-    engine: Box<dyn ucui_engine::Engine + Send>,
+    engine: GameEngine,
+    profile: Profile,
     server_state: UcuiState,
     id: String,
+    /// As last given by the client, less the time the engine thought
+    /// since.
+    clock: Clocks,
+    /// When the engine was last asked for a move.
+    thinking_since: Instant,
+    /// Hints given to the player so far.
+    hints: u32,
+    /// Hints the engine did not answer in time, their answers come
+    /// before its next move.
+    late_hints: u32,
+    /// Where the game started, before the moves given by the client.
+    first: VariantPosition,
+    history: Vec<Move>,
+    repetitions: Repetitions,
+    /// The repertoire the player trains, if any.
+    training: Option<Training>,
 }
 
 impl GameState {
-    fn new(color: Color, position: Option<String>, server_state: UcuiState) -> Self {
-        Self {
-            engine_color: color,
+    async fn new(options: &ConnectOptions, server_state: UcuiState) -> Result<Self, String> {
+        let engine_color: Color = options.engine_color.clone().into();
+        let handicap = handicap(options)?;
+        let start = start_position(options, handicap.as_ref(), engine_color)?;
+        let training = match options.repertoire.as_ref() {
+            Some(id) => Some(training(options, &server_state, id, engine_color).await?),
+            None => None,
+        };
+        let profile = get_engine_profile(options.engine.as_deref()).ok_or_else(|| {
+            format!(
+                "unknown engine '{}'",
+                options.engine.as_deref().unwrap_or_default()
+            )
+        })?;
+        let engine = GameEngine::connect(profile.clone()).await?;
+        if start.game.variant() != Variant::Chess {
+            engine.set_variant(start.game.variant());
+        }
+        if start.castling_mode == CastlingMode::Chess960 {
+            engine.set_chess960(true);
+        }
+        Ok(Self {
+            engine_color,
             server_state,
             id: Uuid::new_v4().to_string(),
-            game: position
-                .and_then(|fen_string| Fen::from_str(&fen_string).ok())
-                .and_then(|fen| {
-                    Chess::from_setup(fen.into_setup(), shakmaty::CastlingMode::Standard).ok()
-                })
-                .unwrap_or_default(),
-            engine: ucui_engine::connect_engine(
-                &get_engine(),
-                get_engine_args(),
-                get_engine_options(),
-            ),
+            game: start.game,
+            castling_mode: start.castling_mode,
+            handicap,
+            engine,
+            profile,
+            clock: Clocks {
+                white: options.white_time,
+                black: options.black_time,
+            },
+            thinking_since: Instant::now(),
+            hints: 0,
+            late_hints: 0,
+            first: start.first,
+            history: start.moves,
+            repetitions: start.repetitions,
+            training,
+        })
+    }
+
+    /// Takes the time the engine thought on its move off its clock.
+    fn press_engine_clock(&mut self) {
+        let spent = self.thinking_since.elapsed().as_millis() as i64;
+        let left = match self.engine_color {
+            Color::White => &mut self.clock.white,
+            Color::Black => &mut self.clock.black,
+        };
+        *left = (*left - spent).max(0);
+    }
+}
+
+/// Repertoires are trained from the start position, the engine playing
+/// the other side.
+async fn training(
+    options: &ConnectOptions,
+    server_state: &UcuiState,
+    id: &str,
+    engine_color: Color,
+) -> Result<Training, String> {
+    if options.fen.is_some()
+        || options.moves.is_some()
+        || options.pgn.is_some()
+        || options.opening.is_some()
+        || options.handicap.is_some()
+        || options.chess960
+        || Variant::from(options.variant) != Variant::Chess
+    {
+        return Err("a repertoire is trained from the start position".into());
+    }
+    let training = Training::start(&server_state.repertoires, id, options.choice).await?;
+    if training.color() == engine_color {
+        return Err(format!(
+            "repertoire '{id}' is prepared for {}",
+            training.color().fold_wb("white", "black")
+        ));
+    }
+    log::info!("Training repertoire {}", training.name());
+    Ok(training)
+}
+
+pub(crate) fn handicap(options: &ConnectOptions) -> Result<Option<Handicap>, String> {
+    options
+        .handicap
+        .as_ref()
+        .map(|handicap| {
+            handicap
+                .parse::<Handicap>()
+                .map_err(|err| format!("invalid handicap: {err}"))
+        })
+        .transpose()
+}
+
+/// Times each position was seen in a game, castling and en passant
+/// rights included.
+#[derive(Clone, Default)]
+pub(crate) struct Repetitions(HashMap<String, u32>);
+
+impl Repetitions {
+    /// Without the move counters.
+    fn key(game: &VariantPosition) -> String {
+        let fen = fen(game);
+        let fields: Vec<&str> = fen.split(' ').collect();
+        fields[..fields.len().saturating_sub(2)].join(" ")
+    }
+
+    pub(crate) fn see(&mut self, game: &VariantPosition) {
+        *self.0.entry(Self::key(game)).or_default() += 1;
+    }
+
+    fn threefold(&self, game: &VariantPosition) -> bool {
+        self.0.get(&Self::key(game)).is_some_and(|seen| *seen >= 3)
+    }
+}
+
+/// The outcome of `game` and why, threefold repetitions included.
+pub(crate) fn game_outcome(
+    game: &VariantPosition,
+    repetitions: &Repetitions,
+) -> Option<(Outcome, &'static str)> {
+    game.outcome()
+        .map(|outcome| (outcome, outcome_reason(game)))
+        .or_else(|| {
+            repetitions
+                .threefold(game)
+                .then_some((Outcome::Draw, "threefold repetition"))
+        })
+}
+
+/// Where a game starts on the server: the position it started from,
+/// the moves played since then, and where they lead.
+pub(crate) struct Start {
+    pub(crate) first: VariantPosition,
+    pub(crate) moves: Vec<Move>,
+    pub(crate) game: VariantPosition,
+    pub(crate) castling_mode: CastlingMode,
+    pub(crate) repetitions: Repetitions,
+}
+
+impl Start {
+    /// Fails on illegal moves, and when the game is already over.
+    fn replay<I>(
+        first: VariantPosition,
+        castling_mode: CastlingMode,
+        moves: I,
+    ) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = Result<Move, String>>,
+    {
+        let mut start = Start {
+            game: first.clone(),
+            first,
+            moves: Vec::new(),
+            castling_mode,
+            repetitions: Repetitions::default(),
+        };
+        start.repetitions.see(&start.game);
+        for (ply, m) in moves.into_iter().enumerate() {
+            if let Some((_, reason)) = game_outcome(&start.game, &start.repetitions) {
+                return Err(format!(
+                    "the game is over by {reason} before ply {}",
+                    ply + 1
+                ));
+            }
+            let m = m.map_err(|err| format!("ply {}: {err}", ply + 1))?;
+            if !start.game.is_legal(&m) {
+                return Err(format!("ply {}: illegal move", ply + 1));
+            }
+            start.game.play_unchecked(&m);
+            start.repetitions.see(&start.game);
+            start.moves.push(m);
         }
+        match game_outcome(&start.game, &start.repetitions) {
+            Some((_, reason)) => Err(format!("the game is already over by {reason}")),
+            None => Ok(start),
+        }
+    }
+}
+
+/// The game given by the client, a PGN or a position followed by UCI
+/// moves. The position is the one given by the client, or the start
+/// position with `giver` giving odds, or a Chess960 start position if
+/// asked for one, or else the start position of the variant. The moves
+/// of an opening come before those given by the client.
+pub(crate) fn start_position(
+    options: &ConnectOptions,
+    handicap: Option<&Handicap>,
+    giver: Color,
+) -> Result<Start, String> {
+    if options.white_time < 0 || options.black_time < 0 {
+        return Err("clock times should not be negative".into());
+    }
+    if let Some(pgn) = options.pgn.as_ref() {
+        let game = parse_pgn(pgn).map_err(|err| format!("invalid PGN: {err}"))?;
+        let castling_mode = game.start.castles().mode();
+        return Start::replay(game.start, castling_mode, game.moves.into_iter().map(Ok));
+    }
+
+    let (first, castling_mode) = first_position(options, handicap, giver)?;
+    let mut ucis: Vec<String> = opening_moves(options, handicap)?;
+    ucis.extend(
+        options
+            .moves
+            .as_deref()
+            .unwrap_or_default()
+            .split([' ', ','])
+            .filter(|uci| !uci.is_empty())
+            .map(String::from),
+    );
+    // moves are read from the position they are played in
+    let mut game = first.clone();
+    let moves = ucis.into_iter().map(move |uci| {
+        let m = uci
+            .parse::<UciMove>()
+            .map_err(|_| format!("invalid UCI move '{uci}'"))?
+            .to_move(&game)
+            .map_err(|_| format!("illegal move '{uci}'"))?;
+        game.play_unchecked(&m);
+        Ok(m)
+    });
+    Start::replay(first, castling_mode, moves)
+}
+
+/// The moves of the opening asked for, in UCI. Openings are lines from
+/// the start position of standard chess.
+fn opening_moves(
+    options: &ConnectOptions,
+    handicap: Option<&Handicap>,
+) -> Result<Vec<String>, String> {
+    let Some(term) = options.opening.as_ref() else {
+        return Ok(Vec::new());
+    };
+    if options.fen.is_some()
+        || options.chess960
+        || handicap.is_some()
+        || Variant::from(options.variant) != Variant::Chess
+    {
+        return Err(format!(
+            "opening '{term}' only starts from the standard start position"
+        ));
     }
+    let eco = lookup_opening(term).ok_or_else(|| format!("unknown opening '{term}'"))?;
+    log::info!("Opening {} {}", eco.code, eco.name);
+    Ok(eco
+        .moves
+        .into_iter()
+        .map(|m| m.0.to_uci(CastlingMode::Standard).to_string())
+        .collect())
+}
+
+fn first_position(
+    options: &ConnectOptions,
+    handicap: Option<&Handicap>,
+    giver: Color,
+) -> Result<(VariantPosition, CastlingMode), String> {
+    let variant: Variant = options.variant.into();
+    if let Some(fen) = options.fen.as_ref() {
+        return variant::parse_position(fen, variant, options.chess960)
+            .map_err(|err| format!("invalid position '{fen}': {err}"));
+    }
+    if let Some(handicap) = handicap {
+        let start = handicap.start_position(giver).and_then(|game| {
+            let fen = Fen::from_position(game, shakmaty::EnPassantMode::Legal).to_string();
+            variant::parse_position(&fen, variant, false)
+        });
+        log::info!("Engine gives {}", handicap.description());
+        return start.map_err(|err| format!("invalid handicap '{handicap}': {err}"));
+    }
+    if options.chess960 {
+        let number = options
+            .chess960_position
+            .filter(|n| *n < chess960::POSITIONS)
+            .unwrap_or_else(chess960::random_number);
+        // horde and racing kings have no Chess960 start positions
+        let start = chess960::start_fen(number)
+            .and_then(|fen| variant::parse_position(&fen, variant, true).ok());
+        if let Some(position) = start {
+            log::info!("Chess960 start position {number}");
+            return Ok(position);
+        }
+    }
+    Ok((VariantPosition::new(variant), CastlingMode::Standard))
+}
+
+fn default_engine_color() -> ColorSerde {
+    ColorSerde::Black
+}
+
+fn default_time() -> i64 {
+    10 * 60 * 1000
 }
 
 #[derive(Deserialize)]
 pub struct ConnectOptions {
-    engine_color: ColorSerde,
-    fen: Option<String>,
-    white_time: i64,
-    black_time: i64,
+    /// The color of the other player, engine or human.
+    #[serde(default = "default_engine_color")]
+    pub(crate) engine_color: ColorSerde,
+    pub(crate) fen: Option<String>,
+    /// UCI moves already played from `fen` or the start position,
+    /// separated by spaces or commas.
+    pub(crate) moves: Option<String>,
+    /// A game to go on with, instead of `fen` and `moves`. Its tags
+    /// tell the variant and start position.
+    pub(crate) pgn: Option<String>,
+    /// An opening to start with, by ECO code ("C50") or words of its
+    /// name ("italian game"), played before `moves`.
+    pub(crate) opening: Option<String>,
+    /// Id of a repertoire to train, see `repertoire`.
+    pub(crate) repertoire: Option<String>,
+    /// How the engine picks the lines of `repertoire`.
+    #[serde(default)]
+    pub(crate) choice: Choice,
+    #[serde(default = "default_time")]
+    pub(crate) white_time: i64,
+    #[serde(default = "default_time")]
+    pub(crate) black_time: i64,
+    /// Castling as in Chess960, from `fen` or from start position
+    /// `chess960_position`, a random one when not given.
+    #[serde(default)]
+    pub(crate) chess960: bool,
+    pub(crate) chess960_position: Option<u32>,
+    /// Material the engine gives, a preset such as "knight" or
+    /// "remove:SQUARES", see `ucui_utils::handicap`.
+    pub(crate) handicap: Option<String>,
+    /// Rules of the game, by their `UCI_Variant` name.
+    #[serde(default)]
+    pub(crate) variant: VariantSerde,
+    /// Play against another client instead of the engine, see `session`.
+    #[serde(default)]
+    pub(crate) human: bool,
+    /// The engine profile to play against, the server's engine when
+    /// not given.
+    pub(crate) engine: Option<String>,
+    /// Join the session of another client, all other options
+    /// are that client's.
+    pub(crate) join: Option<String>,
+    /// Version of the protocol spoken by the client, taken to be
+    /// compatible when not given.
+    pub(crate) protocol: Option<u32>,
 }
 
 // async fn handler(ws: WebSocketUpgrade, State(state): State<GameState>) -> Response {
@@ -65,7 +422,22 @@ pub async fn handler(
     State(server_state): State<UcuiState>,
     Query(options): Query<ConnectOptions>,
 ) -> Response {
-    ws.on_upgrade(move |socket| handle_socket(socket, options, server_state))
+    ws.on_upgrade(move |mut socket| async move {
+        if let Some(version) = options.protocol.filter(|version| !compatible(*version)) {
+            log::warn!("Client speaks protocol version {version}");
+            let reason = format!(
+                "protocol version {version} is not supported, \
+                 the server speaks versions {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION}"
+            );
+            refuse(&mut socket, ErrorCode::UnsupportedProtocol, reason).await;
+            return;
+        }
+        match (options.join.clone(), options.human) {
+            (Some(code), _) => session::join(socket, code, server_state).await,
+            (None, true) => session::host(socket, options, server_state).await,
+            (None, false) => handle_socket(socket, options, server_state).await,
+        }
+    })
 }
 
 fn sort_square(a: Square, b: Square) -> Ordering {
@@ -82,7 +454,7 @@ fn sort_square(a: Square, b: Square) -> Ordering {
     }
 }
 
-fn sort_move(a: &Move, b: &Move) -> Ordering {
+pub(crate) fn sort_move(a: &Move, b: &Move) -> Ordering {
     if a == b {
         Ordering::Equal
     } else {
@@ -102,32 +474,39 @@ async fn send_position(state: &mut GameState, socket: &mut WebSocket) {
     let mut moves = state.game.legal_moves();
     moves.sort_by(sort_move);
     let _ = socket
-        .send(ServerMessage::position(
-            moves.into_iter().map(|m| m.into()).collect(),
-            Fen::from_position(state.game.clone(), shakmaty::EnPassantMode::Legal).to_string(),
-        ))
+        .send(
+            ServerMessage::position(moves.into_iter().map(|m| m.into()).collect(), &state.game)
+                .message(),
+        )
         .await;
 }
 
 async fn play_position(
-    game: Chess,
+    game: VariantPosition,
     state: &mut GameState,
     socket: &mut WebSocket,
     white_time: i64,
     black_time: i64,
 ) -> bool {
-    match game.outcome() {
-        Some(outcome) => {
-            let _ = socket.send(ServerMessage::outcome(outcome)).await;
+    match game_outcome(&game, &state.repetitions) {
+        Some((outcome, reason)) => {
+            monitor_end(state, outcome, reason).await;
+            let _ = socket
+                .send(ServerMessage::outcome_with_reason(outcome, reason).message())
+                .await;
             return true;
         }
         None => {
             state.game = game.clone();
-            state.engine.go(
-                Fen::from_position(game, shakmaty::EnPassantMode::Legal).to_string(),
-                Duration::milliseconds(white_time),
-                Duration::milliseconds(black_time),
-            );
+            state.thinking_since = Instant::now();
+            // a move of the repertoire needs no search
+            if book_move(state).is_none() {
+                state.engine.go(
+                    fen(&game),
+                    Duration::milliseconds(white_time),
+                    Duration::milliseconds(black_time),
+                );
+            }
         }
     }
     false
@@ -146,16 +525,49 @@ async fn handle_incoming_message(
                 white_time,
                 black_time,
             }) => {
+                if white_time < 0 || black_time < 0 {
+                    let _ = socket
+                        .send(
+                            ServerMessage::error(
+                                ErrorCode::InvalidTime,
+                                "clock times should not be negative",
+                                Some(format!("white_time={white_time} black_time={black_time}")),
+                            )
+                            .message(),
+                        )
+                        .await;
+                    return false;
+                }
                 let m: Move = ply.into();
                 let game = state.game.clone();
-                if let Ok(new_pos) = game.play(&m) {
-                    monitor_set(state).await;
-                    return play_position(new_pos, state, socket, white_time, black_time).await;
+                match game.play(&m) {
+                    Ok(new_pos) => {
+                        state.clock = Clocks {
+                            white: white_time,
+                            black: black_time,
+                        };
+                        monitor_move(state, &m, Score::None).await;
+                        if let Some(feedback) = train(state, &m).await {
+                            let _ = socket.send(feedback).await;
+                        }
+                        state.repetitions.see(&new_pos);
+                        return play_position(new_pos, state, socket, white_time, black_time).await;
+                    }
+                    Err(_) => {
+                        log::warn!("Game {}: illegal move", state.id);
+                        let _ = socket
+                            .send(ServerMessage::illegal_move(&m, &state.game).message())
+                            .await;
+                        send_position(state, socket).await;
+                    }
                 }
             }
-
-            _ => {
-                log::warn!("incoming_message failed to parse '{text}'")
+            Ok(ClientMessage::Hint { level }) => return hint(state, socket, level).await,
+            Err(err) => {
+                log::warn!("incoming_message failed to parse '{text}'");
+                let _ = socket
+                    .send(ServerMessage::invalid_message(&err, text.as_str()).message())
+                    .await;
             }
         }
     }
@@ -163,35 +575,171 @@ async fn handle_incoming_message(
     false
 }
 
-async fn monitor_set(state: &mut GameState) {
-    let _ = state
+fn book_move(state: &GameState) -> Option<Move> {
+    state.training.as_ref().and_then(Training::book_move)
+}
+
+/// Follows `m`, about to be played, in the repertoire being trained.
+async fn train(state: &mut GameState, m: &Move) -> Option<Message> {
+    let training = state.training.as_mut()?;
+    training
+        .play(&state.game, m)
+        .await
+        .map(|feedback| ServerMessage::from(feedback).message())
+}
+
+/// Longer than any engine should take past the hint time.
+const HINT_MARGIN: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Asks the engine, idle while the player thinks, for a move. When the
+/// engine does not answer in time, the player gets an error and its late
+/// answer is skipped before its next move.
+async fn hint(state: &mut GameState, socket: &mut WebSocket, level: HintLevel) -> bool {
+    if state.game.turn() == state.engine_color
+        || game_outcome(&state.game, &state.repetitions).is_some()
+    {
+        log::warn!("Game {}: hint out of turn", state.id);
+        let _ = socket
+            .send(
+                ServerMessage::error(
+                    ErrorCode::OutOfTurn,
+                    "hints are given on the player's turn",
+                    None,
+                )
+                .message(),
+            )
+            .await;
+        return false;
+    }
+    let millis = get_hint_time();
+    if state.late_hints > 0 {
+        // still searching the same position for the last hint
+        state.late_hints -= 1;
+    } else {
+        state
+            .engine
+            .search(fen(&state.game), SearchLimit::MoveTime { millis });
+    }
+    let timeout = std::time::Duration::from_millis(millis) + HINT_MARGIN;
+    let error = match state.engine.recv_timeout(timeout).await {
+        Ok(EngineMessage::BestMove { move_, score }) => {
+            state.hints += 1;
+            let _ = socket
+                .send(
+                    ServerMessage::hint(level, &state.game, move_.into(), score, state.hints)
+                        .message(),
+                )
+                .await;
+            return false;
+        }
+        Ok(EngineMessage::Resign { reason }) => reason,
+        Ok(EngineMessage::Id(_)) => String::from("the engine did not search"),
+        Err(RecvTimeoutError::Timeout) => {
+            state.late_hints += 1;
+            String::from("the engine did not answer in time")
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+            log::error!("<{}> engine is gone", state.engine.name());
+            return true;
+        }
+    };
+    log::warn!("<{}> no hint: {error}", state.engine.name());
+    let _ = socket
+        .send(ServerMessage::error(ErrorCode::NoHint, &error, None).message())
+        .await;
+    false
+}
+
+async fn monitor_start(state: &mut GameState) {
+    let (white, black) = match state.engine_color {
+        Color::White => (state.engine.name(), "Human".into()),
+        Color::Black => ("Human".into(), state.engine.name()),
+    };
+    let game = GameRecord::new(
+        state.id.clone(),
+        white,
+        black,
+        &state.first,
+        state.castling_mode == CastlingMode::Chess960,
+        Some(state.clock),
+    )
+    .with_history(&state.first, &state.history, state.castling_mode);
+    state.server_state.monitor.start(game).await;
+}
+
+/// Records `m`, played from the current position.
+async fn monitor_move(state: &mut GameState, m: &Move, score: Score) {
+    let m = MonitorMove::new(
+        &state.game,
+        m,
+        state.castling_mode,
+        Some(state.clock),
+        score,
+    );
+    state.server_state.monitor.play(&state.id, m).await;
+}
+
+async fn monitor_end(state: &mut GameState, outcome: Outcome, reason: &str) {
+    state
         .server_state
         .monitor
-        .set(
-            state.id.clone(),
-            Fen::from_setup(
-                state
-                    .game
-                    .clone()
-                    .into_setup(shakmaty::EnPassantMode::Always),
-            ),
-        )
+        .end(&state.id, outcome, reason)
         .await;
 }
 
+/// Saves the game, unless nothing was played.
+async fn archive(state: &mut GameState) {
+    let Some(record) = state.server_state.monitor.game(&state.id).await else {
+        return;
+    };
+    if record.moves.is_empty() {
+        return;
+    }
+    let engine = EngineProfile {
+        name: state.engine.name(),
+        options: state.profile.options(),
+    };
+    let handicap = state.handicap.as_ref().map(Handicap::description);
+    let mut game = ArchivedGame::new(record, Some(engine), handicap);
+    game.hints = state.hints;
+    let transcript = state.engine.transcript();
+    state.server_state.archive.save(game, transcript).await;
+}
+
 async fn handle_socket(mut socket: WebSocket, options: ConnectOptions, server_state: UcuiState) {
-    let mut state = GameState::new(options.engine_color.into(), options.fen, server_state);
+    let mut state = match GameState::new(&options, server_state).await {
+        Ok(state) => state,
+        Err(err) => {
+            log::warn!("Game refused: {err}");
+            refuse(&mut socket, ErrorCode::InvalidStart, err).await;
+            return;
+        }
+    };
+    log::info!("Game {} against {}", state.id, state.engine.name());
+    monitor_start(&mut state).await;
+    if let Some(transcript) = state.engine.transcript() {
+        state
+            .server_state
+            .transcripts
+            .add(state.id.clone(), state.engine.name(), transcript)
+            .await;
+    }
     let _ = socket
-        .send(ServerMessage::ready(
-            state.engine.name(),
-            state.game.turn().into(),
-            state
-                .game
-                .legal_moves()
-                .into_iter()
-                .map(|m| m.into())
-                .collect(),
-        ))
+        .send(
+            ServerMessage::ready(
+                state.engine.name(),
+                &state.game,
+                state.castling_mode == CastlingMode::Chess960,
+                state.handicap.as_ref().map(Handicap::description),
+                state
+                    .game
+                    .legal_moves()
+                    .into_iter()
+                    .map(|m| m.into())
+                    .collect(),
+            )
+            .message(),
+        )
         .await;
 
     // we might have to start game
@@ -209,8 +757,6 @@ async fn handle_socket(mut socket: WebSocket, options: ConnectOptions, server_st
         .await;
     }
 
-    monitor_set(&mut state).await;
-
     loop {
         if !engine_just_played && state.game.turn() != state.engine_color {
             log::debug!("Waiting for client");
@@ -228,135 +774,133 @@ async fn handle_socket(mut socket: WebSocket, options: ConnectOptions, server_st
             }
         } else {
             log::debug!("Waiting for engine");
-            if let Ok(EngineMessage::BestMove { move_, score }) = state.engine.recv() {
-                let m: Move = move_.into();
-                let from: Vec<ucui_utils::MoveSerde> = state
-                    .game
-                    .legal_moves()
-                    .into_iter()
-                    .map(ucui_utils::MoveSerde::from)
-                    .collect();
-                state.game = state.game.clone().play(&m).unwrap();
-                let check = if state.game.is_check() {
-                    "+"
-                } else if state.game.is_checkmate() {
-                    "#"
-                } else {
-                    ""
-                };
-                let _ = socket
-                    .send(ServerMessage::engine_move(
-                        m,
-                        from,
-                        check.into(),
-                        Fen::from_position(state.game.clone(), shakmaty::EnPassantMode::Legal)
-                            .to_string(),
-                        score,
-                    ))
-                    .await;
-                if let Some(outcome) = state.game.outcome() {
-                    let _ = socket.send(ServerMessage::outcome(outcome)).await;
+            let message = match book_move(&state) {
+                Some(m) => EngineMessage::BestMove {
+                    move_: m.into(),
+                    score: Score::None,
+                },
+                None => match state.engine.recv().await {
+                    Ok(message)
+                        if state.late_hints > 0 && !matches!(message, EngineMessage::Id(_)) =>
+                    {
+                        log::debug!("<{}> late hint skipped", state.engine.name());
+                        state.late_hints -= 1;
+                        continue;
+                    }
+                    Ok(message) => message,
+                    Err(err) => {
+                        log::error!("<{}> engine is gone: {err}", state.engine.name());
+                        break;
+                    }
+                },
+            };
+            match message {
+                EngineMessage::BestMove { move_, score } => {
+                    let m: Move = move_.into();
+                    let from: Vec<ucui_utils::MoveSerde> = state
+                        .game
+                        .legal_moves()
+                        .into_iter()
+                        .map(ucui_utils::MoveSerde::from)
+                        .collect();
+                    let Ok(game) = state.game.clone().play(&m) else {
+                        log::error!(
+                            "<{}> illegal move {}",
+                            state.engine.name(),
+                            m.to_uci(state.castling_mode)
+                        );
+                        break;
+                    };
+                    state.press_engine_clock();
+                    monitor_move(&mut state, &m, score.clone()).await;
+                    let feedback = train(&mut state, &m).await;
+                    state.game = game;
+                    state.repetitions.see(&state.game);
+                    let check = notation::suffix(&state.game);
+                    let _ = socket
+                        .send(
+                            ServerMessage::engine_move(m, from, check.into(), &state.game, score)
+                                .message(),
+                        )
+                        .await;
+                    if let Some(feedback) = feedback {
+                        let _ = socket.send(feedback).await;
+                    }
+                    if let Some((outcome, reason)) = game_outcome(&state.game, &state.repetitions) {
+                        monitor_end(&mut state, outcome, reason).await;
+                        let _ = socket
+                            .send(ServerMessage::outcome_with_reason(outcome, reason).message())
+                            .await;
+                        break;
+                    } else {
+                        send_position(&mut state, &mut socket).await;
+                    }
+                }
+                EngineMessage::Resign { reason } => {
+                    log::warn!("<{}> resigns: {reason}", state.engine.name());
+                    let outcome = Outcome::Decisive {
+                        winner: !state.engine_color,
+                    };
+                    monitor_end(&mut state, outcome, "resignation").await;
+                    let _ = socket
+                        .send(ServerMessage::outcome_with_reason(outcome, "resignation").message())
+                        .await;
                     break;
-                } else {
-                    send_position(&mut state, &mut socket).await;
                 }
-
-                monitor_set(&mut state).await;
+                EngineMessage::Id(_) => {}
             }
         }
 
         engine_just_played = false;
     }
     log::info!("End Of Socket");
+    archive(&mut state).await;
     state.server_state.monitor.del(state.id.clone()).await;
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "_tag")]
-enum ServerMessage {
-    Ready {
-        name: String,
-        turn: ColorSerde,
-        #[serde(rename = "legalMoves")]
-        legal_moves: Vec<ucui_utils::MoveSerde>,
-    },
-    Position {
-        #[serde(rename = "legalMoves")]
-        legal_moves: Vec<ucui_utils::MoveSerde>,
-        fen: String,
-    },
-    EngineMove {
-        #[serde(rename = "move")]
-        _move: ucui_utils::MoveSerde,
-        from: Vec<ucui_utils::MoveSerde>,
-        check: String,
-        fen: String,
-        score: Score,
-    },
-    Outcome {
-        outcome: String,
-    },
-}
-
-impl ServerMessage {
-    fn ready(name: String, turn: ColorSerde, legal_moves: Vec<ucui_utils::MoveSerde>) -> Message {
-        Message::text(
-            serde_json::to_string(&ServerMessage::Ready {
-                name,
-                turn,
-                legal_moves,
-            })
-            .unwrap(),
-        )
+/// A message of the protocol, as sent on a websocket.
+pub(crate) trait WsMessage: Serialize {
+    fn message(&self) -> Message {
+        Message::text(serde_json::to_string(self).unwrap())
     }
+}
 
-    fn position(legal_moves: Vec<ucui_utils::MoveSerde>, fen: String) -> Message {
-        Message::text(serde_json::to_string(&ServerMessage::Position { legal_moves, fen }).unwrap())
-    }
+impl WsMessage for ServerMessage {}
 
-    fn engine_move(
-        m: Move,
-        from: Vec<ucui_utils::MoveSerde>,
-        check: String,
-        fen: String,
-        score: Score,
-    ) -> Message {
-        Message::text(
-            serde_json::to_string(&ServerMessage::EngineMove {
-                _move: m.into(),
-                from,
-                check,
-                fen,
-                score,
-            })
-            .unwrap(),
-        )
-    }
+impl WsMessage for MonitorMessage {}
 
-    fn outcome(outcome: Outcome) -> Message {
-        let o = match outcome {
-            Outcome::Draw => "½-½",
-            Outcome::Decisive { winner } => {
-                if winner == Color::White {
-                    "1-0"
-                } else {
-                    "0-1"
-                }
-            }
-        };
-        Message::text(serde_json::to_string(&ServerMessage::Outcome { outcome: o.into() }).unwrap())
-    }
+/// Tells the client why, before closing the socket.
+pub(crate) async fn refuse(socket: &mut WebSocket, code: ErrorCode, reason: String) {
+    let _ = socket
+        .send(ServerMessage::error(code, &reason, None).message())
+        .await;
+    let _ = socket
+        .send(Message::Close(Some(CloseFrame {
+            code: axum::extract::ws::close_code::POLICY,
+            reason: reason.into(),
+        })))
+        .await;
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(tag = "_tag")]
-enum ClientMessage {
-    Move {
-        #[serde(rename = "move")]
-        _move: ucui_utils::MoveSerde,
-        white_time: i64,
-        black_time: i64,
-    },
+/// Why the game is over, the rules of the variant first.
+pub(crate) fn outcome_reason(game: &VariantPosition) -> &'static str {
+    if game.variant_outcome().is_some() {
+        match game.variant() {
+            Variant::Atomic => "explosion",
+            Variant::Antichess => "no pieces left",
+            Variant::KingOfTheHill => "king in the center",
+            Variant::ThreeCheck => "three checks",
+            Variant::RacingKings => "king on the eighth rank",
+            Variant::Horde => "horde destroyed",
+            Variant::Chess | Variant::Crazyhouse => "variant rules",
+        }
+    } else if game.is_checkmate() {
+        "checkmate"
+    } else if game.is_stalemate() {
+        "stalemate"
+    } else {
+        "insufficient material"
+    }
 }
 
 // #[cfg(test)]
@@ -0,0 +1,412 @@
+//! Opening repertoires to train with
+//!
+//! `POST /repertoire` takes a PGN, variations included, and the color it
+//! is prepared for. Every line goes from the start position to the end of
+//! a variation, and is keyed by its moves as in `ucui_eco`, so that
+//! transpositions are not followed. A game on `/engine` with
+//! `repertoire=ID` has the engine play the opponent's moves of one of the
+//! lines, while the player's moves are checked against the prepared ones.
+//! The training is over at the end of the line or at the first move out
+//! of the repertoire, and the game goes on against the engine.
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hasher, RandomState},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    san::SanPlus,
+    variant::{Variant, VariantPosition},
+    Color, Move, Position,
+};
+use tokio::sync::Mutex;
+use ucui_eco::{find_eco_from_moves, sequence_key};
+use ucui_protocol::ServerMessage;
+use ucui_utils::{notation::movetext, pgn::parse_pgn_lines, variant::fen, ColorSerde};
+use uuid::Uuid;
+
+use crate::state::UcuiState;
+
+/// Oldest repertoires are dropped past this number.
+const MAX_REPERTOIRES: usize = 100;
+
+#[derive(Clone, Default, Serialize)]
+pub struct LineStats {
+    /// Times the player went through the whole line.
+    pub completed: u32,
+    /// Times the player left the repertoire on this line.
+    pub deviations: u32,
+    #[serde(rename = "lastTrained")]
+    pub last_trained: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Line {
+    #[serde(skip)]
+    moves: Vec<Move>,
+    /// In SAN, with move numbers.
+    pub movetext: String,
+    pub eco: Option<String>,
+    pub opening: Option<String>,
+    pub stats: LineStats,
+}
+
+impl Line {
+    fn new(moves: Vec<Move>) -> Self {
+        let eco = find_eco_from_moves(&moves);
+        Line {
+            movetext: movetext(&VariantPosition::new(Variant::Chess), &moves),
+            eco: eco.map(|eco| eco.code.clone()),
+            opening: eco.map(|eco| eco.name.clone()),
+            moves,
+            stats: LineStats::default(),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct Repertoire {
+    pub id: String,
+    pub name: String,
+    /// The side the player prepared.
+    pub color: ColorSerde,
+    pub created: DateTime<Utc>,
+    pub lines: Vec<Line>,
+    /// Moves prepared after each sequence of moves, by its key.
+    #[serde(skip)]
+    tree: HashMap<String, Vec<Move>>,
+}
+
+impl Repertoire {
+    fn new(name: String, color: Color, lines: Vec<Vec<Move>>) -> Self {
+        let mut tree: HashMap<String, Vec<Move>> = HashMap::new();
+        for line in &lines {
+            for ply in 0..line.len() {
+                let next = tree.entry(sequence_key(&line[..ply])).or_default();
+                if !next.contains(&line[ply]) {
+                    next.push(line[ply].clone());
+                }
+            }
+        }
+        Repertoire {
+            id: Uuid::new_v4().to_string(),
+            name,
+            color: color.into(),
+            created: Utc::now(),
+            lines: lines.into_iter().map(Line::new).collect(),
+            tree,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Repertoires(Arc<Mutex<Vec<Repertoire>>>);
+
+impl Repertoires {
+    async fn add(&self, repertoire: Repertoire) {
+        let mut repertoires = self.0.lock().await;
+        if repertoires.len() >= MAX_REPERTOIRES {
+            let _ = repertoires.remove(0);
+        }
+        repertoires.push(repertoire);
+    }
+
+    async fn get(&self, id: &str) -> Option<Repertoire> {
+        let repertoires = self.0.lock().await;
+        repertoires.iter().find(|r| r.id == id).cloned()
+    }
+
+    async fn delete(&self, id: &str) -> bool {
+        let mut repertoires = self.0.lock().await;
+        let before = repertoires.len();
+        repertoires.retain(|r| r.id != id);
+        repertoires.len() < before
+    }
+
+    async fn record(&self, id: &str, line: usize, completed: bool) {
+        let mut repertoires = self.0.lock().await;
+        let stats = repertoires
+            .iter_mut()
+            .find(|r| r.id == id)
+            .and_then(|r| r.lines.get_mut(line))
+            .map(|line| &mut line.stats);
+        if let Some(stats) = stats {
+            if completed {
+                stats.completed += 1;
+            } else {
+                stats.deviations += 1;
+            }
+            stats.last_trained = Some(Utc::now());
+        }
+    }
+}
+
+/// How the engine picks the line to play.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub(crate) enum Choice {
+    /// Lines the player often deviates from come more often.
+    #[default]
+    Weighted,
+    /// Every line as often.
+    Random,
+}
+
+/// A number from 0 to 1, 1 excluded.
+fn random() -> f64 {
+    (RandomState::new().build_hasher().finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// What the player is told about their move.
+pub(crate) enum Feedback {
+    /// A move out of the repertoire, and the moves prepared, in SAN.
+    Deviation {
+        line: usize,
+        played: String,
+        expected: Vec<String>,
+    },
+    /// The end of the line.
+    Completed { line: usize },
+}
+
+impl From<Feedback> for ServerMessage {
+    fn from(feedback: Feedback) -> Self {
+        match feedback {
+            Feedback::Deviation {
+                line,
+                played,
+                expected,
+            } => ServerMessage::Deviation {
+                line,
+                played,
+                expected,
+            },
+            Feedback::Completed { line } => ServerMessage::LineCompleted { line },
+        }
+    }
+}
+
+/// A repertoire being trained in a game.
+pub(crate) struct Training {
+    repertoires: Repertoires,
+    repertoire: Repertoire,
+    choice: Choice,
+    /// The line the engine follows.
+    line: usize,
+    played: Vec<Move>,
+    over: bool,
+}
+
+impl Training {
+    pub(crate) async fn start(
+        repertoires: &Repertoires,
+        id: &str,
+        choice: Choice,
+    ) -> Result<Self, String> {
+        let repertoire = repertoires
+            .get(id)
+            .await
+            .ok_or_else(|| format!("unknown repertoire '{id}'"))?;
+        let mut training = Training {
+            repertoires: repertoires.clone(),
+            repertoire,
+            choice,
+            line: 0,
+            played: Vec::new(),
+            over: false,
+        };
+        training.line = training.pick();
+        Ok(training)
+    }
+
+    /// The color the player trains.
+    pub(crate) fn color(&self) -> Color {
+        self.repertoire.color.clone().into()
+    }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.repertoire.name
+    }
+
+    /// One of the lines going on from the moves played so far.
+    fn pick(&self) -> usize {
+        let candidates: Vec<(usize, f64)> = self
+            .repertoire
+            .lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.moves.starts_with(&self.played))
+            .map(|(index, line)| {
+                let weight = match self.choice {
+                    Choice::Random => 1.0,
+                    Choice::Weighted => {
+                        f64::from(line.stats.deviations + 1) / f64::from(line.stats.completed + 1)
+                    }
+                };
+                (index, weight)
+            })
+            .collect();
+        let total: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+        let mut target = random() * total;
+        for (index, weight) in &candidates {
+            if target < *weight {
+                return *index;
+            }
+            target -= weight;
+        }
+        candidates.last().map(|(index, _)| *index).unwrap_or(0)
+    }
+
+    fn next_move(&self) -> Option<&Move> {
+        self.repertoire.lines[self.line]
+            .moves
+            .get(self.played.len())
+    }
+
+    /// The opponent's move in the line, on the engine's turn.
+    pub(crate) fn book_move(&self) -> Option<Move> {
+        if self.over {
+            return None;
+        }
+        self.next_move().cloned()
+    }
+
+    async fn end(&mut self, completed: bool) {
+        self.over = true;
+        self.repertoires
+            .record(&self.repertoire.id, self.line, completed)
+            .await;
+    }
+
+    /// Follows `m`, played in `game` by either side.
+    pub(crate) async fn play(&mut self, game: &VariantPosition, m: &Move) -> Option<Feedback> {
+        if self.over {
+            return None;
+        }
+        if game.turn() == self.color() {
+            let prepared = self
+                .repertoire
+                .tree
+                .get(&sequence_key(&self.played))
+                .cloned()
+                .unwrap_or_default();
+            if !prepared.contains(m) {
+                self.end(false).await;
+                let san = |m: &Move| SanPlus::from_move(game.clone(), m).to_string();
+                return Some(Feedback::Deviation {
+                    line: self.line,
+                    played: san(m),
+                    expected: prepared.iter().map(san).collect(),
+                });
+            }
+            self.played.push(m.clone());
+            if !self.repertoire.lines[self.line]
+                .moves
+                .starts_with(&self.played)
+            {
+                // another line of the repertoire
+                self.line = self.pick();
+            }
+        } else {
+            self.played.push(m.clone());
+        }
+        if self.next_move().is_none() {
+            self.end(true).await;
+            return Some(Feedback::Completed { line: self.line });
+        }
+        None
+    }
+}
+
+/// Body of `POST /repertoire`.
+#[derive(Deserialize)]
+pub struct NewRepertoire {
+    pgn: String,
+    color: ColorSerde,
+    name: Option<String>,
+}
+
+fn bad_request(message: String) -> Response {
+    (StatusCode::BAD_REQUEST, message).into_response()
+}
+
+fn not_found(id: &str) -> Response {
+    (StatusCode::NOT_FOUND, format!("no repertoire {id}")).into_response()
+}
+
+pub async fn create(
+    State(state): State<UcuiState>,
+    Json(request): Json<NewRepertoire>,
+) -> Response {
+    let games = match parse_pgn_lines(&request.pgn) {
+        Ok(games) => games,
+        Err(err) => return bad_request(err),
+    };
+    let standard = fen(&VariantPosition::new(Variant::Chess));
+    if games
+        .iter()
+        .any(|game| game.variant != Variant::Chess || game.chess960 || fen(&game.start) != standard)
+    {
+        return bad_request("lines should start from the standard start position".into());
+    }
+    if games.is_empty() {
+        return bad_request("the repertoire has no moves".into());
+    }
+    let name = request
+        .name
+        .or_else(|| games[0].header("Event").map(String::from))
+        .unwrap_or_else(|| "Repertoire".into());
+    let lines = games.into_iter().map(|game| game.moves).collect();
+    let repertoire = Repertoire::new(name, request.color.into(), lines);
+    state.repertoires.add(repertoire.clone()).await;
+    (StatusCode::CREATED, Json(repertoire)).into_response()
+}
+
+#[derive(Serialize)]
+pub struct Summary {
+    id: String,
+    name: String,
+    color: ColorSerde,
+    created: DateTime<Utc>,
+    lines: usize,
+}
+
+/// Newest first.
+pub async fn list(State(state): State<UcuiState>) -> Response {
+    let repertoires = state.repertoires.0.lock().await;
+    let summaries: Vec<Summary> = repertoires
+        .iter()
+        .rev()
+        .map(|r| Summary {
+            id: r.id.clone(),
+            name: r.name.clone(),
+            color: r.color.clone(),
+            created: r.created,
+            lines: r.lines.len(),
+        })
+        .collect();
+    Json(summaries).into_response()
+}
+
+pub async fn fetch(State(state): State<UcuiState>, Path(id): Path<String>) -> Response {
+    match state.repertoires.get(&id).await {
+        Some(repertoire) => Json(repertoire).into_response(),
+        None => not_found(&id),
+    }
+}
+
+pub async fn delete(State(state): State<UcuiState>, Path(id): Path<String>) -> Response {
+    if state.repertoires.delete(&id).await {
+        StatusCode::NO_CONTENT.into_response()
+    } else {
+        not_found(&id)
+    }
+}
@@ -1,8 +1,11 @@
-use crate::config::{get_interface, get_port, get_static_dir};
+use crate::config::{get_admin_token, get_interface, get_port, get_static_dir};
 use crate::state::UcuiState;
 use axum::http::Method;
 use axum::response::Redirect;
-use axum::{routing::any, Router};
+use axum::{
+    routing::{any, get, post},
+    Router,
+};
 use std::net::SocketAddr;
 use tokio::runtime::Runtime;
 use tower_http::trace::TraceLayer;
@@ -25,24 +28,53 @@ pub fn start() {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    serve(router(UcuiState::new())).unwrap();
+}
+
+pub fn router(state: UcuiState) -> Router {
     let cors = CorsLayer::new()
-        // allow `GET` and `POST` when accessing the resource
-        .allow_methods([Method::GET, Method::POST])
+        // allow `GET`, `POST` and `DELETE` when accessing the resource
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
         // allow requests from any origin
         .allow_origin(tower_http::cors::Any);
 
-    let router = Router::new()
+    let routes = Router::new()
         .route("/", any(|| async { Redirect::permanent("/play/") }))
         .route("/eco", any(crate::eco::lookup_eco))
         .route("/legals", any(crate::eco::legal_moves))
+        .route("/position/validate", get(crate::eco::validate_position))
+        .route("/convert", get(crate::eco::convert_moves))
         .route("/engine", any(crate::play::handler))
         .route("/games", any(crate::monitor::handler))
+        .route("/games/{id}", any(crate::monitor::game_handler))
+        .route("/archive", get(crate::archive::list))
+        .route(
+            "/archive/{id}",
+            get(crate::archive::fetch).delete(crate::archive::delete),
+        )
+        .route("/archive/{id}/pgn", get(crate::archive::fetch_pgn))
+        .route("/annotate", post(crate::annotate::queue))
+        .route("/annotate/{id}", get(crate::annotate::fetch))
+        .route("/annotate/{id}/pgn", get(crate::annotate::fetch_pgn))
+        .route(
+            "/repertoire",
+            get(crate::repertoire::list).post(crate::repertoire::create),
+        )
+        .route(
+            "/repertoire/{id}",
+            get(crate::repertoire::fetch).delete(crate::repertoire::delete),
+        );
+    let routes = match get_admin_token() {
+        Some(_) => routes
+            .route("/admin/transcripts", get(crate::transcripts::list))
+            .route("/admin/transcripts/{id}", get(crate::transcripts::fetch)),
+        None => routes,
+    };
+    routes
         .fallback_service(ServeDir::new(get_static_dir()).append_index_html_on_directories(true))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
-        .with_state(UcuiState::new());
-
-    serve(router).unwrap();
+        .with_state(state)
 }
 
 fn serve(app: Router) -> Result<(), Box<dyn std::error::Error>> {
@@ -0,0 +1,433 @@
+//! Games between two clients
+//!
+//! A client connects to `/engine?human=true&...` with the usual options,
+//! `engine_color` being the color of its opponent, and gets a `Waiting`
+//! message with a join code. The other client connects to
+//! `/engine?join=CODE`. Both then get the messages of an engine game,
+//! each seeing the other as the engine, while the server checks moves
+//! and runs the clock.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use axum::extract::ws::{Message, WebSocket};
+use shakmaty::{variant::VariantPosition, CastlingMode, Color, Move, Outcome, Position};
+use tokio::{
+    sync::{oneshot, Mutex},
+    time::Instant,
+};
+use ucui_protocol::{ClientMessage, ErrorCode, ServerMessage};
+use ucui_utils::{handicap::Handicap, notation, MoveSerde};
+use uuid::Uuid;
+
+use crate::{
+    archive::ArchivedGame,
+    monitor::{Clocks, GameRecord, MonitorMove},
+    play::{
+        game_outcome, handicap, refuse, sort_move, start_position, ConnectOptions, Repetitions,
+        Start, WsMessage,
+    },
+    state::UcuiState,
+};
+
+/// Length of join codes.
+const CODE_LENGTH: usize = 6;
+
+/// Sessions waiting for a second client, by join code.
+#[derive(Clone, Default)]
+pub struct Sessions(Arc<Mutex<HashMap<String, oneshot::Sender<WebSocket>>>>);
+
+impl Sessions {
+    async fn open(&self) -> (String, oneshot::Receiver<WebSocket>) {
+        let mut sessions = self.0.lock().await;
+        let code = loop {
+            let code: String = Uuid::new_v4()
+                .simple()
+                .to_string()
+                .chars()
+                .take(CODE_LENGTH)
+                .collect::<String>()
+                .to_uppercase();
+            if !sessions.contains_key(&code) {
+                break code;
+            }
+        };
+        let (tx, rx) = oneshot::channel();
+        let _ = sessions.insert(code.clone(), tx);
+        (code, rx)
+    }
+
+    async fn take(&self, code: &str) -> Option<oneshot::Sender<WebSocket>> {
+        self.0.lock().await.remove(&code.to_uppercase())
+    }
+}
+
+/// Waits for another client to join, then plays the game.
+pub async fn host(mut socket: WebSocket, options: ConnectOptions, server_state: UcuiState) {
+    let giver: Color = options.engine_color.clone().into();
+    let start = handicap(&options).and_then(|handicap| {
+        start_position(&options, handicap.as_ref(), giver).map(|start| (start, handicap))
+    });
+    let (start, handicap) = match start {
+        Ok(start) => start,
+        Err(err) => {
+            log::warn!("Session refused: {err}");
+            refuse(&mut socket, ErrorCode::InvalidStart, err).await;
+            return;
+        }
+    };
+    let (code, mut joined) = server_state.sessions.open().await;
+    log::info!("Session {code} waiting for a second player");
+    let _ = socket
+        .send(ServerMessage::waiting(code.clone()).message())
+        .await;
+
+    let guest = loop {
+        tokio::select! {
+            guest = &mut joined => break guest.ok(),
+            message = socket.recv() => {
+                if !matches!(message, Some(Ok(_))) {
+                    break None;
+                }
+            }
+        }
+    };
+    match guest {
+        Some(guest) => {
+            let guest_color: Color = options.engine_color.clone().into();
+            let (white, black) = match guest_color {
+                Color::White => (guest, socket),
+                Color::Black => (socket, guest),
+            };
+            Session::new(&options, start, handicap, server_state, white, black)
+                .play()
+                .await;
+        }
+        None => {
+            log::info!("Session {code} closed before anyone joined");
+            let _ = server_state.sessions.take(&code).await;
+        }
+    }
+}
+
+/// Hands the socket over to the client waiting with `code`.
+pub async fn join(mut socket: WebSocket, code: String, server_state: UcuiState) {
+    match server_state.sessions.take(&code).await {
+        Some(host) => {
+            if host.send(socket).is_err() {
+                log::warn!("Session {code} is gone");
+            }
+        }
+        None => {
+            log::warn!("No session to join with code '{code}'");
+            refuse(
+                &mut socket,
+                ErrorCode::InvalidStart,
+                format!("no session {code}"),
+            )
+            .await;
+        }
+    }
+}
+
+/// Time left to each player, the clock of the player to move
+/// running since `since`.
+struct Clock {
+    white: Duration,
+    black: Duration,
+    since: Instant,
+}
+
+impl Clock {
+    fn left(&self, color: Color) -> Duration {
+        let left = match color {
+            Color::White => self.white,
+            Color::Black => self.black,
+        };
+        left.saturating_sub(self.since.elapsed())
+    }
+
+    /// Time left to each player, the one to move being `turn`.
+    fn clocks(&self, turn: Color) -> Clocks {
+        let millis = |color: Color| {
+            if color == turn {
+                self.left(color)
+            } else if color == Color::White {
+                self.white
+            } else {
+                self.black
+            }
+        };
+        Clocks {
+            white: millis(Color::White).as_millis() as i64,
+            black: millis(Color::Black).as_millis() as i64,
+        }
+    }
+
+    /// Stops the clock of `color` and starts the other one.
+    fn press(&mut self, color: Color) {
+        let left = self.left(color);
+        match color {
+            Color::White => self.white = left,
+            Color::Black => self.black = left,
+        }
+        self.since = Instant::now();
+    }
+}
+
+/// What happened on a socket, for the player of `color`.
+enum Event {
+    Message(Color, Message),
+    Gone(Color),
+    Flag,
+}
+
+struct Session {
+    id: String,
+    game: VariantPosition,
+    castling_mode: CastlingMode,
+    handicap: Option<Handicap>,
+    white: WebSocket,
+    black: WebSocket,
+    clock: Clock,
+    server_state: UcuiState,
+    /// Where the game started, before the moves given by the host.
+    first: VariantPosition,
+    history: Vec<Move>,
+    repetitions: Repetitions,
+}
+
+impl Session {
+    fn new(
+        options: &ConnectOptions,
+        start: Start,
+        handicap: Option<Handicap>,
+        server_state: UcuiState,
+        white: WebSocket,
+        black: WebSocket,
+    ) -> Self {
+        let millis = |ms: i64| Duration::from_millis(ms.max(0) as u64);
+        Session {
+            id: Uuid::new_v4().to_string(),
+            game: start.game,
+            castling_mode: start.castling_mode,
+            handicap,
+            first: start.first,
+            history: start.moves,
+            repetitions: start.repetitions,
+            white,
+            black,
+            clock: Clock {
+                white: millis(options.white_time),
+                black: millis(options.black_time),
+                since: Instant::now(),
+            },
+            server_state,
+        }
+    }
+
+    fn socket(&mut self, color: Color) -> &mut WebSocket {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    async fn send(&mut self, color: Color, message: Message) {
+        let _ = self.socket(color).send(message).await;
+    }
+
+    async fn send_both(&mut self, message: Message) {
+        self.send(Color::White, message.clone()).await;
+        self.send(Color::Black, message).await;
+    }
+
+    fn legal_moves(&self) -> Vec<MoveSerde> {
+        let mut moves = self.game.legal_moves();
+        moves.sort_by(sort_move);
+        moves.into_iter().map(MoveSerde::from).collect()
+    }
+
+    async fn monitor_start(&mut self) {
+        let game = GameRecord::new(
+            self.id.clone(),
+            "Human".into(),
+            "Human".into(),
+            &self.first,
+            self.castling_mode == CastlingMode::Chess960,
+            Some(self.clock.clocks(self.game.turn())),
+        )
+        .with_history(&self.first, &self.history, self.castling_mode);
+        self.server_state.monitor.start(game).await;
+    }
+
+    async fn monitor_end(&mut self, outcome: Outcome, reason: &str) {
+        self.server_state
+            .monitor
+            .end(&self.id, outcome, reason)
+            .await;
+    }
+
+    async fn next_event(&mut self) -> Event {
+        let flag = tokio::time::sleep(self.clock.left(self.game.turn()));
+        tokio::select! {
+            message = self.white.recv() => match message {
+                Some(Ok(message)) => Event::Message(Color::White, message),
+                _ => Event::Gone(Color::White),
+            },
+            message = self.black.recv() => match message {
+                Some(Ok(message)) => Event::Message(Color::Black, message),
+                _ => Event::Gone(Color::Black),
+            },
+            _ = flag => Event::Flag,
+        }
+    }
+
+    async fn play(mut self) {
+        log::info!("Game {} between two players", self.id);
+        for color in [Color::White, Color::Black] {
+            self.send(color, ServerMessage::joined(color.into()).message())
+                .await;
+            let ready = ServerMessage::ready(
+                "Human".into(),
+                &self.game,
+                self.castling_mode == CastlingMode::Chess960,
+                self.handicap.as_ref().map(Handicap::description),
+                self.legal_moves(),
+            )
+            .message();
+            self.send(color, ready).await;
+        }
+        self.clock.since = Instant::now();
+        self.monitor_start().await;
+
+        loop {
+            match self.next_event().await {
+                Event::Flag => {
+                    let loser = self.game.turn();
+                    log::info!("Game {}: {loser} ran out of time", self.id);
+                    let outcome = Outcome::Decisive {
+                        winner: loser.other(),
+                    };
+                    self.monitor_end(outcome, "time").await;
+                    self.send_both(ServerMessage::outcome_with_reason(outcome, "time").message())
+                        .await;
+                    break;
+                }
+                Event::Gone(color) => {
+                    log::info!("Game {}: {color} left", self.id);
+                    let outcome = Outcome::Decisive {
+                        winner: color.other(),
+                    };
+                    self.monitor_end(outcome, "opponent left").await;
+                    self.send(
+                        color.other(),
+                        ServerMessage::outcome_with_reason(outcome, "opponent left").message(),
+                    )
+                    .await;
+                    break;
+                }
+                Event::Message(color, message) => {
+                    if self.handle_message(color, message).await {
+                        break;
+                    }
+                }
+            }
+        }
+        self.archive().await;
+        self.server_state.monitor.del(self.id.clone()).await;
+    }
+
+    /// Saves the game, unless nothing was played.
+    async fn archive(&mut self) {
+        let Some(record) = self.server_state.monitor.game(&self.id).await else {
+            return;
+        };
+        if !record.moves.is_empty() {
+            let handicap = self.handicap.as_ref().map(Handicap::description);
+            self.server_state
+                .archive
+                .save(ArchivedGame::new(record, None, handicap), None)
+                .await;
+        }
+    }
+
+    /// Plays a move of `color` and tells whether the game is over.
+    async fn handle_message(&mut self, color: Color, message: Message) -> bool {
+        let Message::Text(text) = message else {
+            return false;
+        };
+        let m: Move = match serde_json::from_str(text.as_str()) {
+            Ok(ClientMessage::Move { _move: m, .. }) => m.into(),
+            Ok(ClientMessage::Hint { .. }) => {
+                let error = ServerMessage::error(
+                    ErrorCode::InvalidMessage,
+                    "no hints in games between players",
+                    Some(text.to_string()),
+                )
+                .message();
+                self.send(color, error).await;
+                return false;
+            }
+            Err(err) => {
+                log::warn!("incoming_message failed to parse '{text}'");
+                let error = ServerMessage::invalid_message(&err, text.as_str()).message();
+                self.send(color, error).await;
+                return false;
+            }
+        };
+        if color != self.game.turn() {
+            log::warn!("Game {}: {color} played out of turn", self.id);
+            let error = ServerMessage::error(
+                ErrorCode::OutOfTurn,
+                "moves are played on the player's turn",
+                None,
+            )
+            .message();
+            self.send(color, error).await;
+            return false;
+        }
+        let from = self.legal_moves();
+        let game = match self.game.clone().play(&m) {
+            Ok(game) => game,
+            Err(_) => {
+                log::warn!("Game {}: illegal move from {color}", self.id);
+                let error = ServerMessage::illegal_move(&m, &self.game).message();
+                self.send(color, error).await;
+                let position = ServerMessage::position(from, &self.game).message();
+                self.send(color, position).await;
+                return false;
+            }
+        };
+        self.clock.press(color);
+        let clocks = self.clock.clocks(color.other());
+        let played = MonitorMove::new(
+            &self.game,
+            &m,
+            self.castling_mode,
+            Some(clocks),
+            ucui_engine::Score::None,
+        );
+        self.server_state.monitor.play(&self.id, played).await;
+        self.game = game;
+        self.repetitions.see(&self.game);
+
+        let check = notation::suffix(&self.game).to_string();
+        let reply =
+            ServerMessage::engine_move(m, from, check, &self.game, ucui_engine::Score::None)
+                .message();
+        self.send(color.other(), reply).await;
+        match game_outcome(&self.game, &self.repetitions) {
+            Some((outcome, reason)) => {
+                self.monitor_end(outcome, reason).await;
+                let outcome = ServerMessage::outcome_with_reason(outcome, reason).message();
+                self.send_both(outcome).await;
+                true
+            }
+            None => {
+                let position = ServerMessage::position(self.legal_moves(), &self.game).message();
+                self.send(color.other(), position).await;
+                false
+            }
+        }
+    }
+}
@@ -1,14 +1,33 @@
-use crate::monitor::Monitor;
+use crate::{
+    annotate::Annotations, archive::Archive, config::get_archive_dir, monitor::Monitor,
+    repertoire::Repertoires, session::Sessions, transcripts::Transcripts,
+};
 
 #[derive(Clone)]
 pub struct UcuiState {
     pub monitor: Monitor,
+    pub transcripts: Transcripts,
+    pub sessions: Sessions,
+    pub archive: Archive,
+    pub annotations: Annotations,
+    pub repertoires: Repertoires,
 }
 
 impl UcuiState {
     pub fn new() -> Self {
         Self {
             monitor: Monitor::new(),
+            transcripts: Transcripts::default(),
+            sessions: Sessions::default(),
+            archive: Archive::new(get_archive_dir()),
+            annotations: Annotations::default(),
+            repertoires: Repertoires::default(),
         }
     }
 }
+
+impl Default for UcuiState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
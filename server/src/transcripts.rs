@@ -0,0 +1,125 @@
+//! Engine transcripts of the games played on this server, for admins
+//! to look at when a game went wrong.
+//!
+//! The endpoints are only served with `--admin-token`, and answer the
+//! requests carrying the token. The last games are kept in memory, and
+//! archived games keep theirs in the archive.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::Mutex;
+use ucui_engine::Transcript;
+
+use crate::{config::get_admin_token, state::UcuiState};
+
+/// Oldest transcripts are dropped past this number.
+const MAX_TRANSCRIPTS: usize = 100;
+
+struct Entry {
+    id: String,
+    engine: String,
+    started: DateTime<Utc>,
+    transcript: Transcript,
+}
+
+#[derive(Serialize)]
+pub struct Summary {
+    id: String,
+    engine: String,
+    started: DateTime<Utc>,
+    lines: usize,
+}
+
+#[derive(Clone, Default)]
+pub struct Transcripts(Arc<Mutex<VecDeque<Entry>>>);
+
+impl Transcripts {
+    pub async fn add(&self, id: String, engine: String, transcript: Transcript) {
+        let mut entries = self.0.lock().await;
+        if entries.len() >= MAX_TRANSCRIPTS {
+            let _ = entries.pop_front();
+        }
+        entries.push_back(Entry {
+            id,
+            engine,
+            started: Utc::now(),
+            transcript,
+        });
+    }
+
+    async fn summaries(&self) -> Vec<Summary> {
+        let entries = self.0.lock().await;
+        entries
+            .iter()
+            .map(|entry| Summary {
+                id: entry.id.clone(),
+                engine: entry.engine.clone(),
+                started: entry.started,
+                lines: entry.transcript.len(),
+            })
+            .collect()
+    }
+
+    async fn get(&self, id: &str) -> Option<Transcript> {
+        let entries = self.0.lock().await;
+        entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| entry.transcript.clone())
+    }
+}
+
+/// Whether the request carries the admin token.
+pub(crate) fn authorized(headers: &HeaderMap) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    matches!((bearer, get_admin_token()), (Some(bearer), Some(token)) if bearer == token)
+}
+
+pub(crate) fn unauthorized() -> Response {
+    (StatusCode::UNAUTHORIZED, "admin token required").into_response()
+}
+
+pub async fn list(State(state): State<UcuiState>, headers: HeaderMap) -> Response {
+    if !authorized(&headers) {
+        return unauthorized();
+    }
+    Json(state.transcripts.summaries().await).into_response()
+}
+
+/// The transcript as text, ready to be replayed with `ucui-mock-engine --replay`.
+pub async fn fetch(
+    State(state): State<UcuiState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    if !authorized(&headers) {
+        return unauthorized();
+    }
+    let transcript = match state.transcripts.get(&id).await {
+        Some(transcript) => Some(transcript.to_string()),
+        None => state.archive.transcript(&id).await,
+    };
+    match transcript {
+        Some(transcript) => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            transcript,
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            format!("no transcript for game {id}"),
+        )
+            .into_response(),
+    }
+}
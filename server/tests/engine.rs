@@ -0,0 +1,1339 @@
+//! Plays games against ucui-mock-engine through the websocket endpoints,
+//! with the router running in process.
+
+use std::{
+    io::{Read, Write},
+    process::{Command, Stdio},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use ucui_engine::{Direction, Transcript};
+use ucui_protocol::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use ucui_server::{archive::Archive, config::init_config, server::router, state::UcuiState};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const ADMIN_TOKEN: &str = "secret";
+
+const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const AFTER_E4: &str = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+const AFTER_E4_E5_NF3: &str = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2";
+const AFTER_D4: &str = "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1";
+const AFTER_D4_D5: &str = "rnbqkbnr/ppp1pppp/8/3p4/3P4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 2";
+const AFTER_C4: &str = "rnbqkbnr/pppppppp/8/8/2P5/8/PP1PPPPP/RNBQKBNR b KQkq - 0 1";
+
+/// Serves the router on a free port, with the mock engine and
+/// tests/mock-engine.script as the configured engine.
+async fn start_server() -> String {
+    let _ = init_config([
+        "ucui-server",
+        "--engine",
+        env!("CARGO_BIN_EXE_ucui-mock-engine"),
+        "--engine-args",
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/mock-engine.script"),
+        "--engine-profile",
+        "name=greedy,bot=greedy",
+        "--admin-token",
+        ADMIN_TOKEN,
+    ]);
+    serve(UcuiState::new()).await
+}
+
+async fn serve(state: UcuiState) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router(state)).await.unwrap() });
+    format!("ws://{addr}")
+}
+
+async fn connect(url: String) -> Socket {
+    let (socket, _) = connect_async(url).await.expect("websocket connection");
+    socket
+}
+
+async fn start_game(server: &str, engine_color: &str, fen: Option<&str>) -> Socket {
+    let fen = fen
+        .map(|fen| format!("&fen={}", fen.replace(' ', "%20")))
+        .unwrap_or_default();
+    connect(format!(
+        "{server}/engine?engine_color={engine_color}&white_time=60000&black_time=60000{fen}"
+    ))
+    .await
+}
+
+/// Status line and body of a plain HTTP request.
+async fn http_request(server: &str, method: &str, path: &str) -> (String, String) {
+    http_send(server, method, path, None).await
+}
+
+/// Like `http_request`, with a JSON body.
+async fn http_send(
+    server: &str,
+    method: &str,
+    path: &str,
+    json: Option<Value>,
+) -> (String, String) {
+    http_send_with_headers(server, method, path, json, "").await
+}
+
+/// Body of a successful GET request with the admin token.
+async fn admin_get(server: &str, path: &str) -> String {
+    let auth = format!("Authorization: Bearer {ADMIN_TOKEN}\r\n");
+    let (status, body) = http_send_with_headers(server, "GET", path, None, &auth).await;
+    assert!(status.starts_with("HTTP/1.1 200"), "{status}");
+    body
+}
+
+/// Like `http_send`, with more headers, each ending with "\r\n".
+async fn http_send_with_headers(
+    server: &str,
+    method: &str,
+    path: &str,
+    json: Option<Value>,
+    headers: &str,
+) -> (String, String) {
+    let addr = server.trim_start_matches("ws://");
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let body = json.map(|json| json.to_string()).unwrap_or_default();
+    let content = if body.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "Content-Type: application/json\r\nContent-Length: {}\r\n",
+            body.len()
+        )
+    };
+    let request = format!(
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n{headers}{content}\r\n{body}"
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    let _ = stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap_or_default().to_string();
+    (status, body.to_string())
+}
+
+/// Body of a successful plain HTTP request.
+async fn http_get(server: &str, path: &str) -> String {
+    let (status, body) = http_request(server, "GET", path).await;
+    assert!(status.starts_with("HTTP/1.1 200"), "{status}");
+    body
+}
+
+/// Next message from the server, `None` once the socket is closed.
+async fn recv(socket: &mut Socket) -> Option<Value> {
+    loop {
+        let message = tokio::time::timeout(TIMEOUT, socket.next())
+            .await
+            .expect("server should answer in time");
+        match message {
+            Some(Ok(Message::Text(text))) => {
+                return Some(serde_json::from_str(text.as_str()).unwrap())
+            }
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+            Some(Ok(_)) => {}
+        }
+    }
+}
+
+async fn recv_tagged(socket: &mut Socket, tag: &str) -> Value {
+    let message = recv(socket).await.expect("socket should be open");
+    assert_eq!(message["_tag"], tag, "unexpected message {message}");
+    message
+}
+
+fn pawn_move(from: &str, to: &str) -> Value {
+    json!({
+        "_tag": "Normal",
+        "role": "Pawn",
+        "from": from,
+        "capture": null,
+        "to": to,
+        "promotion": null,
+    })
+}
+
+async fn send_move(socket: &mut Socket, m: Value) {
+    let message = json!({
+        "_tag": "Move",
+        "move": m,
+        "white_time": 60000,
+        "black_time": 60000,
+    });
+    socket
+        .send(Message::text(message.to_string()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn engine_plays_white() {
+    let server = start_server().await;
+    let mut game = start_game(&server, "white", None).await;
+
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["name"], "Mock Engine");
+    assert_eq!(ready["turn"], "white");
+    assert_eq!(ready["legalMoves"].as_array().unwrap().len(), 20);
+
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E2", "E4"));
+    assert_eq!(engine_move["check"], "");
+    assert_eq!(engine_move["fen"], AFTER_E4);
+    assert_eq!(
+        engine_move["score"],
+        json!({"_tag": "CentiPawns", "score": 17})
+    );
+    assert_eq!(engine_move["from"].as_array().unwrap().len(), 20);
+
+    let position = recv_tagged(&mut game, "Position").await;
+    assert_eq!(position["fen"], AFTER_E4);
+    assert_eq!(position["legalMoves"].as_array().unwrap().len(), 20);
+
+    send_move(&mut game, pawn_move("E7", "E5")).await;
+
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(
+        engine_move["move"],
+        json!({
+            "_tag": "Normal",
+            "role": "Knight",
+            "from": "G1",
+            "capture": null,
+            "to": "F3",
+            "promotion": null,
+        })
+    );
+    assert_eq!(engine_move["score"], json!({"_tag": "None"}));
+    assert_eq!(engine_move["fen"], AFTER_E4_E5_NF3);
+
+    let position = recv_tagged(&mut game, "Position").await;
+    assert_eq!(position["fen"], AFTER_E4_E5_NF3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn engine_profiles_are_chosen() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&engine=greedy"
+    ))
+    .await;
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["name"], "greedy");
+
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&engine=nobody"
+    ))
+    .await;
+    assert_eq!(close_reason(&mut game).await, "unknown engine 'nobody'");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn engine_takes_its_time() {
+    let server = start_server().await;
+    let started = Instant::now();
+    let mut game = start_game(&server, "black", Some(AFTER_D4)).await;
+
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["turn"], "black");
+
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert_eq!(engine_move["move"], pawn_move("D7", "D5"));
+    assert_eq!(engine_move["score"], json!({"_tag": "Mate", "moves": 3}));
+    assert_eq!(engine_move["fen"], AFTER_D4_D5);
+
+    let position = recv_tagged(&mut game, "Position").await;
+    assert_eq!(position["fen"], AFTER_D4_D5);
+
+    // the time it took is off its clock
+    let mut monitor = connect(format!("{server}/games")).await;
+    let init = recv_tagged(&mut monitor, "Init").await;
+    let id = init["games"][0]["id"].as_str().unwrap().to_string();
+    let mut spectator = connect(format!("{server}/games/{id}")).await;
+    let record = recv_tagged(&mut spectator, "Game").await;
+    let clock = &record["game"]["moves"][0]["clock"];
+    assert_eq!(clock["white"], 60000);
+    assert!(clock["black"].as_i64().unwrap() <= 59500, "{clock}");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn engine_crash_closes_the_game() {
+    let server = start_server().await;
+    let mut game = start_game(&server, "black", Some(AFTER_C4)).await;
+
+    let _ = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(recv(&mut game).await, None);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn monitor_follows_games() {
+    let server = start_server().await;
+    let mut monitor = connect(format!("{server}/games")).await;
+    assert_eq!(
+        recv(&mut monitor).await,
+        Some(json!({"_tag": "Init", "version": 0, "games": []}))
+    );
+
+    let mut game = start_game(&server, "white", None).await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    let _ = recv_tagged(&mut game, "EngineMove").await;
+    let _ = recv_tagged(&mut game, "Position").await;
+
+    // changes can be coalesced, only the last state is certain
+    let diff = recv_tagged(&mut monitor, "Diff").await;
+    let added = diff["added"].as_array().unwrap();
+    assert_eq!(added.len(), 1);
+    assert_eq!(added[0]["white"], "Mock Engine");
+    assert_eq!(added[0]["black"], "Human");
+    let id = added[0]["id"].as_str().unwrap().to_string();
+    let mut summary = added[0].clone();
+    while summary["fen"] != AFTER_E4 {
+        assert_eq!(summary["fen"], START);
+        let diff = recv_tagged(&mut monitor, "Diff").await;
+        assert_eq!(diff["added"], json!([]));
+        summary = diff["changed"][0].clone();
+        assert_eq!(summary["id"], id.as_str());
+        assert!(summary["version"].as_u64().unwrap() <= diff["version"].as_u64().unwrap());
+    }
+    assert_eq!(summary["moves"], 1);
+
+    let mut spectator = connect(format!("{server}/games/{id}")).await;
+    let record = recv_tagged(&mut spectator, "Game").await;
+    assert_eq!(record["game"]["startFen"], START);
+    assert_eq!(record["game"]["moves"][0]["san"], "e4");
+    assert_eq!(
+        record["game"]["moves"][0]["score"],
+        json!({"_tag": "CentiPawns", "score": 17})
+    );
+    let white = record["game"]["clock"]["white"].as_i64().unwrap();
+    assert!((50000..=60000).contains(&white), "{white}");
+
+    send_move(&mut game, pawn_move("E7", "E5")).await;
+    let played = recv_tagged(&mut spectator, "Move").await;
+    assert_eq!(played["ply"], 1);
+    assert_eq!(played["move"]["san"], "e5");
+    let played = recv_tagged(&mut spectator, "Move").await;
+    assert_eq!(played["ply"], 2);
+    assert_eq!(played["move"]["san"], "Nf3");
+    assert_eq!(played["move"]["fen"], AFTER_E4_E5_NF3);
+
+    game.close(None).await.unwrap();
+    let _ = recv_tagged(&mut spectator, "Gone").await;
+    loop {
+        let diff = recv_tagged(&mut monitor, "Diff").await;
+        if diff["removed"] == json!([id]) {
+            break;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn spectators_see_outcomes() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&variant=kingofthehill&fen={}",
+        "4k3/8/8/8/8/4K3/8/8 w - - 0 1".replace(' ', "%20")
+    ))
+    .await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+
+    let mut monitor = connect(format!("{server}/games")).await;
+    let init = recv_tagged(&mut monitor, "Init").await;
+    let id = init["games"][0]["id"].as_str().unwrap().to_string();
+    let mut spectator = connect(format!("{server}/games/{id}")).await;
+    let record = recv_tagged(&mut spectator, "Game").await;
+    assert_eq!(record["game"]["variant"], "kingofthehill");
+    assert_eq!(record["game"]["outcome"], Value::Null);
+
+    send_move(
+        &mut game,
+        json!({
+            "_tag": "Normal",
+            "role": "King",
+            "from": "E3",
+            "capture": null,
+            "to": "E4",
+            "promotion": null,
+        }),
+    )
+    .await;
+    assert_eq!(
+        recv_tagged(&mut spectator, "Move").await["move"]["san"],
+        "Ke4#"
+    );
+    let outcome = recv_tagged(&mut spectator, "Outcome").await;
+    assert_eq!(
+        outcome["outcome"],
+        json!({"outcome": "1-0", "reason": "king in the center"})
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unknown_games_are_refused() {
+    let server = start_server().await;
+    let mut spectator = connect(format!("{server}/games/nope")).await;
+    assert!(recv(&mut spectator).await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn transcripts_can_be_replayed() {
+    let server = start_server().await;
+    let mut game = start_game(&server, "white", None).await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    let _ = recv_tagged(&mut game, "EngineMove").await;
+    let _ = recv_tagged(&mut game, "Position").await;
+
+    let (status, _) = http_request(&server, "GET", "/admin/transcripts").await;
+    assert!(status.starts_with("HTTP/1.1 401"), "{status}");
+    let list: Value =
+        serde_json::from_str(&admin_get(&server, "/admin/transcripts").await).unwrap();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["engine"], "Mock Engine");
+    let id = list[0]["id"].as_str().unwrap();
+
+    let text = admin_get(&server, &format!("/admin/transcripts/{id}")).await;
+    let lines = Transcript::from_str(&text).unwrap().lines();
+    let sent: Vec<String> = lines
+        .iter()
+        .filter(|l| l.direction == Direction::Sent)
+        .map(|l| l.line.clone())
+        .collect();
+    let received: Vec<String> = lines
+        .iter()
+        .filter(|l| l.direction == Direction::Received)
+        .map(|l| l.line.clone())
+        .collect();
+    assert_eq!(sent[0], "uci");
+    assert_eq!(received.last().map(String::as_str), Some("bestmove e2e4"));
+
+    let path = std::env::temp_dir().join(format!("ucui-transcript-{id}.txt"));
+    std::fs::write(&path, &text).unwrap();
+    let mut mock = Command::new(env!("CARGO_BIN_EXE_ucui-mock-engine"))
+        .arg("--replay")
+        .arg(&path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = mock.stdin.take().unwrap();
+    for line in sent.iter() {
+        writeln!(stdin, "{line}").unwrap();
+    }
+    drop(stdin);
+    let mut output = String::new();
+    let _ = mock
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut output)
+        .unwrap();
+    let _ = mock.wait();
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(output.lines().collect::<Vec<_>>(), received);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chess960_castling() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&chess960=true&fen={}",
+        "r3k2r/8/8/8/8/8/8/1R3K1R w HBha - 0 1".replace(' ', "%20")
+    ))
+    .await;
+
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["chess960"], true);
+    assert_eq!(ready["fen"], "r3k2r/8/8/8/8/8/8/1R3K1R w KQkq - 0 1");
+    let castle = json!({"_tag": "Castle", "king": "F1", "rook": "H1"});
+    assert!(ready["legalMoves"].as_array().unwrap().contains(&castle));
+
+    send_move(&mut game, castle).await;
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(
+        engine_move["move"],
+        json!({"_tag": "Castle", "king": "E8", "rook": "A8"})
+    );
+    assert_eq!(engine_move["fen"], "2kr3r/8/8/8/8/8/8/1R3RK1 w - - 2 2");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chess960_start_position() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&chess960=true&chess960_position=0"
+    ))
+    .await;
+
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["chess960"], true);
+    assert_eq!(
+        ready["fen"],
+        "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn crazyhouse_pockets_and_drops() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&variant=crazyhouse&fen={}",
+        "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR[] w KQkq - 0 2".replace(' ', "%20")
+    ))
+    .await;
+
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["variant"], "crazyhouse");
+    assert_eq!(ready["pockets"]["white"]["pawn"], 0);
+    assert_eq!(ready["remainingChecks"], Value::Null);
+
+    send_move(
+        &mut game,
+        json!({
+            "_tag": "Normal",
+            "role": "Pawn",
+            "from": "E4",
+            "capture": "Pawn",
+            "to": "D5",
+            "promotion": null,
+        }),
+    )
+    .await;
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["move"]["from"], "D8");
+    assert_eq!(engine_move["pockets"]["white"]["pawn"], 1);
+    assert_eq!(engine_move["pockets"]["black"]["pawn"], 1);
+
+    let position = recv_tagged(&mut game, "Position").await;
+    let drop = json!({"_tag": "Put", "role": "Pawn", "to": "E4"});
+    assert!(position["legalMoves"].as_array().unwrap().contains(&drop));
+
+    send_move(&mut game, drop).await;
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["pockets"]["white"]["pawn"], 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn king_of_the_hill_outcome() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&variant=kingofthehill&fen={}",
+        "4k3/8/8/8/8/4K3/8/8 w - - 0 1".replace(' ', "%20")
+    ))
+    .await;
+
+    let _ = recv_tagged(&mut game, "Ready").await;
+    send_move(
+        &mut game,
+        json!({
+            "_tag": "Normal",
+            "role": "King",
+            "from": "E3",
+            "capture": null,
+            "to": "E4",
+            "promotion": null,
+        }),
+    )
+    .await;
+    let outcome = recv_tagged(&mut game, "Outcome").await;
+    assert_eq!(outcome["outcome"], "1-0");
+    assert_eq!(outcome["reason"], "king in the center");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn engine_gives_odds() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=white&white_time=60000&black_time=60000&handicap=pawn-and-move"
+    ))
+    .await;
+
+    // black moves first, the engine waits
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["handicap"], "pawn and move");
+    assert_eq!(
+        ready["fen"],
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPP1PP/RNBQKBNR b KQkq - 0 1"
+    );
+    assert_eq!(ready["turn"], "black");
+
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&handicap=remove:d8,h8"
+    ))
+    .await;
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(
+        ready["fen"],
+        "rnb1kbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQq - 0 1"
+    );
+}
+
+/// Opens a session as white and joins it as black.
+async fn start_session(server: &str, times: &str) -> (Socket, Socket) {
+    let mut host = connect(format!(
+        "{server}/engine?human=true&engine_color=black&{times}"
+    ))
+    .await;
+    let waiting = recv_tagged(&mut host, "Waiting").await;
+    let code = waiting["code"].as_str().unwrap();
+    let guest = connect(format!("{server}/engine?join={code}")).await;
+    (host, guest)
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn players_join_sessions() {
+    let server = start_server().await;
+    let (mut white, mut black) = start_session(&server, "white_time=60000&black_time=60000").await;
+
+    assert_eq!(recv_tagged(&mut white, "Joined").await["color"], "white");
+    assert_eq!(recv_tagged(&mut black, "Joined").await["color"], "black");
+    assert_eq!(recv_tagged(&mut white, "Ready").await["fen"], START);
+    let _ = recv_tagged(&mut black, "Ready").await;
+
+    // out of turn, refused
+    send_move(&mut black, pawn_move("E7", "E5")).await;
+    let error = recv_tagged(&mut black, "Error").await;
+    assert_eq!(error["code"], "OutOfTurn");
+
+    send_move(&mut white, pawn_move("E2", "E4")).await;
+    let engine_move = recv_tagged(&mut black, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E2", "E4"));
+    assert_eq!(engine_move["fen"], AFTER_E4);
+    let _ = recv_tagged(&mut black, "Position").await;
+
+    send_move(&mut black, pawn_move("E7", "E5")).await;
+    let engine_move = recv_tagged(&mut white, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E7", "E5"));
+
+    drop(white);
+    let outcome = recv_tagged(&mut black, "Outcome").await;
+    assert_eq!(outcome["outcome"], "0-1");
+    assert_eq!(outcome["reason"], "opponent left");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn server_runs_session_clocks() {
+    let server = start_server().await;
+    let (mut white, mut black) = start_session(&server, "white_time=300&black_time=60000").await;
+    let _ = recv_tagged(&mut white, "Joined").await;
+    let _ = recv_tagged(&mut white, "Ready").await;
+    let _ = recv_tagged(&mut black, "Joined").await;
+    let _ = recv_tagged(&mut black, "Ready").await;
+
+    let outcome = recv_tagged(&mut black, "Outcome").await;
+    assert_eq!(outcome["outcome"], "0-1");
+    assert_eq!(outcome["reason"], "time");
+    assert_eq!(recv_tagged(&mut white, "Outcome").await["reason"], "time");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn protocol_versions_are_checked() {
+    let server = start_server().await;
+    let url = format!("{server}/engine?engine_color=black&protocol={MIN_PROTOCOL_VERSION}");
+    let mut game = connect(url).await;
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["protocol"], PROTOCOL_VERSION);
+
+    let url = format!(
+        "{server}/engine?engine_color=black&protocol={}",
+        PROTOCOL_VERSION + 1
+    );
+    let mut game = connect(url).await;
+    let error = recv_tagged(&mut game, "Error").await;
+    assert_eq!(error["code"], "UnsupportedProtocol");
+    assert!(recv(&mut game).await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn unknown_join_codes_are_refused() {
+    let server = start_server().await;
+    let mut guest = connect(format!("{server}/engine?join=NOPE42")).await;
+    let error = recv_tagged(&mut guest, "Error").await;
+    assert_eq!(error["code"], "InvalidStart");
+    assert_eq!(error["message"], "no session NOPE42");
+    assert!(recv(&mut guest).await.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn finished_games_are_archived() {
+    let _ = start_server().await;
+    let dir = std::env::temp_dir().join(format!("ucui-archive-{}", std::process::id()));
+    let mut state = UcuiState::new();
+    state.archive = Archive::new(Some(dir.clone()));
+    let server = serve(state).await;
+
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&variant=kingofthehill&fen={}",
+        "4k3/8/8/8/8/4K3/8/8 w - - 0 1".replace(' ', "%20")
+    ))
+    .await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    send_move(
+        &mut game,
+        json!({
+            "_tag": "Normal",
+            "role": "King",
+            "from": "E3",
+            "capture": null,
+            "to": "E4",
+            "promotion": null,
+        }),
+    )
+    .await;
+    let _ = recv_tagged(&mut game, "Outcome").await;
+
+    let started = Instant::now();
+    let list = loop {
+        let list: Value = serde_json::from_str(&http_get(&server, "/archive").await).unwrap();
+        if !list.as_array().unwrap().is_empty() || started.elapsed() > TIMEOUT {
+            break list;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    assert_eq!(list.as_array().unwrap().len(), 1);
+    assert_eq!(list[0]["result"], "1-0");
+    assert_eq!(list[0]["engine"], "Mock Engine");
+    let id = list[0]["id"].as_str().unwrap().to_string();
+
+    let filtered = |query: &str| format!("/archive?{query}");
+    let none: Value =
+        serde_json::from_str(&http_get(&server, &filtered("result=0-1")).await).unwrap();
+    assert_eq!(none, json!([]));
+    let some: Value =
+        serde_json::from_str(&http_get(&server, &filtered("engine=mock")).await).unwrap();
+    assert_eq!(some.as_array().unwrap().len(), 1);
+
+    let archived: Value =
+        serde_json::from_str(&http_get(&server, &format!("/archive/{id}")).await).unwrap();
+    assert_eq!(archived["reason"], "king in the center");
+    assert_eq!(archived["moves"][0]["san"], "Ke4#");
+
+    let pgn = http_get(&server, &format!("/archive/{id}/pgn")).await;
+    assert!(pgn.contains("[Variant \"King of the Hill\"]\n"), "{pgn}");
+    assert!(
+        pgn.contains("[FEN \"4k3/8/8/8/8/4K3/8/8 w - - 0 1\"]\n"),
+        "{pgn}"
+    );
+    assert!(pgn.ends_with("\n1. Ke4# {[%clk 0:01:00]} 1-0\n"), "{pgn}");
+
+    let (status, _) = http_request(&server, "DELETE", &format!("/archive/{id}")).await;
+    assert!(status.starts_with("HTTP/1.1 401"), "{status}");
+    let auth = format!("Authorization: Bearer {ADMIN_TOKEN}\r\n");
+    let (status, _) =
+        http_send_with_headers(&server, "DELETE", &format!("/archive/{id}"), None, &auth).await;
+    assert!(status.starts_with("HTTP/1.1 204"), "{status}");
+    let (status, _) = http_request(&server, "GET", &format!("/archive/{id}")).await;
+    assert!(status.starts_with("HTTP/1.1 404"), "{status}");
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+/// Polls an annotation job until it is over.
+async fn annotation(server: &str, id: &str) -> Value {
+    let started = Instant::now();
+    loop {
+        let job: Value =
+            serde_json::from_str(&http_get(server, &format!("/annotate/{id}")).await).unwrap();
+        if !matches!(job["_tag"].as_str(), Some("Queued" | "Running")) {
+            return job;
+        }
+        assert!(
+            started.elapsed() < TIMEOUT,
+            "annotation should be done in time"
+        );
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn games_are_annotated() {
+    let server = start_server().await;
+    let pgn = "[White \"A\"]\n[Black \"B\"]\n[Result \"*\"]\n\n1. e4 f5 *\n";
+
+    let (status, body) = http_send(
+        &server,
+        "POST",
+        "/annotate",
+        Some(json!({ "pgn": pgn, "depth": 8 })),
+    )
+    .await;
+    assert!(status.starts_with("HTTP/1.1 202"), "{status}");
+    let id = serde_json::from_str::<Value>(&body).unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let job = annotation(&server, &id).await;
+    assert_eq!(job["_tag"], "Done", "{job}");
+    let moves = &job["game"]["moves"];
+    assert_eq!(
+        moves[0]["eval"],
+        json!({ "_tag": "CentiPawns", "score": 20 })
+    );
+    assert_eq!(moves[0]["bestMove"], Value::Null);
+    assert_eq!(moves[0]["classification"], Value::Null);
+    assert_eq!(
+        moves[1]["eval"],
+        json!({ "_tag": "CentiPawns", "score": 300 })
+    );
+    assert_eq!(moves[1]["bestMove"], "e5");
+    assert_eq!(moves[1]["loss"]["centipawns"], 280);
+    assert_eq!(moves[1]["classification"], "Mistake");
+
+    let annotated = http_get(&server, &format!("/annotate/{id}/pgn")).await;
+    assert!(annotated.contains("[Annotator \"ucui\"]\n"), "{annotated}");
+    assert!(
+        annotated
+            .ends_with("\n1. e4 {[%eval 0.20]} f5 $2 {[%eval 3.00] Mistake. e5 was best.} *\n"),
+        "{annotated}"
+    );
+
+    let (_, body) = http_send(
+        &server,
+        "POST",
+        "/annotate",
+        Some(json!({
+            "pgn": pgn,
+            "movetime": 100,
+            "thresholds": { "_tag": "WinChance", "inaccuracy": 5, "mistake": 10, "blunder": 15 },
+        })),
+    )
+    .await;
+    let id = serde_json::from_str::<Value>(&body).unwrap()["id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let job = annotation(&server, &id).await;
+    assert_eq!(
+        job["game"]["moves"][1]["classification"], "Blunder",
+        "{job}"
+    );
+
+    let (status, _) = http_send(
+        &server,
+        "POST",
+        "/annotate",
+        Some(json!({ "pgn": "1. e4 e4 *" })),
+    )
+    .await;
+    assert!(status.starts_with("HTTP/1.1 400"), "{status}");
+    let (status, _) = http_send(
+        &server,
+        "POST",
+        "/annotate",
+        Some(json!({ "archive": "0123abcd" })),
+    )
+    .await;
+    assert!(status.starts_with("HTTP/1.1 404"), "{status}");
+}
+
+async fn ask_hint(socket: &mut Socket, level: &str) -> Value {
+    let message = json!({ "_tag": "Hint", "level": level });
+    socket
+        .send(Message::text(message.to_string()))
+        .await
+        .unwrap();
+    recv_tagged(socket, "Hint").await
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn hints_are_given_and_counted() {
+    let server = start_server().await;
+    let mut game = start_game(&server, "black", None).await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    let hint = ask_hint(&mut game, "Piece").await;
+    assert_eq!(hint["role"], "Pawn");
+    assert_eq!(hint["from"], "E2");
+    assert_eq!(hint["to"], Value::Null);
+    assert_eq!(hint["move"], Value::Null);
+    assert_eq!(hint["score"], json!({ "_tag": "CentiPawns", "score": 17 }));
+    let hint = ask_hint(&mut game, "Move").await;
+    assert_eq!(hint["move"], pawn_move("E2", "E4"));
+    assert_eq!(hint["san"], "e4");
+    assert_eq!(hint["hints"], 2);
+    send_move(&mut game, pawn_move("E2", "E4")).await;
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E7", "E5"));
+
+    let dir = std::env::temp_dir().join(format!("ucui-hints-{}", std::process::id()));
+    let mut state = UcuiState::new();
+    state.archive = Archive::new(Some(dir.clone()));
+    let server = serve(state).await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&variant=kingofthehill&fen={}",
+        "4k3/8/8/8/8/4K3/8/8 w - - 0 1".replace(' ', "%20")
+    ))
+    .await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    let hint = ask_hint(&mut game, "Destination").await;
+    assert_eq!(hint["role"], Value::Null);
+    assert_eq!(hint["from"], Value::Null);
+    assert!(hint["to"].is_string(), "{hint}");
+    send_move(
+        &mut game,
+        json!({
+            "_tag": "Normal",
+            "role": "King",
+            "from": "E3",
+            "capture": null,
+            "to": "E4",
+            "promotion": null,
+        }),
+    )
+    .await;
+    let _ = recv_tagged(&mut game, "Outcome").await;
+
+    let started = Instant::now();
+    let list = loop {
+        let list: Value = serde_json::from_str(&http_get(&server, "/archive").await).unwrap();
+        if !list.as_array().unwrap().is_empty() || started.elapsed() > TIMEOUT {
+            break list;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    };
+    let id = list[0]["id"].as_str().unwrap().to_string();
+    let archived: Value =
+        serde_json::from_str(&http_get(&server, &format!("/archive/{id}")).await).unwrap();
+    assert_eq!(archived["hints"], 1);
+    let pgn = http_get(&server, &format!("/archive/{id}/pgn")).await;
+    assert!(pgn.contains("[Hints \"1\"]\n"), "{pgn}");
+
+    // a new server finds the transcript in the archive
+    let mut state = UcuiState::new();
+    state.archive = Archive::new(Some(dir.clone()));
+    let server = serve(state).await;
+    let transcript = admin_get(&server, &format!("/admin/transcripts/{id}")).await;
+    assert!(transcript.contains("bestmove"), "{transcript}");
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn failed_hints_leave_the_game_on() {
+    let server = start_server().await;
+    let mut game = start_game(
+        &server,
+        "black",
+        Some("rnbqkbnr/pppp1ppp/8/4p3/2P5/8/PP1PPPPP/RNBQKBNR w KQkq - 0 2"),
+    )
+    .await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    let message = json!({ "_tag": "Hint", "level": "Move" });
+    game.send(Message::text(message.to_string())).await.unwrap();
+    let error = recv_tagged(&mut game, "Error").await;
+    assert_eq!(error["code"], "NoHint");
+    send_move(&mut game, pawn_move("D2", "D4")).await;
+    let _ = recv_tagged(&mut game, "EngineMove").await;
+}
+
+/// The reason the socket was closed for, after an `Error` message
+/// telling the same.
+async fn close_reason(socket: &mut Socket) -> String {
+    let mut error = None;
+    loop {
+        let message = tokio::time::timeout(TIMEOUT, socket.next())
+            .await
+            .expect("server should answer in time");
+        match message {
+            Some(Ok(Message::Close(frame))) => {
+                let reason = frame.map(|f| f.reason.to_string()).unwrap_or_default();
+                assert_eq!(error, Some(Value::String(reason.clone())));
+                return reason;
+            }
+            Some(Ok(Message::Text(text))) => {
+                let message: Value = serde_json::from_str(text.as_str()).unwrap();
+                assert_eq!(message["_tag"], "Error", "unexpected message {text}");
+                assert_eq!(message["code"], "InvalidStart");
+                error = Some(message["message"].clone());
+            }
+            Some(Ok(_)) => {}
+            Some(Err(_)) | None => return String::new(),
+        }
+    }
+}
+
+fn query(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace(' ', "%20")
+        .replace('\n', "%0A")
+        .replace('"', "%22")
+        .replace('[', "%5B")
+        .replace(']', "%5D")
+        .replace('#', "%23")
+        .replace('+', "%2B")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn games_start_after_given_moves() {
+    let server = start_server().await;
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=60000&black_time=60000&moves=e2e4"
+    ))
+    .await;
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(ready["fen"], AFTER_E4);
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E7", "E5"));
+
+    let pgn = "[Event \"Club\"]\n\n1. e4 e5 *";
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=white&white_time=60000&black_time=60000&pgn={}",
+        query(pgn)
+    ))
+    .await;
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(
+        ready["fen"],
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+    );
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["fen"], AFTER_E4_E5_NF3);
+
+    let mut monitor = connect(format!("{server}/games")).await;
+    let init = recv_tagged(&mut monitor, "Init").await;
+    let id = init["games"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|g| g["fen"] == AFTER_E4_E5_NF3)
+        .map(|g| g["id"].as_str().unwrap().to_string())
+        .expect("game should be monitored");
+    let mut follow = connect(format!("{server}/games/{id}")).await;
+    let record = recv_tagged(&mut follow, "Game").await;
+    assert_eq!(record["game"]["startFen"], START);
+    let sans: Vec<&str> = record["game"]["moves"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["san"].as_str().unwrap())
+        .collect();
+    assert_eq!(sans, ["e4", "e5", "Nf3"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn games_start_from_an_opening() {
+    let server = start_server().await;
+    // C20 is 1.e4 e5
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=white&white_time=60000&black_time=60000&opening=C20"
+    ))
+    .await;
+    let ready = recv_tagged(&mut game, "Ready").await;
+    assert_eq!(
+        ready["fen"],
+        "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2"
+    );
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["fen"], AFTER_E4_E5_NF3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn invalid_starts_are_refused() {
+    let server = start_server().await;
+    let refused = |query: String| {
+        let server = server.clone();
+        async move {
+            let mut game = connect(format!(
+                "{server}/engine?engine_color=black&white_time=60000&black_time=60000&{query}"
+            ))
+            .await;
+            close_reason(&mut game).await
+        }
+    };
+    assert!(refused("fen=nonsense".into())
+        .await
+        .starts_with("invalid position"));
+    assert_eq!(
+        refused("moves=e2e4,e2e4".into()).await,
+        "ply 2: illegal move 'e2e4'"
+    );
+    assert_eq!(
+        refused("moves=g1f3 g8f6 f3g1 f6g8 g1f3 g8f6 f3g1 f6g8".replace(' ', "%20")).await,
+        "the game is already over by threefold repetition"
+    );
+    assert!(refused(format!("pgn={}", query("1. f3 e5 2. g4 Qh4#")))
+        .await
+        .starts_with("the game is already over by checkmate"));
+    assert!(refused(format!("pgn={}", query("1. e4 e4")))
+        .await
+        .starts_with("invalid PGN"));
+    assert!(refused("handicap=nonsense".into())
+        .await
+        .starts_with("invalid handicap"));
+    assert_eq!(
+        refused("opening=nonsense".into()).await,
+        "unknown opening 'nonsense'"
+    );
+    assert!(refused("opening=C20&chess960=true".into())
+        .await
+        .ends_with("only starts from the standard start position"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn repertoires_are_trained() {
+    let server = start_server().await;
+    let (status, body) = http_send(
+        &server,
+        "POST",
+        "/repertoire",
+        Some(json!({
+            "pgn": "[Event \"Open games\"]\n\n1. e4 e5 2. Nf3 (2. Bc4) *",
+            "color": "white",
+        })),
+    )
+    .await;
+    assert!(status.starts_with("HTTP/1.1 201"), "{status}");
+    let repertoire: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(repertoire["name"], "Open games");
+    assert_eq!(repertoire["lines"][1]["movetext"], "1. e4 e5 2. Nf3");
+    let id = repertoire["id"].as_str().unwrap().to_string();
+
+    let training = |choice: &str| {
+        let url = format!(
+            "{server}/engine?engine_color=black&white_time=60000&black_time=60000&repertoire={id}&choice={choice}"
+        );
+        async move { connect(url).await }
+    };
+    let mut game = training("Random").await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    send_move(&mut game, pawn_move("E2", "E4")).await;
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E7", "E5"));
+    assert_eq!(engine_move["score"]["_tag"], "None");
+    let _ = recv_tagged(&mut game, "Position").await;
+    send_move(
+        &mut game,
+        json!({
+            "_tag": "Normal",
+            "role": "Knight",
+            "from": "G1",
+            "capture": null,
+            "to": "F3",
+            "promotion": null,
+        }),
+    )
+    .await;
+    let completed = recv_tagged(&mut game, "LineCompleted").await;
+    assert_eq!(completed["line"], 1);
+
+    let mut game = training("Weighted").await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+    send_move(&mut game, pawn_move("D2", "D4")).await;
+    let deviation = recv_tagged(&mut game, "Deviation").await;
+    assert_eq!(deviation["played"], "d4");
+    assert_eq!(deviation["expected"], json!(["e4"]));
+    // out of the repertoire, the engine plays on
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["fen"], AFTER_D4_D5);
+
+    let repertoire: Value =
+        serde_json::from_str(&http_get(&server, &format!("/repertoire/{id}")).await).unwrap();
+    let lines = repertoire["lines"].as_array().unwrap();
+    assert_eq!(lines[1]["stats"]["completed"], 1);
+    let deviations: u64 = lines
+        .iter()
+        .map(|line| line["stats"]["deviations"].as_u64().unwrap())
+        .sum();
+    assert_eq!(deviations, 1);
+
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=white&white_time=60000&black_time=60000&repertoire={id}"
+    ))
+    .await;
+    assert_eq!(
+        close_reason(&mut game).await,
+        format!("repertoire '{id}' is prepared for white")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn client_errors_are_reported() {
+    let server = start_server().await;
+    let mut game = start_game(&server, "black", None).await;
+    let _ = recv_tagged(&mut game, "Ready").await;
+
+    game.send(Message::text("hello")).await.unwrap();
+    let error = recv_tagged(&mut game, "Error").await;
+    assert_eq!(error["code"], "InvalidMessage");
+    assert_eq!(error["context"], "hello");
+
+    send_move(&mut game, pawn_move("E2", "E5")).await;
+    let error = recv_tagged(&mut game, "Error").await;
+    assert_eq!(error["code"], "IllegalMove");
+    assert_eq!(error["context"], "e2e5");
+    let position = recv_tagged(&mut game, "Position").await;
+    assert_eq!(position["fen"], START);
+
+    let message = json!({
+        "_tag": "Move",
+        "move": pawn_move("E2", "E4"),
+        "white_time": -5,
+        "black_time": 60000,
+    });
+    game.send(Message::text(message.to_string())).await.unwrap();
+    let error = recv_tagged(&mut game, "Error").await;
+    assert_eq!(error["code"], "InvalidTime");
+
+    // the game goes on
+    send_move(&mut game, pawn_move("E2", "E4")).await;
+    let engine_move = recv_tagged(&mut game, "EngineMove").await;
+    assert_eq!(engine_move["move"], pawn_move("E7", "E5"));
+
+    let mut game = connect(format!(
+        "{server}/engine?engine_color=black&white_time=-1000&black_time=60000"
+    ))
+    .await;
+    assert_eq!(
+        close_reason(&mut game).await,
+        "clock times should not be negative"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn legal_moves_are_described() {
+    let server = start_server().await;
+    let legals: Value =
+        serde_json::from_str(&http_get(&server, &format!("/legals?fen={}", query(START))).await)
+            .unwrap();
+    let legals = legals.as_array().unwrap();
+    assert_eq!(legals.len(), 20);
+    assert_eq!(legals[0]["_tag"], "Normal");
+
+    let before_mate = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+    let path = format!(
+        "/legals?fen={}&with=san,uci,fen,flags&group=true",
+        query(before_mate)
+    );
+    let groups: Value = serde_json::from_str(&http_get(&server, &path).await).unwrap();
+    let roles: Vec<&Value> = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|group| &group["role"])
+        .collect();
+    assert_eq!(roles, ["Pawn", "Bishop", "Knight", "Queen", "King"]);
+    let queen = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|group| group["role"] == "Queen")
+        .unwrap();
+    let mate = queen["moves"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["uci"] == "h5f7")
+        .unwrap();
+    assert_eq!(mate["san"], "Qxf7#");
+    assert_eq!(
+        mate["fen"],
+        "r1bqkb1r/pppp1Qpp/2n2n2/4p3/2B1P3/8/PPPP1PPP/RNB1K1NR b KQkq - 0 4"
+    );
+    assert_eq!(
+        mate["flags"],
+        json!({"capture": true, "check": true, "checkmate": true, "promotion": false})
+    );
+
+    let (status, _) = http_request(&server, "GET", "/legals?fen=nonsense").await;
+    assert!(status.starts_with("HTTP/1.1 400"), "{status}");
+    let no_kings = format!("/legals?fen={}", query("8/8/8/8/8/8/8/8 w - - 0 1"));
+    let (status, body) = http_request(&server, "GET", &no_kings).await;
+    assert!(status.starts_with("HTTP/1.1 422"), "{status}");
+    assert!(body.starts_with("impossible position"), "{body}");
+    let (status, _) = http_request(&server, "GET", &format!("{no_kings}&with=lan")).await;
+    assert!(status.starts_with("HTTP/1.1 400"), "{status}");
+}
+
+#[tokio::test]
+async fn moves_are_converted() {
+    let server = start_server().await;
+    let path = format!("/convert?moves={}", query("1. e4 e5 2. Ng1-f3 b8c6"));
+    let conversion: Value = serde_json::from_str(&http_get(&server, &path).await).unwrap();
+    assert_eq!(conversion["fen"], START);
+    assert_eq!(conversion["movetext"], "1. e4 e5 2. Nf3 Nc6");
+    let knight = &conversion["moves"][2];
+    assert_eq!(
+        *knight,
+        json!({
+            "san": "Nf3",
+            "uci": "g1f3",
+            "lan": "Ng1-f3",
+            "figurine": "♘f3",
+            "fen": AFTER_E4_E5_NF3,
+        })
+    );
+
+    let after_e4 = format!("/convert?fen={}&moves=c5", query(AFTER_E4));
+    let conversion: Value = serde_json::from_str(&http_get(&server, &after_e4).await).unwrap();
+    assert_eq!(conversion["movetext"], "1... c5");
+
+    let (status, body) = http_request(&server, "GET", "/convert?moves=e4%20e4").await;
+    assert!(status.starts_with("HTTP/1.1 400"), "{status}");
+    assert_eq!(body, "invalid move 'e4' at ply 2");
+}
+
+#[tokio::test]
+async fn positions_are_validated() {
+    let server = start_server().await;
+    let validate = |fen: &str| format!("/position/validate?fen={}", query(fen));
+
+    let open_game = "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+    let check: Value =
+        serde_json::from_str(&http_get(&server, &validate(open_game)).await).unwrap();
+    assert_eq!(check["valid"], true);
+    assert_eq!(check["errors"], json!([]));
+    assert_eq!(check["fen"], open_game);
+    assert_eq!(check["turn"], "white");
+    assert_eq!(check["outcome"], Value::Null);
+    assert_eq!(check["eco"]["code"], "C20");
+
+    let no_white_king = "4k3/8/8/8/8/8/8/8 w - - 0 1";
+    let check: Value =
+        serde_json::from_str(&http_get(&server, &validate(no_white_king)).await).unwrap();
+    assert_eq!(check["valid"], false);
+    assert_eq!(check["errors"][0]["code"], "MissingKing");
+    assert_eq!(check["turn"], "white");
+    assert_eq!(check["fen"], Value::Null);
+
+    let unreadable = "rnbqkbnr/ppppxppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    let check: Value =
+        serde_json::from_str(&http_get(&server, &validate(unreadable)).await).unwrap();
+    assert_eq!(check["valid"], false);
+    assert_eq!(check["errors"][0]["code"], "InvalidBoard");
+    assert_eq!(check["turn"], Value::Null);
+
+    let fools_mate = "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3";
+    let check: Value =
+        serde_json::from_str(&http_get(&server, &validate(fools_mate)).await).unwrap();
+    assert_eq!(check["valid"], true);
+    assert_eq!(
+        check["outcome"],
+        json!({"outcome": "0-1", "reason": "checkmate"})
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn chess960_castling_rights_are_reported() {
+    let server = start_server().await;
+    let validate = |fen: &str| format!("/position/validate?fen={}", query(fen));
+
+    let check: Value = serde_json::from_str(&http_get(&server, &validate(START)).await).unwrap();
+    assert_eq!(check["chess960"], false);
+
+    let king_on_f1 = "r3k2r/8/8/8/8/8/8/1R3K1R w HBha - 0 1";
+    let check: Value =
+        serde_json::from_str(&http_get(&server, &validate(king_on_f1)).await).unwrap();
+    assert_eq!(check["valid"], true);
+    assert_eq!(check["chess960"], true);
+}
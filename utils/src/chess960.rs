@@ -0,0 +1,164 @@
+//! Chess960, aka Fischer Random Chess
+//!
+//! Start positions are numbered from 0 to 959 following Scharnagl,
+//! the standard start position being 518.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    str::FromStr,
+};
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, FromSetup, Role};
+
+pub const POSITIONS: u32 = 960;
+pub const STANDARD_POSITION: u32 = 518;
+
+/// Knight placements among the five squares left after the
+/// bishops and the queen.
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+fn nth_empty(rank: &[Option<Role>; 8], n: usize) -> usize {
+    rank.iter()
+        .enumerate()
+        .filter(|(_, role)| role.is_none())
+        .nth(n)
+        .map(|(file, _)| file)
+        .expect("enough empty squares on the back rank")
+}
+
+/// Pieces on the back rank of start position `number`, from the a-file.
+pub fn back_rank(number: u32) -> Option<[Role; 8]> {
+    if number >= POSITIONS {
+        return None;
+    }
+    let mut rank: [Option<Role>; 8] = [None; 8];
+    let mut n = number as usize;
+
+    rank[(n % 4) * 2 + 1] = Some(Role::Bishop);
+    n /= 4;
+    rank[(n % 4) * 2] = Some(Role::Bishop);
+    n /= 4;
+    let queen = nth_empty(&rank, n % 6);
+    rank[queen] = Some(Role::Queen);
+    n /= 6;
+    let (first, second) = KNIGHTS[n];
+    let (first, second) = (nth_empty(&rank, first), nth_empty(&rank, second));
+    rank[first] = Some(Role::Knight);
+    rank[second] = Some(Role::Knight);
+    for role in [Role::Rook, Role::King, Role::Rook] {
+        let file = nth_empty(&rank, 0);
+        rank[file] = Some(role);
+    }
+
+    Some(rank.map(|role| role.expect("a full back rank")))
+}
+
+/// Start position `number` as a X-FEN.
+pub fn start_fen(number: u32) -> Option<String> {
+    let rank: String = back_rank(number)?.iter().map(|role| role.char()).collect();
+    Some(format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        rank,
+        rank.to_uppercase()
+    ))
+}
+
+pub fn start_position(number: u32) -> Option<Chess> {
+    let fen = Fen::from_str(&start_fen(number)?).ok()?;
+    Chess::from_setup(fen.into_setup(), CastlingMode::Chess960).ok()
+}
+
+pub fn random_number() -> u32 {
+    (RandomState::new().build_hasher().finish() % POSITIONS as u64) as u32
+}
+
+/// Parses a FEN, X-FEN or Shredder-FEN position. Castling rights
+/// only possible in Chess960 switch to Chess960 even if not asked to.
+pub fn parse_position(fen: &str, chess960: bool) -> Result<(Chess, CastlingMode), String> {
+    let setup = Fen::from_str(fen.trim())
+        .map_err(|err| err.to_string())?
+        .into_setup();
+    let mode = CastlingMode::from_chess960(chess960);
+    match Chess::from_setup(setup.clone(), mode) {
+        Ok(game) => Ok((game, mode)),
+        Err(err) if mode == CastlingMode::Standard => {
+            Chess::from_setup(setup, CastlingMode::Chess960)
+                .map(|game| (game, CastlingMode::Chess960))
+                .map_err(|_| err.to_string())
+        }
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{CastlingSide, Color, EnPassantMode, Position};
+    use std::collections::HashSet;
+
+    #[test]
+    fn standard_position_is_518() {
+        let game = start_position(STANDARD_POSITION).unwrap();
+        assert_eq!(
+            Fen::from_position(game, EnPassantMode::Legal).to_string(),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn all_positions_are_distinct() {
+        let ranks: HashSet<String> = (0..POSITIONS)
+            .map(|n| start_fen(n).expect("a valid number"))
+            .collect();
+        assert_eq!(ranks.len(), POSITIONS as usize);
+        assert!((0..POSITIONS).all(|n| start_position(n).is_some()));
+        assert!(start_fen(POSITIONS).is_none());
+    }
+
+    #[test]
+    fn known_positions() {
+        assert_eq!(
+            back_rank(0).unwrap().map(|r| r.char()),
+            ['b', 'b', 'q', 'n', 'n', 'r', 'k', 'r']
+        );
+        assert_eq!(
+            back_rank(959).unwrap().map(|r| r.char()),
+            ['r', 'k', 'r', 'n', 'n', 'q', 'b', 'b']
+        );
+    }
+
+    #[test]
+    fn parses_shredder_fen() {
+        let (game, mode) = parse_position(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            false,
+        )
+        .unwrap();
+        assert_eq!(mode, CastlingMode::Chess960);
+        assert_eq!(game.turn(), Color::White);
+        assert!(game.castles().has(Color::White, CastlingSide::KingSide));
+        assert!(game.castles().has(Color::Black, CastlingSide::QueenSide));
+    }
+
+    #[test]
+    fn standard_fen_stays_standard() {
+        let (_, mode) = parse_position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            false,
+        )
+        .unwrap();
+        assert_eq!(mode, CastlingMode::Standard);
+    }
+}
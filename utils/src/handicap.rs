@@ -0,0 +1,166 @@
+//! Odds games, one side starting without some of its material
+//!
+//! Presets take material from the side giving the odds: `pawn-and-move`
+//! (the f-pawn, and the other side moves first), `knight` (the queen's
+//! knight), `rook` (the queen's rook) and `queen`. `remove:a1,b1` takes
+//! away whatever stands on the given squares, of either side.
+
+use std::{fmt::Display, str::FromStr};
+
+use shakmaty::{
+    Bitboard, CastlingMode, Chess, Color, EnPassantMode, File, FromSetup, Position, Rank, Role,
+    Square,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Handicap {
+    PawnAndMove,
+    Knight,
+    Rook,
+    Queen,
+    Squares(Vec<Square>),
+}
+
+pub const PRESETS: [Handicap; 4] = [
+    Handicap::PawnAndMove,
+    Handicap::Knight,
+    Handicap::Rook,
+    Handicap::Queen,
+];
+
+impl Handicap {
+    /// Squares emptied when `giver` gives the odds.
+    fn squares(&self, giver: Color) -> Vec<Square> {
+        let back_rank = giver.fold_wb(Rank::First, Rank::Eighth);
+        match self {
+            Handicap::PawnAndMove => vec![Square::from_coords(
+                File::F,
+                giver.fold_wb(Rank::Second, Rank::Seventh),
+            )],
+            Handicap::Knight => vec![Square::from_coords(File::B, back_rank)],
+            Handicap::Rook => vec![Square::from_coords(File::A, back_rank)],
+            Handicap::Queen => vec![Square::from_coords(File::D, back_rank)],
+            Handicap::Squares(squares) => squares.clone(),
+        }
+    }
+
+    /// In words, e.g. for a PGN header.
+    pub fn description(&self) -> String {
+        match self {
+            Handicap::PawnAndMove => "pawn and move".into(),
+            Handicap::Knight => "knight odds".into(),
+            Handicap::Rook => "rook odds".into(),
+            Handicap::Queen => "queen odds".into(),
+            Handicap::Squares(squares) => {
+                let squares: Vec<String> = squares.iter().map(Square::to_string).collect();
+                format!("odds of {}", squares.join(", "))
+            }
+        }
+    }
+
+    /// The standard start position without the material given by `giver`.
+    pub fn start_position(&self, giver: Color) -> Result<Chess, String> {
+        let mut setup = Chess::default().into_setup(EnPassantMode::Legal);
+        for square in self.squares(giver) {
+            match setup.board.remove_piece_at(square) {
+                Some(piece) if piece.role == Role::King => {
+                    return Err(format!("the king on {square} cannot be given"))
+                }
+                Some(_) => setup.castling_rights &= !Bitboard::from(square),
+                None => return Err(format!("no piece on {square}")),
+            }
+        }
+        if *self == Handicap::PawnAndMove {
+            setup.turn = giver.other();
+        }
+        Chess::from_setup(setup, CastlingMode::Standard).map_err(|err| err.to_string())
+    }
+}
+
+impl Display for Handicap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Handicap::PawnAndMove => f.write_str("pawn-and-move"),
+            Handicap::Knight => f.write_str("knight"),
+            Handicap::Rook => f.write_str("rook"),
+            Handicap::Queen => f.write_str("queen"),
+            Handicap::Squares(squares) => {
+                let squares: Vec<String> = squares.iter().map(Square::to_string).collect();
+                write!(f, "remove:{}", squares.join(","))
+            }
+        }
+    }
+}
+
+impl FromStr for Handicap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(squares) = s.strip_prefix("remove:") {
+            return squares
+                .split(',')
+                .map(|square| {
+                    Square::from_str(square.trim())
+                        .map_err(|_| format!("'{square}' is not a square"))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(Handicap::Squares);
+        }
+        PRESETS
+            .iter()
+            .find(|handicap| handicap.to_string() == s)
+            .cloned()
+            .ok_or_else(|| {
+                let names: Vec<String> = PRESETS.iter().map(Handicap::to_string).collect();
+                format!(
+                    "unknown handicap '{s}', expected one of {} or remove:SQUARES",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::fen::Fen;
+
+    fn fen(game: Chess) -> String {
+        Fen::from_position(game, EnPassantMode::Legal).to_string()
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(
+            fen(Handicap::Knight.start_position(Color::Black).unwrap()),
+            "r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+        );
+        assert_eq!(
+            fen(Handicap::Rook.start_position(Color::White).unwrap()),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w Kkq - 0 1"
+        );
+        assert_eq!(
+            fen(Handicap::PawnAndMove.start_position(Color::White).unwrap()),
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPP1PP/RNBQKBNR b KQkq - 0 1"
+        );
+    }
+
+    #[test]
+    fn removes_squares() {
+        let handicap = Handicap::from_str("remove:a8,h8").unwrap();
+        assert_eq!(handicap.to_string(), "remove:a8,h8");
+        assert_eq!(
+            fen(handicap.start_position(Color::Black).unwrap()),
+            "1nbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1"
+        );
+        assert!(Handicap::from_str("remove:e1")
+            .unwrap()
+            .start_position(Color::White)
+            .is_err());
+        assert!(Handicap::from_str("remove:e4")
+            .unwrap()
+            .start_position(Color::White)
+            .is_err());
+        assert!(Handicap::from_str("bishop").is_err());
+    }
+}
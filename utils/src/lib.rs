@@ -1,3 +1,21 @@
+pub mod chess960;
+pub mod handicap;
+pub mod notation;
+pub mod pgn;
 pub mod serde;
+pub mod variant;
 
 pub use serde::*;
+
+use shakmaty::Role;
+
+/// Piece roles, in the order clients list them, e.g. in the terminal
+/// client's move picker.
+pub const ROLE_LIST: [Role; 6] = [
+    Role::Pawn,
+    Role::Bishop,
+    Role::Knight,
+    Role::Rook,
+    Role::Queen,
+    Role::King,
+];
@@ -0,0 +1,287 @@
+//! Move notations
+//!
+//! SAN with check and mate markers ("Nf3", "exd5+"), UCI ("g1f3"), LAN,
+//! the long algebraic notation ("Ng1-f3", "e4xd5+"), and figurine, SAN
+//! with piece symbols ("♘f3"). Moves are read in any of them, one at a
+//! time or as PGN movetext, and written in all of them.
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    san::{SanPlus, Suffix},
+    uci::UciMove,
+    variant::VariantPosition,
+    Color, Move, Position, Role,
+};
+use ts_rs::TS;
+
+use crate::{pgn::main_line, variant::fen};
+
+/// Letters and symbols of the white and black pieces.
+const FIGURINES: [(char, char, char); 6] = [
+    ('K', '♔', '♚'),
+    ('Q', '♕', '♛'),
+    ('R', '♖', '♜'),
+    ('B', '♗', '♝'),
+    ('N', '♘', '♞'),
+    ('P', '♙', '♟'),
+];
+
+/// A move in every notation, and the position it leads to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, TS)]
+pub struct Ply {
+    pub san: String,
+    pub uci: String,
+    pub lan: String,
+    pub figurine: String,
+    /// The position after the move.
+    pub fen: String,
+}
+
+/// "+" when the side to move in `after` is in check, "#" when the game
+/// is won, by mate or by the rules of the variant, as SAN has it.
+pub fn suffix<P: Position>(after: &P) -> &'static str {
+    match Suffix::from_position(after) {
+        Some(Suffix::Checkmate) => "#",
+        Some(Suffix::Check) => "+",
+        None => "",
+    }
+}
+
+pub fn san<P: Position + Clone>(game: &P, m: &Move) -> String {
+    SanPlus::from_move(game.clone(), m).to_string()
+}
+
+pub fn uci<P: Position>(game: &P, m: &Move) -> String {
+    m.to_uci(game.castles().mode()).to_string()
+}
+
+pub fn lan<P: Position + Clone>(game: &P, m: &Move) -> String {
+    let text = match m {
+        Move::Normal {
+            role,
+            from,
+            capture,
+            to,
+            promotion,
+        } => {
+            let piece = match role {
+                Role::Pawn => String::new(),
+                role => role.upper_char().to_string(),
+            };
+            let separator = if capture.is_some() { 'x' } else { '-' };
+            let promotion = promotion
+                .map(|role| format!("={}", role.upper_char()))
+                .unwrap_or_default();
+            format!("{piece}{from}{separator}{to}{promotion}")
+        }
+        Move::EnPassant { from, to } => format!("{from}x{to}"),
+        Move::Castle { king, rook } if rook.file() < king.file() => String::from("O-O-O"),
+        Move::Castle { .. } => String::from("O-O"),
+        Move::Put { role, to } => format!("{}@{to}", role.upper_char()),
+    };
+    let mut after = game.clone();
+    after.play_unchecked(m);
+    format!("{text}{}", suffix(&after))
+}
+
+/// SAN with the symbols of the pieces of the side to move.
+pub fn figurine<P: Position + Clone>(game: &P, m: &Move) -> String {
+    san(game, m)
+        .chars()
+        .map(|c| {
+            FIGURINES
+                .iter()
+                .find(|(letter, _, _)| *letter == c)
+                .map(|(_, white, black)| match game.turn() {
+                    Color::White => *white,
+                    Color::Black => *black,
+                })
+                .unwrap_or(c)
+        })
+        .collect()
+}
+
+/// The UCI of a LAN move, and the role it names.
+fn lan_to_uci(lan: &str) -> Option<(String, Role)> {
+    let lan = lan.trim_end_matches(['+', '#']);
+    let piece = lan.chars().next().filter(char::is_ascii_uppercase);
+    let (role, squares) = match piece.and_then(Role::from_char) {
+        Some(role) if role != Role::Pawn => (role, &lan[1..]),
+        _ => (Role::Pawn, lan),
+    };
+    let from = squares.get(..2)?;
+    let rest = squares.get(2..)?.strip_prefix(['-', 'x'])?;
+    let to = rest.get(..2)?;
+    let promotion = rest.get(2..)?.trim_start_matches('=').to_lowercase();
+    Some((format!("{from}{to}{promotion}"), role))
+}
+
+/// A move in SAN, UCI, LAN or figurine notation.
+pub fn parse_move<P: Position>(game: &P, text: &str) -> Result<Move, String> {
+    let letters: String = text
+        .trim()
+        .chars()
+        .map(|c| {
+            FIGURINES
+                .iter()
+                .find(|(_, white, black)| *white == c || *black == c)
+                .map(|(letter, _, _)| *letter)
+                .unwrap_or(c)
+        })
+        .collect();
+    let from_uci = |uci: &str| {
+        UciMove::from_str(uci)
+            .ok()
+            .and_then(|uci| uci.to_move(game).ok())
+    };
+    from_uci(&letters)
+        .or_else(|| {
+            SanPlus::from_str(&letters)
+                .ok()
+                .and_then(|san| san.san.to_move(game).ok())
+        })
+        .or_else(|| {
+            let (uci, role) = lan_to_uci(&letters)?;
+            from_uci(&uci).filter(|m| m.role() == role)
+        })
+        .ok_or_else(|| format!("invalid move '{text}'"))
+}
+
+/// The moves of a list or of PGN movetext, in any notation, played
+/// from `start`.
+pub fn parse_moves(start: &VariantPosition, text: &str) -> Result<Vec<Move>, String> {
+    let mut game = start.clone();
+    let mut moves = Vec::new();
+    for (ply, token) in main_line(text).iter().enumerate() {
+        let m = parse_move(&game, token).map_err(|err| format!("{err} at ply {}", ply + 1))?;
+        game.play_unchecked(&m);
+        moves.push(m);
+    }
+    Ok(moves)
+}
+
+pub fn plies(start: &VariantPosition, moves: &[Move]) -> Vec<Ply> {
+    let mut game = start.clone();
+    moves
+        .iter()
+        .map(|m| {
+            let ply = Ply {
+                san: san(&game, m),
+                uci: uci(&game, m),
+                lan: lan(&game, m),
+                figurine: figurine(&game, m),
+                fen: String::new(),
+            };
+            game.play_unchecked(m);
+            Ply {
+                fen: fen(&game),
+                ..ply
+            }
+        })
+        .collect()
+}
+
+/// PGN movetext of `moves` from `start`, without a result.
+pub fn movetext(start: &VariantPosition, moves: &[Move]) -> String {
+    annotated_movetext(start, moves, |_, _| Vec::new())
+}
+
+/// PGN movetext of `moves` from `start`, each move followed by what
+/// `annotate` gives for it, e.g. NAGs and comments. `annotate` is called
+/// with the index of the move and the position it is played from.
+pub fn annotated_movetext<F>(start: &VariantPosition, moves: &[Move], mut annotate: F) -> String
+where
+    F: FnMut(usize, &VariantPosition) -> Vec<String>,
+{
+    let mut game = start.clone();
+    let mut text = Vec::new();
+    for (i, m) in moves.iter().enumerate() {
+        if game.turn() == Color::White {
+            text.push(format!("{}.", game.fullmoves()));
+        } else if i == 0 {
+            text.push(format!("{}...", game.fullmoves()));
+        }
+        text.push(san(&game, m));
+        text.extend(annotate(i, &game));
+        game.play_unchecked(m);
+    }
+    text.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variant::parse_position;
+    use shakmaty::variant::Variant;
+
+    #[test]
+    fn reads_any_notation() {
+        let start = VariantPosition::new(Variant::Chess);
+        let moves = parse_moves(&start, "1. e4 e7e5 2. Ng1-f3 ♞c6 3. Bb5").unwrap();
+        let converted = plies(&start, &moves);
+        let sans: Vec<&str> = converted.iter().map(|ply| ply.san.as_str()).collect();
+        assert_eq!(sans, ["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(converted[2].lan, "Ng1-f3");
+        assert_eq!(converted[3].figurine, "♞c6");
+        assert_eq!(converted[4].uci, "f1b5");
+        assert_eq!(
+            converted[4].fen,
+            "r1bqkbnr/pppp1ppp/2n5/1B2p3/4P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3"
+        );
+        assert_eq!(movetext(&start, &moves), "1. e4 e5 2. Nf3 Nc6 3. Bb5");
+        assert_eq!(
+            parse_moves(&start, "e4 e5 Ke2-e4").unwrap_err(),
+            "invalid move 'Ke2-e4' at ply 3"
+        );
+        assert!(parse_moves(&start, "Ng1-h3 Nb8-c6 Bf1-g2").is_err());
+    }
+
+    #[test]
+    fn writes_captures_checks_and_castling() {
+        let (game, _) = parse_position(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+            Variant::Chess,
+            false,
+        )
+        .unwrap();
+        let mate = parse_move(&game, "h5f7").unwrap();
+        assert_eq!(san(&game, &mate), "Qxf7#");
+        assert_eq!(lan(&game, &mate), "Qh5xf7#");
+        assert_eq!(figurine(&game, &mate), "♕xf7#");
+
+        let (game, _) =
+            parse_position("5k2/8/8/8/8/8/8/4K2R w K - 0 1", Variant::Chess, false).unwrap();
+        let castle = parse_move(&game, "O-O").unwrap();
+        assert_eq!(lan(&game, &castle), "O-O+");
+        assert_eq!(uci(&game, &castle), "e1g1");
+        assert_eq!(movetext(&game, &[]), "");
+
+        let (game, _) = parse_position(
+            "4k3/8/8/8/8/4K3/8/8 w - - 0 1",
+            Variant::KingOfTheHill,
+            false,
+        )
+        .unwrap();
+        let center = parse_move(&game, "Ke4").unwrap();
+        assert_eq!(san(&game, &center), "Ke4#");
+        assert_eq!(lan(&game, &center), "Ke3-e4#");
+    }
+
+    #[test]
+    fn numbers_from_black() {
+        let (game, _) = parse_position(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            Variant::Chess,
+            false,
+        )
+        .unwrap();
+        let moves = parse_moves(&game, "c5 Nf3").unwrap();
+        assert_eq!(movetext(&game, &moves), "1... c5 2. Nf3");
+        let annotated = annotated_movetext(&game, &moves, |i, position| {
+            vec![format!("{{{i} {:?}}}", position.turn())]
+        });
+        assert_eq!(annotated, "1... c5 {0 Black} 2. Nf3 {1 White}");
+    }
+}
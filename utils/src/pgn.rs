@@ -0,0 +1,378 @@
+//! Games in PGN
+//!
+//! Comments, NAGs, move numbers and the result are skipped. `parse_pgn`
+//! reads the main line of the first game of the text, `parse_pgn_lines`
+//! every line of every game, variations included. `write_pgn` writes
+//! a game back.
+
+use std::str::FromStr;
+
+use shakmaty::{
+    san::San,
+    variant::{Variant, VariantPosition},
+    Move, Position,
+};
+
+use crate::variant::parse_position;
+
+/// Tags and values, in their order in the PGN.
+pub type Headers = Vec<(String, String)>;
+
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub headers: Headers,
+    pub variant: Variant,
+    pub chess960: bool,
+    pub start: VariantPosition,
+    pub moves: Vec<Move>,
+}
+
+impl PgnGame {
+    pub fn header(&self, tag: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name == tag)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// As in the PGN Variant tag.
+pub fn variant_tag(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Chess => "Standard",
+        Variant::Atomic => "Atomic",
+        Variant::Antichess => "Antichess",
+        Variant::KingOfTheHill => "King of the Hill",
+        Variant::ThreeCheck => "Three-check",
+        Variant::Crazyhouse => "Crazyhouse",
+        Variant::RacingKings => "Racing Kings",
+        Variant::Horde => "Horde",
+    }
+}
+
+/// The variant of a Variant tag, and whether it is Chess960.
+fn from_variant_tag(tag: &str) -> Result<(Variant, bool), String> {
+    let name: String = tag
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    match name.as_str() {
+        "" | "standard" | "chess" | "fromposition" => Ok((Variant::Chess, false)),
+        "chess960" | "fischerandom" | "fischerrandom" => Ok((Variant::Chess, true)),
+        _ => Variant::ALL
+            .iter()
+            .find(|variant| {
+                let known: String = variant_tag(**variant)
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric())
+                    .collect();
+                known.to_lowercase() == name || variant.uci() == name
+            })
+            .map(|variant| (*variant, false))
+            .ok_or_else(|| format!("unknown variant '{tag}'")),
+    }
+}
+
+fn parse_header(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.trim_end().strip_suffix(']')?;
+    let (tag, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    Some((tag.to_string(), unescaped))
+}
+
+/// A header value between quotes, its quotes and backslashes escaped.
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// A game in PGN, from its headers, its movetext and its result.
+pub fn write_pgn<T: AsRef<str>, V: AsRef<str>>(
+    headers: &[(T, V)],
+    movetext: &str,
+    result: &str,
+) -> String {
+    let mut pgn: String = headers
+        .iter()
+        .map(|(tag, value)| format!("[{} \"{}\"]\n", tag.as_ref(), escape(value.as_ref())))
+        .collect();
+    pgn.push('\n');
+    if !movetext.is_empty() {
+        pgn.push_str(movetext);
+        pgn.push(' ');
+    }
+    pgn.push_str(result);
+    pgn.push('\n');
+    pgn
+}
+
+enum Token {
+    San(String),
+    /// Start of a variation.
+    Open,
+    /// End of a variation.
+    Close,
+}
+
+/// SAN tokens and variations, without comments, NAGs, move numbers
+/// and the result.
+fn tokens(movetext: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut chars = movetext.chars();
+    while let Some(c) = chars.next() {
+        if (matches!(c, '{' | ';' | '(' | ')' | '.') || c.is_whitespace()) && !token.is_empty() {
+            tokens.push(Token::San(std::mem::take(&mut token)));
+        }
+        match c {
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() || c == '.' => {}
+            c => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(Token::San(token));
+    }
+    tokens
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::San(san)
+                if san.starts_with('$')
+                    || san.chars().all(|c| c.is_ascii_digit())
+                    || matches!(san.as_str(), "1-0" | "0-1" | "1/2-1/2" | "½-½" | "*") =>
+            {
+                None
+            }
+            Token::San(san) => Some(Token::San(san.trim_end_matches(['!', '?']).to_string())),
+            token => Some(token),
+        })
+        .collect()
+}
+
+/// SAN tokens of the main line.
+pub(crate) fn main_line(movetext: &str) -> Vec<String> {
+    let mut depth = 0;
+    let mut line = Vec::new();
+    for token in tokens(movetext) {
+        match token {
+            Token::Open => depth += 1,
+            Token::Close => depth -= 1,
+            Token::San(san) if depth == 0 => line.push(san),
+            Token::San(_) => {}
+        }
+    }
+    line
+}
+
+/// SAN tokens of every line, from the start to the end of the main
+/// line or of a variation. A variation replaces the last move before it.
+fn all_lines(movetext: &str) -> Vec<Vec<String>> {
+    let mut lines: Vec<Vec<String>> = Vec::new();
+    let mut line = Vec::new();
+    let mut outer: Vec<Vec<String>> = Vec::new();
+    for token in tokens(movetext) {
+        match token {
+            Token::San(san) => line.push(san),
+            Token::Open => {
+                outer.push(line.clone());
+                line.pop();
+            }
+            Token::Close => {
+                if let Some(back) = outer.pop() {
+                    lines.push(std::mem::replace(&mut line, back));
+                }
+            }
+        }
+    }
+    lines.push(line);
+    // a line ending where another goes on is part of it
+    let ends = |line: &Vec<String>| {
+        !lines
+            .iter()
+            .any(|other| other.len() > line.len() && other.starts_with(line))
+    };
+    let mut leaves: Vec<Vec<String>> = Vec::new();
+    for line in lines.iter().filter(|line| !line.is_empty() && ends(line)) {
+        if !leaves.contains(line) {
+            leaves.push(line.clone());
+        }
+    }
+    leaves
+}
+
+/// Headers and movetext of each game of `pgn`.
+fn split_games(pgn: &str) -> Result<Vec<(Headers, String)>, String> {
+    let mut games = Vec::new();
+    let mut headers = Vec::new();
+    let mut movetext = String::new();
+    for line in pgn.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            if !movetext.trim().is_empty() {
+                // the next game
+                games.push((std::mem::take(&mut headers), std::mem::take(&mut movetext)));
+            }
+            let header =
+                parse_header(trimmed).ok_or_else(|| format!("invalid header {trimmed}"))?;
+            headers.push(header);
+        } else {
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if !headers.is_empty() || !movetext.trim().is_empty() {
+        games.push((headers, movetext));
+    }
+    Ok(games)
+}
+
+/// A game with no moves yet, from its headers.
+fn new_game(headers: Headers) -> Result<PgnGame, String> {
+    let header = |tag: &str| {
+        headers
+            .iter()
+            .find(|(name, _)| name == tag)
+            .map(|(_, value)| value.clone())
+    };
+
+    let (variant, chess960) = from_variant_tag(&header("Variant").unwrap_or_default())?;
+    let start = match header("FEN") {
+        Some(fen) => {
+            parse_position(&fen, variant, chess960)
+                .map_err(|err| format!("invalid FEN {fen}: {err}"))?
+                .0
+        }
+        None => VariantPosition::new(variant),
+    };
+    Ok(PgnGame {
+        headers,
+        variant,
+        chess960,
+        start,
+        moves: Vec::new(),
+    })
+}
+
+fn play_sans(start: &VariantPosition, sans: &[String]) -> Result<Vec<Move>, String> {
+    let mut position = start.clone();
+    let mut moves = Vec::new();
+    for (ply, token) in sans.iter().enumerate() {
+        let m = San::from_str(token)
+            .ok()
+            .and_then(|san| san.to_move(&position).ok())
+            .ok_or_else(|| format!("illegal move {token} at ply {}", ply + 1))?;
+        position.play_unchecked(&m);
+        moves.push(m);
+    }
+    Ok(moves)
+}
+
+pub fn parse_pgn(pgn: &str) -> Result<PgnGame, String> {
+    let (headers, movetext) = split_games(pgn)?.into_iter().next().unwrap_or_default();
+    let mut game = new_game(headers)?;
+    game.moves = play_sans(&game.start, &main_line(&movetext))?;
+    Ok(game)
+}
+
+/// Every line of every game of `pgn`, variations included, each as a
+/// game of its own with the headers of the game it comes from.
+pub fn parse_pgn_lines(pgn: &str) -> Result<Vec<PgnGame>, String> {
+    let mut games = Vec::new();
+    for (headers, movetext) in split_games(pgn)? {
+        let game = new_game(headers)?;
+        for sans in all_lines(&movetext) {
+            let moves = play_sans(&game.start, &sans)?;
+            games.push(PgnGame {
+                moves,
+                ..game.clone()
+            });
+        }
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_main_line() {
+        let game = parse_pgn(
+            "[Event \"Casual \\\"blitz\\\"\"]\n[White \"A\"]\n\n\
+             1. e4 {best by test} e5 (1... c5 2. Nf3) 2. Nf3!? $1 Nc6 ; a comment\n\
+             3. Bb5 a6 1/2-1/2\n",
+        )
+        .unwrap();
+        assert_eq!(game.header("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.moves.len(), 6);
+        assert_eq!(game.variant, Variant::Chess);
+    }
+
+    #[test]
+    fn starts_from_fen_and_variant() {
+        let game = parse_pgn(
+            "[Variant \"Three-check\"]\n\
+             [FEN \"rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1\"]\n\n\
+             1. e4 e5 *",
+        )
+        .unwrap();
+        assert_eq!(game.variant, Variant::ThreeCheck);
+        assert_eq!(game.moves.len(), 2);
+        assert!(parse_pgn("1. e4 e4").is_err());
+        assert!(parse_pgn("[Variant \"Shogi\"]\n\n*").is_err());
+    }
+
+    #[test]
+    fn reads_every_line() {
+        let lines = parse_pgn_lines(
+            "[Event \"White repertoire\"]\n\n\
+             1. e4 e5 (1... c5 2. Nf3 (2. c3) d6) 2. Nf3 Nc6 *\n\n\
+             [Event \"More\"]\n\n1. e4 e6 2. d4 *",
+        )
+        .unwrap();
+        let lengths: Vec<usize> = lines.iter().map(|line| line.moves.len()).collect();
+        assert_eq!(lengths, [3, 4, 4, 3]);
+        assert_eq!(lines[0].moves[1].to(), shakmaty::Square::C5);
+        assert_eq!(lines[3].header("Event"), Some("More"));
+        assert!(parse_pgn_lines("1. e4 (1. e5) *").is_err());
+    }
+
+    #[test]
+    fn writes_headers_back() {
+        let pgn = write_pgn(
+            &[("Event", "\"Blitz\" \\ rapid"), ("Result", "*")],
+            "1. e4",
+            "*",
+        );
+        assert_eq!(
+            pgn,
+            "[Event \"\\\"Blitz\\\" \\\\ rapid\"]\n[Result \"*\"]\n\n1. e4 *\n"
+        );
+        let game = parse_pgn(&pgn).unwrap();
+        assert_eq!(game.header("Event"), Some("\"Blitz\" \\ rapid"));
+        assert_eq!(write_pgn::<&str, &str>(&[], "", "*"), "\n*\n");
+    }
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use shakmaty::{Color, Move, Role, Square};
+use ts_rs::TS;
 
 #[rustfmt::skip]
 #[derive(Serialize, Deserialize)]
@@ -89,8 +90,38 @@ pub enum MoveDef {
     },
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct MoveSerde(#[serde(with = "MoveDef")] pub Move);
+// Moves as `MoveDef` writes them, for the TypeScript types only.
+#[derive(TS)]
+#[ts(rename = "Move", tag = "_tag")]
+#[allow(dead_code)]
+enum MoveType {
+    Normal {
+        role: RoleSerde,
+        from: SquareSerde,
+        capture: Option<RoleSerde>,
+        to: SquareSerde,
+        promotion: Option<RoleSerde>,
+    },
+    EnPassant {
+        from: SquareSerde,
+        to: SquareSerde,
+    },
+    Castle {
+        king: SquareSerde,
+        rook: SquareSerde,
+    },
+    Put {
+        role: RoleSerde,
+        to: SquareSerde,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, TS)]
+pub struct MoveSerde(
+    #[serde(with = "MoveDef")]
+    #[ts(as = "MoveType")]
+    pub Move,
+);
 
 impl From<Move> for MoveSerde {
     fn from(value: Move) -> Self {
@@ -104,7 +135,45 @@ impl From<MoveSerde> for Move {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Copy, TS)]
+#[ts(rename = "Square")]
+pub struct SquareSerde(
+    #[serde(with = "SquareDef")]
+    #[ts(
+        type = "\"A1\" | \"B1\" | \"C1\" | \"D1\" | \"E1\" | \"F1\" | \"G1\" | \"H1\" | \
+        \"A2\" | \"B2\" | \"C2\" | \"D2\" | \"E2\" | \"F2\" | \"G2\" | \"H2\" | \
+        \"A3\" | \"B3\" | \"C3\" | \"D3\" | \"E3\" | \"F3\" | \"G3\" | \"H3\" | \
+        \"A4\" | \"B4\" | \"C4\" | \"D4\" | \"E4\" | \"F4\" | \"G4\" | \"H4\" | \
+        \"A5\" | \"B5\" | \"C5\" | \"D5\" | \"E5\" | \"F5\" | \"G5\" | \"H5\" | \
+        \"A6\" | \"B6\" | \"C6\" | \"D6\" | \"E6\" | \"F6\" | \"G6\" | \"H6\" | \
+        \"A7\" | \"B7\" | \"C7\" | \"D7\" | \"E7\" | \"F7\" | \"G7\" | \"H7\" | \
+        \"A8\" | \"B8\" | \"C8\" | \"D8\" | \"E8\" | \"F8\" | \"G8\" | \"H8\""
+    )]
+    pub Square,
+);
+
+impl From<Square> for SquareSerde {
+    fn from(value: Square) -> Self {
+        SquareSerde(value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, TS)]
+#[ts(rename = "Role")]
+pub struct RoleSerde(
+    #[serde(with = "RoleDef")]
+    #[ts(type = "\"Pawn\" | \"Knight\" | \"Bishop\" | \"Rook\" | \"Queen\" | \"King\"")]
+    pub Role,
+);
+
+impl From<Role> for RoleSerde {
+    fn from(value: Role) -> Self {
+        RoleSerde(value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, TS)]
+#[ts(rename = "Color")]
 pub enum ColorSerde {
     #[serde(rename = "white")]
     White,
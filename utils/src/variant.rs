@@ -0,0 +1,199 @@
+//! Chess variants, as implemented by `shakmaty::variant`
+//!
+//! Variants are named as in the `UCI_Variant` option, e.g. "crazyhouse"
+//! or "3check".
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use shakmaty::{
+    fen::Fen,
+    variant::{Variant, VariantPosition},
+    CastlingMode, EnPassantMode, Position,
+};
+use ts_rs::TS;
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Variant")]
+pub enum VariantDef {
+    #[serde(rename = "chess")]
+    Chess,
+    #[serde(rename = "atomic")]
+    Atomic,
+    #[serde(rename = "antichess")]
+    Antichess,
+    #[serde(rename = "kingofthehill")]
+    KingOfTheHill,
+    #[serde(rename = "3check")]
+    ThreeCheck,
+    #[serde(rename = "crazyhouse")]
+    Crazyhouse,
+    #[serde(rename = "racingkings")]
+    RacingKings,
+    #[serde(rename = "horde")]
+    Horde,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, TS)]
+#[ts(rename = "Variant")]
+pub struct VariantSerde(
+    #[serde(with = "VariantDef")]
+    #[ts(
+        type = "\"chess\" | \"atomic\" | \"antichess\" | \"kingofthehill\" | \"3check\" | \
+        \"crazyhouse\" | \"racingkings\" | \"horde\""
+    )]
+    pub Variant,
+);
+
+impl Default for VariantSerde {
+    fn default() -> Self {
+        VariantSerde(Variant::Chess)
+    }
+}
+
+impl From<Variant> for VariantSerde {
+    fn from(value: Variant) -> Self {
+        VariantSerde(value)
+    }
+}
+
+impl From<VariantSerde> for Variant {
+    fn from(val: VariantSerde) -> Self {
+        val.0
+    }
+}
+
+/// Pieces in hand, in crazyhouse.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, TS)]
+#[ts(rename = "Pocket")]
+pub struct PocketSerde {
+    pub pawn: u8,
+    pub knight: u8,
+    pub bishop: u8,
+    pub rook: u8,
+    pub queen: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, TS)]
+#[ts(rename = "Pockets")]
+pub struct PocketsSerde {
+    pub white: PocketSerde,
+    pub black: PocketSerde,
+}
+
+/// Checks left before winning, in three-check.
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq, TS)]
+#[ts(rename = "RemainingChecks")]
+pub struct RemainingChecksSerde {
+    pub white: u32,
+    pub black: u32,
+}
+
+/// The FEN of a position, with pockets and remaining checks when the
+/// variant has them.
+pub fn fen(game: &VariantPosition) -> String {
+    Fen::from_position(game.clone(), EnPassantMode::Legal).to_string()
+}
+
+pub fn pockets(game: &VariantPosition) -> Option<PocketsSerde> {
+    game.pockets().map(|pockets| {
+        let pocket = |p: &shakmaty::ByRole<u8>| PocketSerde {
+            pawn: p.pawn,
+            knight: p.knight,
+            bishop: p.bishop,
+            rook: p.rook,
+            queen: p.queen,
+        };
+        PocketsSerde {
+            white: pocket(&pockets.white),
+            black: pocket(&pockets.black),
+        }
+    })
+}
+
+pub fn remaining_checks(game: &VariantPosition) -> Option<RemainingChecksSerde> {
+    game.remaining_checks().map(|checks| RemainingChecksSerde {
+        white: u32::from(checks.white),
+        black: u32::from(checks.black),
+    })
+}
+
+/// Parses a position of `variant` from a FEN, X-FEN or Shredder-FEN,
+/// with pockets and remaining checks when the variant has them.
+/// Castling rights only possible in Chess960 switch to Chess960 even
+/// if not asked to.
+pub fn parse_position(
+    fen: &str,
+    variant: Variant,
+    chess960: bool,
+) -> Result<(VariantPosition, CastlingMode), String> {
+    let setup = Fen::from_str(fen.trim())
+        .map_err(|err| err.to_string())?
+        .into_setup();
+    let mode = CastlingMode::from_chess960(chess960);
+    match VariantPosition::from_setup(variant, setup.clone(), mode) {
+        Ok(game) => Ok((game, mode)),
+        Err(err) if mode == CastlingMode::Standard => {
+            VariantPosition::from_setup(variant, setup, CastlingMode::Chess960)
+                .map(|game| (game, CastlingMode::Chess960))
+                .map_err(|_| err.to_string())
+        }
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shakmaty::{Color, Move, Role, Square};
+
+    #[test]
+    fn names_are_uci_names() {
+        for variant in Variant::ALL {
+            let json = serde_json::to_string(&VariantSerde(variant)).unwrap();
+            assert_eq!(json, format!("\"{}\"", variant.uci()));
+        }
+    }
+
+    #[test]
+    fn crazyhouse_pockets() {
+        let (game, _) = parse_position(
+            "rnb1kbnr/ppp1pppp/8/3q4/8/8/PPPP1PPP/RNBQKBNR[Pp] w KQkq - 0 3",
+            Variant::Crazyhouse,
+            false,
+        )
+        .unwrap();
+        let expected = PocketSerde {
+            pawn: 1,
+            ..PocketSerde::default()
+        };
+        assert_eq!(
+            pockets(&game),
+            Some(PocketsSerde {
+                white: expected.clone(),
+                black: expected,
+            })
+        );
+        let drop = Move::Put {
+            role: Role::Pawn,
+            to: Square::E4,
+        };
+        assert!(game.is_legal(&drop));
+        assert_eq!(game.turn(), Color::White);
+    }
+
+    #[test]
+    fn three_check_counts() {
+        let (game, _) = parse_position(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+2 0 1",
+            Variant::ThreeCheck,
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            remaining_checks(&game),
+            Some(RemainingChecksSerde { white: 3, black: 2 })
+        );
+        assert_eq!(pockets(&game), None);
+    }
+}